@@ -1,9 +1,17 @@
 import * as vscode from "vscode"
+import { LanguageClient, LanguageClientOptions, ServerOptions } from "vscode-languageclient/node"
 import { GoboscriptTaskProvider } from "./goboscript-task-provider"
 
 let goboscriptTaskProvider: vscode.Disposable | undefined
+let client: LanguageClient | undefined
 
 export function activate(context: vscode.ExtensionContext): void {
+  const serverOptions: ServerOptions = { command: "goboscript", args: ["lsp"] }
+  const clientOptions: LanguageClientOptions = {
+    documentSelector: [{ scheme: "file", language: "goboscript" }],
+  }
+  client = new LanguageClient("goboscript", "goboscript", serverOptions, clientOptions)
+  void client.start()
   const workspaceRoot =
     vscode.workspace.workspaceFolders && vscode.workspace.workspaceFolders.length > 0 ?
       vscode.workspace.workspaceFolders[0]
@@ -14,3 +22,8 @@ export function activate(context: vscode.ExtensionContext): void {
     new GoboscriptTaskProvider(workspaceRoot),
   )
 }
+
+export function deactivate(): Thenable<void> | undefined {
+  goboscriptTaskProvider?.dispose()
+  return client?.stop()
+}
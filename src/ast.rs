@@ -1,4 +1,5 @@
 mod arg;
+mod const_expr;
 mod costume;
 mod enum_;
 mod enum_variant;
@@ -6,6 +7,7 @@ mod event;
 mod event_kind;
 mod expr;
 mod func;
+mod import;
 mod kwarg;
 mod list;
 mod name;
@@ -16,11 +18,13 @@ mod stmt;
 mod struct_;
 mod struct_field;
 mod struct_literal_field;
+mod test;
 mod type_;
 mod value;
 mod var;
 
 pub use arg::*;
+pub use const_expr::*;
 pub use costume::*;
 pub use enum_::*;
 pub use enum_variant::*;
@@ -28,6 +32,7 @@ pub use event::*;
 pub use event_kind::*;
 pub use expr::*;
 pub use func::*;
+pub use import::*;
 pub use kwarg::*;
 pub use list::*;
 pub use name::*;
@@ -38,6 +43,7 @@ pub use stmt::*;
 pub use struct_::*;
 pub use struct_field::*;
 pub use struct_literal_field::*;
+pub use test::*;
 pub use type_::*;
 pub use value::*;
 pub use var::*;
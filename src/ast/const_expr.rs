@@ -0,0 +1,33 @@
+use logos::Span;
+use smol_str::SmolStr;
+
+use super::value::Value;
+
+#[derive(Debug)]
+pub enum ConstExpr {
+    Value {
+        value: Value,
+        span: Span,
+    },
+    StructLiteral {
+        name: SmolStr,
+        span: Span,
+        fields: Vec<ConstStructField>,
+    },
+}
+
+#[derive(Debug)]
+pub struct ConstStructField {
+    pub name: SmolStr,
+    pub span: Span,
+    pub value: Value,
+}
+
+impl ConstExpr {
+    pub fn span(&self) -> &Span {
+        match self {
+            Self::Value { span, .. } => span,
+            Self::StructLiteral { span, .. } => span,
+        }
+    }
+}
@@ -0,0 +1,10 @@
+use logos::Span;
+use smol_str::SmolStr;
+
+#[derive(Debug)]
+pub struct Import {
+    pub path: SmolStr,
+    pub span: Span,
+    pub alias: SmolStr,
+    pub alias_span: Span,
+}
@@ -1,7 +1,7 @@
 use logos::Span;
 use smol_str::SmolStr;
 
-use super::type_::Type;
+use super::{const_expr::ConstExpr, type_::Type};
 
 #[derive(Debug)]
 pub struct List {
@@ -9,6 +9,7 @@ pub struct List {
     pub span: Span,
     pub type_: Type,
     pub cmd: Option<Cmd>,
+    pub initial_items: Vec<ConstExpr>,
 }
 
 #[derive(Debug)]
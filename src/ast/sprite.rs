@@ -1,9 +1,9 @@
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use smol_str::SmolStr;
 
 use super::{
     costume::Costume, enum_::Enum, event::Event, list::List, proc::Proc, struct_::Struct, var::Var,
-    Func,
+    Func, Import, Test,
 };
 
 #[derive(Debug, Default)]
@@ -16,4 +16,8 @@ pub struct Sprite {
     pub vars: FxHashMap<SmolStr, Var>,
     pub lists: FxHashMap<SmolStr, List>,
     pub events: Vec<Event>,
+    pub tests: Vec<Test>,
+    pub imports: Vec<Import>,
+    pub exports: FxHashSet<SmolStr>,
+    pub private: FxHashSet<SmolStr>,
 }
@@ -77,6 +77,11 @@ pub enum Stmt {
     Return {
         value: Rrc<Expr>,
     },
+    Assert {
+        cond: Rrc<Expr>,
+        message: Option<Rrc<Expr>>,
+        span: Span,
+    },
 }
 
 impl Stmt {
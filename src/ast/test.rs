@@ -0,0 +1,26 @@
+use fxhash::FxHashMap;
+use logos::Span;
+use smol_str::SmolStr;
+
+use super::{stmt::Stmt, var::Var};
+
+#[derive(Debug)]
+pub struct Test {
+    pub name: SmolStr,
+    pub span: Span,
+    /// Variables which are first set in the test. They are local to it, so that they are
+    /// not declared in builds, which leave tests out.
+    pub locals: FxHashMap<SmolStr, Var>,
+    pub body: Vec<Stmt>,
+}
+
+impl Test {
+    pub fn new(name: SmolStr, span: Span, body: Vec<Stmt>) -> Self {
+        Self {
+            name,
+            span,
+            locals: FxHashMap::default(),
+            body,
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use logos::Span;
 use smol_str::SmolStr;
 
-use super::type_::Type;
+use super::{const_expr::ConstExpr, type_::Type};
 
 #[derive(Debug)]
 pub struct Var {
@@ -9,4 +9,5 @@ pub struct Var {
     pub span: Span,
     pub type_: Type,
     pub is_cloud: bool,
+    pub initial_value: Option<ConstExpr>,
 }
@@ -1,11 +1,15 @@
-mod cmd;
+pub mod cmd;
+pub mod const_expr;
+mod data;
 mod event;
 mod expr;
 mod input;
+mod monitor;
 mod mutation;
 mod node;
 mod node_id;
 mod node_id_factory;
+mod properties;
 pub mod sb3;
 mod stmt;
 pub mod turbowarp_config;
@@ -1,18 +1,50 @@
 use std::{
-    env::consts::OS,
-    fs::File,
-    io::{self, BufRead, BufReader, Write},
+    collections::BTreeSet,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Write},
     path::Path,
     process::{Command, Stdio},
     str,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
+use md5::{Digest, Md5};
+
+use super::data::{self, Format};
 use crate::{
-    ast::Cmd,
+    ast::{Cmd, Struct},
+    config::{CacheMode, Config},
     diagnostic::{Diagnostic, DiagnosticKind},
 };
 
-pub fn cmd_to_list(cmd: &Cmd, input: &Path) -> Result<Vec<String>, Diagnostic> {
+const CACHE_DIR: &str = ".goboscript/cache";
+
+const DEFAULT_TIMEOUT: f64 = 60.0;
+
+pub fn cmd_to_list(
+    cmd: &Cmd,
+    input: &Path,
+    struct_: Option<&Struct>,
+    config: &Config,
+) -> Result<Vec<String>, Diagnostic> {
+    if let Some(format) = cmd
+        .program
+        .as_ref()
+        .and_then(|program| Format::from_program(&program.name))
+    {
+        let text = fs::read_to_string(input.join(&cmd.cmd)).map_err(|err| Diagnostic {
+            kind: match err.kind() {
+                io::ErrorKind::NotFound => DiagnosticKind::FileNotFound(cmd.cmd.clone()),
+                _ => DiagnosticKind::InvalidListData(err.to_string()),
+            },
+            span: cmd.span.clone(),
+        })?;
+        return data::load(format, &text, struct_).map_err(|kind| Diagnostic {
+            kind,
+            span: cmd.span.clone(),
+        });
+    }
     if cmd
         .program
         .as_ref()
@@ -28,55 +60,157 @@ pub fn cmd_to_list(cmd: &Cmd, input: &Path) -> Result<Vec<String>, Diagnostic> {
         let lines: Vec<String> = reader.lines().map_while(Result::ok).collect();
         return Ok(lines);
     }
-    let mut child = if let Some(program) = &cmd.program {
-        let command = Command::new(&program.name)
-            .current_dir(input)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn();
-        if let Err(err) = &command {
-            if err.kind() == io::ErrorKind::NotFound {
+    let cache_path = (config.cache.unwrap_or(true) && config.cache_mode != CacheMode::Off)
+        .then(|| input.join(CACHE_DIR).join(cache_key(cmd, input)));
+    let cached = cache_path.as_ref().and_then(|path| fs::read(path).ok());
+    let stdout = match cached {
+        Some(stdout) => stdout,
+        None => {
+            if !config.allow_exec.unwrap_or(true) {
                 return Err(Diagnostic {
-                    kind: DiagnosticKind::FileNotFound(program.name.clone()),
-                    span: program.span.clone(),
+                    kind: DiagnosticKind::CommandNotAllowed,
+                    span: cmd.span.clone(),
                 });
-            };
+            }
+            let timeout = config.command_timeout.unwrap_or(DEFAULT_TIMEOUT);
+            let timeout = Duration::try_from_secs_f64(timeout).unwrap_or(Duration::MAX);
+            let stdout = run(cmd, input, timeout)?;
+            // The cache only saves time, so a build does not fail if it cannot be written.
+            if let Some(path) = cache_path
+                .as_ref()
+                .filter(|_| config.cache_mode == CacheMode::ReadWrite)
+            {
+                let _ = write_cache(path, &stdout);
+            }
+            stdout
+        }
+    };
+    let mut lines: Vec<String> = stdout
+        .split(|&b| b == b'\n')
+        .map(|line| str::from_utf8(line).unwrap_or_default().to_owned())
+        .collect();
+    if lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    Ok(lines)
+}
+
+/// The output of a command is kept in the project directory, and used instead of running
+/// it again until the command, its program or a file it names changes. Files are only
+/// found by splitting the command into words, so files which it reads through a glob, a
+/// variable or another program are not noticed, and neither is anything else which
+/// changes its output, such as the time for `date` or the commit for `git rev-parse HEAD`.
+fn cache_key(cmd: &Cmd, input: &Path) -> String {
+    let mut hasher = Md5::new();
+    let program = cmd.program.as_ref().map_or("bash", |program| &program.name);
+    hasher.update(program.as_bytes());
+    hasher.update([0]);
+    hasher.update(cmd.cmd.as_bytes());
+    // Any word of the command which is the path of a file in the project directory, such
+    // as `data.txt` in `cat data.txt`, is taken to be a file it reads.
+    let words: BTreeSet<&str> = cmd
+        .cmd
+        .split(|c: char| !(c.is_alphanumeric() || matches!(c, '/' | '.' | '_' | '-')))
+        .filter(|word| !word.is_empty())
+        .collect();
+    for word in words {
+        let path = input.join(word);
+        if !path.is_file() {
+            continue;
+        }
+        if let Ok(contents) = fs::read(&path) {
+            hasher.update([0]);
+            hasher.update(word.as_bytes());
+            hasher.update([0]);
+            hasher.update(Md5::digest(contents));
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Written to a temporary file first, so that a build which is stopped while writing it
+/// does not leave a partial output in the cache.
+fn write_cache(path: &Path, stdout: &[u8]) -> io::Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, stdout)?;
+    fs::rename(&temp_path, path)
+}
+
+fn run(cmd: &Cmd, input: &Path, timeout: Duration) -> Result<Vec<u8>, Diagnostic> {
+    let failed = |reason: String, stderr: Vec<u8>| Diagnostic {
+        kind: DiagnosticKind::CommandFailed { reason, stderr },
+        span: cmd.span.clone(),
+    };
+    let mut command = match &cmd.program {
+        Some(program) => Command::new(&program.name),
+        None => {
+            let mut command = Command::new("bash");
+            command.args(["-e", "-u", "-o", "pipefail"]);
+            command
+        }
+    };
+    let spawned = command
+        .current_dir(input)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match (spawned, &cmd.program) {
+        (Ok(child), _) => child,
+        (Err(err), Some(program)) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(Diagnostic {
+                kind: DiagnosticKind::FileNotFound(program.name.clone()),
+                span: program.span.clone(),
+            });
+        }
+        (Err(err), program) => {
+            let program = program.as_ref().map_or("bash", |program| &program.name);
+            return Err(failed(format!("could not start {program}: {err}"), vec![]));
         }
-        command.unwrap()
-    } else if OS == "windows" {
-        unimplemented!()
-    } else {
-        Command::new("bash")
-            .args(["-e", "-u", "-o", "pipefail"])
-            .current_dir(input)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .unwrap()
     };
     let mut stdin = child.stdin.take().unwrap();
-    stdin.write_all(cmd.cmd.as_bytes()).unwrap();
-    stdin.flush().unwrap();
-    drop(stdin);
-    let output = child.wait_with_output().unwrap();
-    if output.status.success() {
-        let mut lines: Vec<String> = output
-            .stdout
-            .split(|&b| b == b'\n')
-            .map(|line| str::from_utf8(line).unwrap_or_default().to_owned())
-            .collect();
-        if lines.last().is_some_and(|line| line.is_empty()) {
-            lines.pop();
+    let script = cmd.cmd.to_string();
+    // A command which exits without reading all of its input has not failed because of
+    // it, so errors writing it are ignored.
+    thread::spawn(move || {
+        let _ = stdin.write_all(script.as_bytes());
+    });
+    let stdout = read_in_background(child.stdout.take().unwrap());
+    let stderr = read_in_background(child.stderr.take().unwrap());
+    // A timeout too long to add to the time now is no timeout.
+    let deadline = Instant::now().checked_add(timeout);
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(failed(
+                    format!("timed out after {} seconds", timeout.as_secs_f64()),
+                    vec![],
+                ));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(err) => return Err(failed(err.to_string(), vec![])),
         }
-        Ok(lines)
-    } else {
-        Err(Diagnostic {
-            kind: DiagnosticKind::CommandFailed {
-                stderr: output.stderr,
-            },
-            span: cmd.span.clone(),
-        })
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if status.success() {
+        return Ok(stdout);
     }
+    let reason = match status.code() {
+        Some(code) => format!("exited with status {code}"),
+        None => "was stopped by a signal".to_owned(),
+    };
+    Err(failed(reason, stderr))
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
 }
@@ -0,0 +1,90 @@
+use crate::{
+    ast::{ConstExpr, Sprite, Type, Value},
+    diagnostic::{suggest, Diagnostic, DiagnosticKind},
+};
+
+pub fn const_values(
+    sprite: &Sprite,
+    type_: &Type,
+    expr: &ConstExpr,
+) -> Result<Vec<Value>, Diagnostic> {
+    match (type_, expr) {
+        (Type::Value, ConstExpr::Value { value, .. }) => Ok(vec![value.clone()]),
+        (Type::Value, ConstExpr::StructLiteral { name, span, .. }) => Err(Diagnostic {
+            kind: DiagnosticKind::TypeMismatch {
+                expected: Type::Value,
+                given: Type::Struct {
+                    name: name.clone(),
+                    span: span.clone(),
+                },
+            },
+            span: span.clone(),
+        }),
+        (Type::Struct { .. }, ConstExpr::Value { span, .. }) => Err(Diagnostic {
+            kind: DiagnosticKind::TypeMismatch {
+                expected: type_.clone(),
+                given: Type::Value,
+            },
+            span: span.clone(),
+        }),
+        (
+            Type::Struct {
+                name: type_name,
+                span: type_span,
+            },
+            ConstExpr::StructLiteral { name, span, fields },
+        ) => {
+            if name != type_name {
+                return Err(Diagnostic {
+                    kind: DiagnosticKind::TypeMismatch {
+                        expected: type_.clone(),
+                        given: Type::Struct {
+                            name: name.clone(),
+                            span: span.clone(),
+                        },
+                    },
+                    span: span.clone(),
+                });
+            }
+            let Some(struct_) = sprite.structs.get(type_name) else {
+                return Err(Diagnostic {
+                    kind: DiagnosticKind::UnrecognizedStruct(type_name.clone()),
+                    span: type_span.clone(),
+                });
+            };
+            if let Some(field) = fields
+                .iter()
+                .find(|field| !struct_.fields.iter().any(|f| f.name == field.name))
+            {
+                return Err(Diagnostic {
+                    kind: DiagnosticKind::UnrecognizedStructField {
+                        name: field.name.clone(),
+                        suggestion: suggest(
+                            &field.name,
+                            struct_.fields.iter().map(|field| field.name.as_str()),
+                        ),
+                    },
+                    span: field.span.clone(),
+                });
+            }
+            Ok(struct_
+                .fields
+                .iter()
+                .map(|struct_field| {
+                    fields
+                        .iter()
+                        .find(|field| field.name == struct_field.name)
+                        .map_or(Value::Int(0), |field| field.value.clone())
+                })
+                .collect())
+        }
+    }
+}
+
+pub fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Int(int) => (*int).into(),
+        Value::Float(float) => (*float).into(),
+        Value::String(string) => string.as_str().into(),
+    }
+}
@@ -0,0 +1,178 @@
+use serde_json::Value;
+
+use crate::{ast::Struct, diagnostic::DiagnosticKind};
+
+#[derive(Debug, Copy, Clone)]
+pub enum Format {
+    Csv,
+    Tsv,
+    Json,
+}
+
+impl Format {
+    pub fn from_program(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(Self::Csv),
+            "tsv" => Some(Self::Tsv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+pub fn load(
+    format: Format,
+    text: &str,
+    struct_: Option<&Struct>,
+) -> Result<Vec<String>, DiagnosticKind> {
+    match format {
+        Format::Csv => table(csv_rows(text)?, struct_),
+        Format::Tsv => table(tsv_rows(text), struct_),
+        Format::Json => json(text, struct_),
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> DiagnosticKind {
+    DiagnosticKind::InvalidListData(reason.into())
+}
+
+fn csv_rows(text: &str) -> Result<Vec<Vec<String>>, DiagnosticKind> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(invalid("a quoted field is not closed"));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn tsv_rows(text: &str) -> Vec<Vec<String>> {
+    text.lines()
+        .map(|line| line.split('\t').map(str::to_owned).collect())
+        .collect()
+}
+
+fn table(rows: Vec<Vec<String>>, struct_: Option<&Struct>) -> Result<Vec<String>, DiagnosticKind> {
+    let mut rows = rows.into_iter();
+    let Some(header) = rows.next() else {
+        return Ok(vec![]);
+    };
+    let columns = match struct_ {
+        None if header.len() == 1 => vec![0],
+        None => {
+            return Err(invalid(format!(
+                "it has {} columns, but only struct lists can be loaded from more than one \
+                 column",
+                header.len()
+            )))
+        }
+        Some(struct_) => struct_
+            .fields
+            .iter()
+            .map(|field| {
+                header
+                    .iter()
+                    .position(|column| column.trim() == field.name)
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "it has no column named {:?}, for field {} of struct {}",
+                            field.name, field.name, struct_.name
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<usize>, DiagnosticKind>>()?,
+    };
+    let mut items = vec![];
+    for (index, row) in rows.enumerate() {
+        for &column in &columns {
+            let Some(item) = row.get(column) else {
+                return Err(invalid(format!(
+                    "row {} has {} columns, but the first row has {}",
+                    index + 2,
+                    row.len(),
+                    header.len()
+                )));
+            };
+            items.push(item.clone());
+        }
+    }
+    Ok(items)
+}
+
+fn json(text: &str, struct_: Option<&Struct>) -> Result<Vec<String>, DiagnosticKind> {
+    let value: Value =
+        serde_json::from_str(text).map_err(|err| invalid(format!("it is not JSON: {err}")))?;
+    let Value::Array(values) = value else {
+        return Err(invalid("it is not a JSON array"));
+    };
+    let mut items = vec![];
+    for (index, value) in values.iter().enumerate() {
+        let Some(struct_) = struct_ else {
+            items.push(json_item(value).ok_or_else(|| {
+                invalid(format!(
+                    "the item at index {index} is not a number, a string or a boolean"
+                ))
+            })?);
+            continue;
+        };
+        let Value::Object(object) = value else {
+            return Err(invalid(format!(
+                "the item at index {index} is not an object, for struct {}",
+                struct_.name
+            )));
+        };
+        for field in &struct_.fields {
+            let Some(value) = object.get(field.name.as_str()) else {
+                return Err(invalid(format!(
+                    "the item at index {index} has no key {:?}, for field {} of struct {}",
+                    field.name, field.name, struct_.name
+                )));
+            };
+            items.push(json_item(value).ok_or_else(|| {
+                invalid(format!(
+                    "{:?} of the item at index {index} is not a number, a string or a boolean",
+                    field.name
+                ))
+            })?);
+        }
+    }
+    Ok(items)
+}
+
+fn json_item(value: &Value) -> Option<String> {
+    match value {
+        Value::Bool(bool) => Some(bool.to_string()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::String(string) => Some(string.clone()),
+        _ => None,
+    }
+}
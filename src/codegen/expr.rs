@@ -1,4 +1,4 @@
-use std::io::{self, Seek, Write};
+use std::io::{Seek, Write};
 
 use logos::Span;
 use smol_str::SmolStr;
@@ -7,26 +7,20 @@ use super::{
     mutation::Mutation,
     node::Node,
     node_id::NodeID,
-    sb3::{qualify_struct_var_name, QualifiedName, Sb3, D, S},
+    sb3::{check_struct_literal_fields, qualify_struct_var_name, QualifiedName, Sb3, D, S},
 };
 use crate::{
     ast::*,
     blocks::{BinOp, Repr, UnOp},
-    diagnostic::DiagnosticKind,
-    misc::{write_comma_io, Rrc},
+    diagnostic::{suggest, DiagnosticKind},
+    misc::Rrc,
+    sb3,
 };
 
 impl<T> Sb3<T>
 where T: Write + Seek
 {
-    pub fn arg(
-        &mut self,
-        s: S,
-        d: D,
-        this_id: NodeID,
-        parent_id: NodeID,
-        name: &Name,
-    ) -> io::Result<()> {
+    pub fn arg(&mut self, s: S, d: D, this_id: NodeID, parent_id: NodeID, name: &Name) {
         let basename = name.basename();
 
         if !(s
@@ -39,18 +33,15 @@ where T: Write + Seek
                 DiagnosticKind::UnrecognizedArgument(basename.clone()),
                 &name.span(),
             );
-            return Ok(());
+            return;
         }
 
         let qualified_name = match name.fieldname() {
             Some(fieldname) => qualify_struct_var_name(fieldname, basename),
             None => basename.clone(),
         };
-        self.begin_node(
-            Node::new("argument_reporter_string_number", this_id).parent_id(parent_id),
-        )?;
-        self.single_field("VALUE", &qualified_name)?;
-        self.end_obj() // node
+        self.add_node(Node::new("argument_reporter_string_number", this_id).parent_id(parent_id));
+        self.single_field("VALUE", &qualified_name);
     }
 
     pub fn repr(
@@ -62,16 +53,15 @@ where T: Write + Seek
         repr: &Repr,
         span: &Span,
         args: &Vec<Rrc<Expr>>,
-    ) -> io::Result<()> {
+    ) {
         if args.len() != repr.args().len() {
             todo!()
         }
-        self.begin_node(Node::new(repr.opcode(), this_id).parent_id(parent_id))?;
+        self.add_node(Node::new(repr.opcode(), this_id).parent_id(parent_id));
         let arg_ids: Vec<NodeID> = (&mut self.id).take(args.len()).collect();
         let menu_id = repr.menu().map(|_| self.id.new_id());
         let mut menu_value = None;
         let mut menu_is_default = menu_id.is_some();
-        self.begin_inputs()?;
         for ((&arg_name, arg_value), &arg_id) in repr.args().iter().zip(args).zip(&arg_ids) {
             if repr.menu().is_some_and(|menu| menu.input == arg_name) {
                 if let Expr::Value { value, span: _ } = &*arg_value.borrow() {
@@ -86,43 +76,36 @@ where T: Write + Seek
                         &arg_value.borrow(),
                         arg_id,
                         menu_id.unwrap(),
-                    )?;
+                    );
                 }
             } else {
-                self.input(s, d, arg_name, &arg_value.borrow(), arg_id)?;
+                self.input(s, d, arg_name, &arg_value.borrow(), arg_id);
             }
         }
         if menu_is_default {
-            write_comma_io(&mut self.zip, &mut self.inputs_comma)?;
-            write!(
-                self,
-                r#""{}":[1,{}]"#,
+            self.set_input(
                 repr.menu().unwrap().input,
-                menu_id.unwrap()
-            )?;
+                sb3::Input::Shadow(Some(menu_id.unwrap().into())),
+            );
         }
-        self.end_obj()?; // inputs
         if let Some(fields) = repr.fields() {
-            write!(self, r#","fields":{fields}"#)?;
+            self.fields(fields);
         }
-        self.end_obj()?; // node
         for (arg, arg_id) in args.iter().zip(arg_ids) {
-            self.expr(s, d, &arg.borrow(), arg_id, this_id)?;
+            self.expr(s, d, &arg.borrow(), arg_id, this_id);
         }
         if let Some(menu) = repr.menu() {
-            self.begin_node(
+            self.add_node(
                 Node::new(menu.opcode, menu_id.unwrap())
                     .parent_id(this_id)
                     .shadow(true),
-            )?;
+            );
             if let Some(menu_value) = menu_value {
-                self.single_field(menu.input, &menu_value.to_string())?;
+                self.single_field(menu.input, &menu_value.to_string());
             } else {
-                self.single_field(menu.input, menu.default)?;
+                self.single_field(menu.input, menu.default);
             }
-            self.end_obj()?; // node
         }
-        Ok(())
     }
 
     pub fn un_op(
@@ -134,7 +117,7 @@ where T: Write + Seek
         op: &UnOp,
         _span: &Span,
         opr: &Rrc<Expr>,
-    ) -> io::Result<()> {
+    ) {
         if matches!(op, UnOp::Length) {
             if let Expr::Name(Name::Name { name, .. }) = &*opr.borrow() {
                 if s.sprite.lists.contains_key(name)
@@ -145,14 +128,11 @@ where T: Write + Seek
             }
         }
         let opr_id = self.id.new_id();
-        self.begin_node(Node::new(op.opcode(), this_id).parent_id(parent_id))?;
-        self.begin_inputs()?;
-        self.input(s, d, op.input(), &opr.borrow(), opr_id)?;
-        self.end_obj()?; // inputs
+        self.add_node(Node::new(op.opcode(), this_id).parent_id(parent_id));
+        self.input(s, d, op.input(), &opr.borrow(), opr_id);
         if let Some(fields) = op.fields() {
-            write!(self, r#","fields":{fields}"#)?;
+            self.fields(fields);
         }
-        self.end_obj()?; // node
         self.expr(s, d, &opr.borrow(), opr_id, this_id)
     }
 
@@ -166,7 +146,7 @@ where T: Write + Seek
         _span: &Span,
         lhs: &Rrc<Expr>,
         rhs: &Rrc<Expr>,
-    ) -> io::Result<()> {
+    ) {
         if let BinOp::Of = op {
             if let Expr::Name(name) = &*lhs.borrow() {
                 if let Some(QualifiedName::List(qualified_name, _)) = s.qualify_name(d, name) {
@@ -176,13 +156,10 @@ where T: Write + Seek
         }
         let lhs_id = self.id.new_id();
         let rhs_id = self.id.new_id();
-        self.begin_node(Node::new(op.opcode(), this_id).parent_id(parent_id))?;
-        self.begin_inputs()?;
-        self.input(s, d, op.lhs(), &lhs.borrow(), lhs_id)?;
-        self.input(s, d, op.rhs(), &rhs.borrow(), rhs_id)?;
-        self.end_obj()?; // inputs
-        self.end_obj()?; // node
-        self.expr(s, d, &lhs.borrow(), lhs_id, this_id)?;
+        self.add_node(Node::new(op.opcode(), this_id).parent_id(parent_id));
+        self.input(s, d, op.lhs(), &lhs.borrow(), lhs_id);
+        self.input(s, d, op.rhs(), &rhs.borrow(), rhs_id);
+        self.expr(s, d, &lhs.borrow(), lhs_id, this_id);
         self.expr(s, d, &rhs.borrow(), rhs_id, this_id)
     }
 
@@ -194,34 +171,24 @@ where T: Write + Seek
         parent_id: NodeID,
         name: &str,
         index: &Rrc<Expr>,
-    ) -> io::Result<()> {
+    ) {
         let index_id = self.id.new_id();
-        self.begin_node(Node::new("data_itemoflist", this_id).parent_id(parent_id))?;
-        self.begin_inputs()?;
-        self.input(s, d, "INDEX", &index.borrow(), index_id)?;
-        self.end_obj()?; // inputs
-        self.single_field_id("LIST", name)?;
-        self.end_obj()?; // node
+        self.add_node(Node::new("data_itemoflist", this_id).parent_id(parent_id));
+        self.input(s, d, "INDEX", &index.borrow(), index_id);
+        self.single_field_id("LIST", name);
         self.expr(s, d, &index.borrow(), index_id, this_id)
     }
 
-    fn list_length(
-        &mut self,
-        s: S,
-        this_id: NodeID,
-        parent_id: NodeID,
-        name: &str,
-    ) -> io::Result<()> {
-        self.begin_node(Node::new("data_lengthoflist", this_id).parent_id(parent_id))?;
+    fn list_length(&mut self, s: S, this_id: NodeID, parent_id: NodeID, name: &str) {
+        self.add_node(Node::new("data_lengthoflist", this_id).parent_id(parent_id));
         let list = s.get_list(name).unwrap();
         if let Some((type_name, _type_span)) = list.type_.struct_() {
             let struct_ = s.get_struct(type_name).unwrap();
             let qualified_name = qualify_struct_var_name(&struct_.fields[0].name, name);
-            self.single_field_id("LIST", &qualified_name)?;
+            self.single_field_id("LIST", &qualified_name);
         } else {
-            self.single_field_id("LIST", name)?;
+            self.single_field_id("LIST", name);
         }
-        self.end_obj() // node
     }
 
     pub fn func_call(
@@ -232,10 +199,32 @@ where T: Write + Seek
         name: &SmolStr,
         span: &Span,
         args: &Vec<Rrc<Expr>>,
-    ) -> io::Result<()> {
+    ) {
         let Some(func) = s.sprite.funcs.get(name) else {
-            d.report(DiagnosticKind::UnrecognizedProcedure(name.clone()), span);
-            return Ok(());
+            let suggestion = suggest(
+                name,
+                s.sprite
+                    .funcs
+                    .keys()
+                    .map(SmolStr::as_str)
+                    .chain(Repr::all_names().iter().copied()),
+            );
+            let kind = if suggestion
+                .as_ref()
+                .is_some_and(|suggestion| !s.sprite.funcs.contains_key(suggestion))
+            {
+                DiagnosticKind::UnrecognizedReporter {
+                    name: name.clone(),
+                    suggestion,
+                }
+            } else {
+                DiagnosticKind::UnrecognizedFunction {
+                    name: name.clone(),
+                    suggestion,
+                }
+            };
+            d.report(kind, span);
+            return;
         };
         if func.args.len() != args.len() {
             d.report(
@@ -248,12 +237,11 @@ where T: Write + Seek
         }
         let mut qualified_args: Vec<(SmolStr, NodeID)> = Vec::new();
         let mut qualified_arg_values: Vec<Rrc<Expr>> = Vec::new();
-        self.begin_inputs()?;
         for (arg, kwarg) in func.args.iter().zip(args) {
             match &arg.type_ {
                 Type::Value => {
                     let arg_id = self.id.new_id();
-                    self.input(s, d, &arg.name, &kwarg.borrow(), arg_id)?;
+                    self.input(s, d, &arg.name, &kwarg.borrow(), arg_id);
                     qualified_args.push((arg.name.clone(), arg_id));
                     qualified_arg_values.push(kwarg.clone());
                 }
@@ -284,15 +272,13 @@ where T: Write + Seek
                                 );
                                 continue;
                             }
-                            if struct_literal_fields.len() != struct_.fields.len() {
-                                panic!()
-                            }
-                            for (struct_field, struct_literal_field) in
-                                struct_.fields.iter().zip(struct_literal_fields)
-                            {
-                                if struct_field.name != struct_literal_field.name {
-                                    panic!()
-                                }
+                            if !check_struct_literal_fields(
+                                d,
+                                struct_,
+                                struct_literal_span,
+                                struct_literal_fields,
+                            ) {
+                                continue;
                             }
                             struct_literal_fields
                         }
@@ -311,24 +297,17 @@ where T: Write + Seek
                             &qualified_arg_name,
                             &struct_literal_field.value.borrow(),
                             arg_id,
-                        )?;
+                        );
                         qualified_args.push((qualified_arg_name, arg_id));
                         qualified_arg_values.push(struct_literal_field.value.clone());
                     }
                 }
             }
         }
-        self.end_obj()?; // inputs
-        write!(
-            self,
-            "{}",
-            Mutation::call(func.name.clone(), &qualified_args, true)
-        )?;
-        self.end_obj()?; // node
+        self.mutation(Mutation::call(func.name.clone(), &qualified_args, true));
         for (arg, (_, arg_id)) in qualified_arg_values.iter().zip(qualified_args) {
-            self.expr(s, d, &arg.borrow(), arg_id, this_id)?;
+            self.expr(s, d, &arg.borrow(), arg_id, this_id);
         }
-        Ok(())
     }
 
     pub fn expr_dot(
@@ -340,10 +319,10 @@ where T: Write + Seek
         lhs: &Expr,
         rhs: &SmolStr,
         rhs_span: Span,
-    ) -> io::Result<()> {
+    ) {
         if let Expr::Name(name) = lhs {
             if let Some(enum_) = s.get_enum(name.basename()) {
-                return Ok(());
+                return;
             }
         }
         panic!("attempted to codegen Expr::Dot lhs = {lhs:#?}, rhs = {rhs:#?}")
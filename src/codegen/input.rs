@@ -1,4 +1,4 @@
-use std::io::{self, Seek, Write};
+use std::io::{Seek, Write};
 
 use serde_json::json;
 
@@ -8,21 +8,14 @@ use super::{
 };
 use crate::{
     ast::{Expr, Name, Value},
-    diagnostic::DiagnosticKind,
-    misc::write_comma_io,
+    diagnostic::{suggest, DiagnosticKind},
+    sb3::{self, InputValue, Primitive},
 };
 
 impl<T> Sb3<T>
 where T: Write + Seek
 {
-    pub fn input(
-        &mut self,
-        s: S,
-        d: D,
-        name: &str,
-        expr: &Expr,
-        this_id: NodeID,
-    ) -> io::Result<()> {
+    pub fn input(&mut self, s: S, d: D, name: &str, expr: &Expr, this_id: NodeID) {
         self._input(s, d, name, expr, this_id, None)
     }
 
@@ -34,7 +27,7 @@ where T: Write + Seek
         expr: &Expr,
         this_id: NodeID,
         shadow_id: NodeID,
-    ) -> io::Result<()> {
+    ) {
         self._input(s, d, name, expr, this_id, Some(shadow_id))
     }
 
@@ -46,121 +39,99 @@ where T: Write + Seek
         expr: &Expr,
         this_id: NodeID,
         shadow_id: Option<NodeID>,
-    ) -> io::Result<()> {
-        write_comma_io(&mut self.zip, &mut self.inputs_comma)?;
-        write!(self, r#""{input_name}":"#)?;
-        match expr {
-            Expr::Value { value, span: _ } => return self.value_input(input_name, value),
-            Expr::Name(name) => return self.name_input(s, d, input_name, name, shadow_id),
-            Expr::CallSite { id } => return self.call_site_input(input_name, *id),
-            Expr::Dot { lhs, rhs, rhs_span } => {
+    ) {
+        let input = match expr {
+            Expr::Value { value, span: _ } => value_input(input_name, value),
+            Expr::Name(name) => name_input(s, d, input_name, name, shadow_id),
+            Expr::CallSite { id } => sb3::Input::Obscured(
+                Some(InputValue::Primitive(Primitive::variable(format!("c{id}")))),
+                Some(shadow(input_name, None)),
+            ),
+            Expr::Dot { lhs, rhs, rhs_span } => 'dot: {
                 if let Expr::Name(lhs_name) = &*lhs.borrow() {
                     if let Some(enum_) = s.get_enum(lhs_name.basename()) {
                         if let Some(variant) =
                             enum_.variants.iter().find(|variant| &variant.name == rhs)
                         {
-                            return self
-                                .value_input(input_name, &variant.value.as_ref().unwrap().0);
+                            break 'dot value_input(input_name, &variant.value.as_ref().unwrap().0);
                         } else {
                             d.report(
-                                DiagnosticKind::UnrecognizedEnumVariant(
-                                    lhs_name.basename().clone(),
-                                ),
+                                DiagnosticKind::UnrecognizedEnumVariant {
+                                    name: rhs.clone(),
+                                    suggestion: suggest(
+                                        rhs,
+                                        enum_.variants.iter().map(|variant| variant.name.as_str()),
+                                    ),
+                                },
                                 rhs_span,
                             );
                         }
                     }
                 }
+                node_input(input_name, this_id, shadow_id)
             }
-            _ => {}
-        }
-        self.node_input(input_name, this_id, shadow_id)
+            _ => node_input(input_name, this_id, shadow_id),
+        };
+        self.set_input(input_name, input);
     }
+}
 
-    fn value_input(&mut self, name: &str, value: &Value) -> io::Result<()> {
-        match value {
-            Value::Int(int_value) => {
-                write!(self, "[1,[4,{}]]", json!(int_value))
-            }
-            Value::Float(float_value) => {
-                write!(self, "[1,[4,{}]]", json!(float_value))
-            }
-            Value::String(string_value) => {
-                let color = ["COLOR", "COLOR2"]
-                    .contains(&name)
-                    .then(|| {
-                        csscolorparser::parse(string_value)
-                            .ok()
-                            .filter(|color| color.a == 1.0)
-                    })
-                    .flatten();
-                if name == "BROADCAST_INPUT" {
-                    write!(
-                        self,
-                        "[1,[11,{},{}]]",
-                        json!(**string_value),
-                        json!(**string_value)
-                    )
-                } else if let Some(color) = color {
-                    write!(self, "[1,[9,{}]]", json!(color.to_hex_string()))
-                } else {
-                    write!(self, "[1,[10,{}]]", json!(**string_value))
+fn value_input(name: &str, value: &Value) -> sb3::Input {
+    let primitive = match value {
+        Value::Int(int_value) => Primitive::Number(json!(int_value)),
+        Value::Float(float_value) => Primitive::Number(json!(float_value)),
+        Value::String(string_value) => {
+            let color = ["COLOR", "COLOR2"]
+                .contains(&name)
+                .then(|| {
+                    csscolorparser::parse(string_value)
+                        .ok()
+                        .filter(|color| color.a == 1.0)
+                })
+                .flatten();
+            if name == "BROADCAST_INPUT" {
+                Primitive::Broadcast {
+                    name: string_value.to_string(),
+                    id: string_value.to_string(),
                 }
+            } else if let Some(color) = color {
+                Primitive::Color(json!(color.to_hex_string()))
+            } else {
+                Primitive::text(&**string_value)
             }
         }
-    }
-
-    fn name_input(
-        &mut self,
-        s: S,
-        d: D,
-        input_name: &str,
-        name: &Name,
-        shadow_id: Option<NodeID>,
-    ) -> io::Result<()> {
-        match s.qualify_name(d, name) {
-            Some(QualifiedName::Var(name, _)) => {
-                write!(self, "[3,[12,{},{}],", json!(*name), json!(*name))?;
-            }
-            Some(QualifiedName::List(name, _)) => {
-                write!(self, "[3,[13,{},{}],", json!(*name), json!(*name))?;
-            }
-            None => {}
-        }
-        self.shadow_input(input_name, shadow_id)
-    }
+    };
+    sb3::Input::Shadow(Some(InputValue::Primitive(primitive)))
+}
 
-    fn call_site_input(&mut self, input_name: &str, id: usize) -> io::Result<()> {
-        write!(
-            self,
-            "[3,[12,{},{}],",
-            json!(format!("c{id}")),
-            json!(format!("c{id}"))
-        )?;
-        self.shadow_input(input_name, None)
-    }
+fn name_input(s: S, d: D, input_name: &str, name: &Name, shadow_id: Option<NodeID>) -> sb3::Input {
+    let primitive = match s.qualify_name(d, name) {
+        Some(QualifiedName::Var(name, _)) => Primitive::variable(&*name),
+        Some(QualifiedName::List(name, _)) => Primitive::list(&*name),
+        None => return sb3::Input::Shadow(Some(shadow(input_name, shadow_id))),
+    };
+    sb3::Input::Obscured(
+        Some(InputValue::Primitive(primitive)),
+        Some(shadow(input_name, shadow_id)),
+    )
+}
 
-    fn node_input(
-        &mut self,
-        input_name: &str,
-        node_id: NodeID,
-        shadow_id: Option<NodeID>,
-    ) -> io::Result<()> {
-        if ["CONDITION", "CONDITION2"].contains(&input_name) {
-            return write!(self, "[2,{node_id}]");
-        }
-        write!(self, "[3,{node_id},")?;
-        self.shadow_input(input_name, shadow_id)
+fn node_input(input_name: &str, node_id: NodeID, shadow_id: Option<NodeID>) -> sb3::Input {
+    if ["CONDITION", "CONDITION2"].contains(&input_name) {
+        return sb3::Input::NoShadow(Some(node_id.into()));
     }
+    sb3::Input::Obscured(Some(node_id.into()), Some(shadow(input_name, shadow_id)))
+}
 
-    fn shadow_input(&mut self, input_name: &str, shadow_id: Option<NodeID>) -> io::Result<()> {
-        if let Some(shadow_id) = shadow_id {
-            write!(self, "{shadow_id}]")
-        } else if input_name == "BROADCAST_INPUT" {
-            let broadcast_name = json!("message1");
-            write!(self, "[11,{},{}]]", broadcast_name, broadcast_name)
-        } else {
-            write!(self, r#"[10, ""]]"#)
-        }
+fn shadow(input_name: &str, shadow_id: Option<NodeID>) -> InputValue {
+    if let Some(shadow_id) = shadow_id {
+        shadow_id.into()
+    } else if input_name == "BROADCAST_INPUT" {
+        InputValue::Primitive(Primitive::Broadcast {
+            name: "message1".to_owned(),
+            id: "message1".to_owned(),
+        })
+    } else {
+        InputValue::Primitive(Primitive::text(""))
     }
 }
@@ -0,0 +1,126 @@
+use anyhow::bail;
+use indexmap::IndexMap;
+use serde_json::Value;
+
+use crate::{
+    config::{Config, Monitor, MonitorMode},
+    sb3,
+};
+
+const MARGIN: f64 = 5.0;
+
+fn height(mode: sb3::MonitorMode, height: f64) -> f64 {
+    match mode {
+        sb3::MonitorMode::Default | sb3::MonitorMode::Large => 27.0,
+        sb3::MonitorMode::Slider => 45.0,
+        sb3::MonitorMode::List if height > 0.0 => height,
+        sb3::MonitorMode::List => 200.0,
+    }
+}
+
+pub fn monitors(config: &Config, targets: &[sb3::Target]) -> anyhow::Result<Vec<sb3::Monitor>> {
+    let mut monitors = Vec::new();
+    let mut next_y = MARGIN;
+    for (sprite, sprite_monitors) in &config.monitors {
+        let target = targets.iter().find(|target| {
+            if sprite == "stage" {
+                target.is_stage
+            } else {
+                !target.is_stage && target.name == *sprite
+            }
+        });
+        let Some(target) = target else {
+            bail!("goboscript.toml: monitors.{sprite}: there is no sprite named {sprite:?}");
+        };
+        for (name, monitor) in sprite_monitors {
+            let mut sb3_monitor = if let Some(var) = target.variables.get(name) {
+                var_monitor(name, var, monitor)
+            } else if let Some(list) = target.lists.get(name) {
+                if monitor
+                    .mode
+                    .is_some_and(|mode| mode != MonitorMode::Default)
+                    || monitor.slider_min.is_some()
+                    || monitor.slider_max.is_some()
+                    || monitor.discrete.is_some()
+                {
+                    bail!(
+                        "goboscript.toml: monitors.{sprite}.{name}: {name} is a list, which \
+                         cannot be shown large or as a slider"
+                    );
+                }
+                list_monitor(name, list, monitor)
+            } else {
+                bail!(
+                    "goboscript.toml: monitors.{sprite}.{name}: {sprite} has no variable or \
+                     list named {name:?}"
+                );
+            };
+            sb3_monitor.sprite_name = (!target.is_stage).then(|| target.name.clone());
+            if monitor.x.is_none() && monitor.y.is_none() {
+                sb3_monitor.x = MARGIN;
+                sb3_monitor.y = next_y;
+                next_y += height(sb3_monitor.mode, sb3_monitor.height) + MARGIN;
+            }
+            monitors.push(sb3_monitor);
+        }
+    }
+    Ok(monitors)
+}
+
+fn var_monitor(id: &str, var: &sb3::Variable, monitor: &Monitor) -> sb3::Monitor {
+    let slider_min = monitor.slider_min.unwrap_or(0.0);
+    let slider_max = monitor.slider_max.unwrap_or(100.0);
+    sb3::Monitor {
+        mode: match monitor.mode.unwrap_or(MonitorMode::Default) {
+            MonitorMode::Default => sb3::MonitorMode::Default,
+            MonitorMode::Large => sb3::MonitorMode::Large,
+            MonitorMode::Slider => sb3::MonitorMode::Slider,
+        },
+        opcode: "data_variable".to_owned(),
+        params: IndexMap::from([("VARIABLE".to_owned(), Value::from(var.name.clone()))]),
+        value: var.value.clone(),
+        slider_min: Some(slider_min),
+        slider_max: Some(slider_max),
+        is_discrete: Some(
+            monitor
+                .discrete
+                .unwrap_or(slider_min.fract() == 0.0 && slider_max.fract() == 0.0),
+        ),
+        ..monitor_base(id, monitor)
+    }
+}
+
+fn list_monitor(id: &str, list: &sb3::List, monitor: &Monitor) -> sb3::Monitor {
+    sb3::Monitor {
+        mode: sb3::MonitorMode::List,
+        opcode: "data_listcontents".to_owned(),
+        params: IndexMap::from([("LIST".to_owned(), Value::from(list.name.clone()))]),
+        value: Value::from(list.items.clone()),
+        // The Scratch editor gives list monitors without a size its default size.
+        width: monitor.width.unwrap_or_default(),
+        height: monitor.height.unwrap_or_default(),
+        ..monitor_base(id, monitor)
+    }
+}
+
+/// A monitor's ID is the ID of its variable or list, which is how `show` and `hide`
+/// find it.
+fn monitor_base(id: &str, monitor: &Monitor) -> sb3::Monitor {
+    sb3::Monitor {
+        id: id.to_owned(),
+        mode: sb3::MonitorMode::Default,
+        opcode: String::new(),
+        params: IndexMap::new(),
+        sprite_name: None,
+        value: Value::Null,
+        width: 0.0,
+        height: 0.0,
+        x: monitor.x.unwrap_or(MARGIN),
+        y: monitor.y.unwrap_or(MARGIN),
+        visible: monitor.visible.unwrap_or(true),
+        slider_min: None,
+        slider_max: None,
+        is_discrete: None,
+        extra: Default::default(),
+    }
+}
@@ -1,9 +1,7 @@
-use std::fmt::{self, Display};
-
 use smol_str::SmolStr;
 
 use super::node_id::NodeID;
-use crate::misc::write_comma_fmt;
+use crate::sb3;
 
 pub struct Mutation<'a> {
     name: SmolStr,
@@ -32,38 +30,24 @@ impl<'a> Mutation<'a> {
     }
 }
 
-impl<'a> Display for Mutation<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, r#","mutation":{{"tagName":"mutation","children":[]"#)?;
-        write!(f, r#","warp":"{}""#, self.warp)?;
-        write!(f, r#","proccode":"{}"#, self.name)?;
-        for _ in 0..self.args.len() {
-            write!(f, " %s")?;
-        }
-        write!(f, "\"")?;
-        write!(f, r#","argumentids":"["#)?;
-        let mut comma = false;
-        for (arg_name, _) in self.args {
-            write_comma_fmt(&mut *f, &mut comma)?;
-            write!(f, r#"\"{}\""#, arg_name)?;
+impl<'a> From<Mutation<'a>> for sb3::Mutation {
+    fn from(mutation: Mutation<'a>) -> Self {
+        let mut proccode = mutation.name.to_string();
+        for _ in mutation.args {
+            proccode.push_str(" %s");
         }
-        write!(f, "]\"")?;
-        if !self.is_call {
-            write!(f, r#","argumentnames":"["#)?;
-            let mut comma = false;
-            for (arg_name, _) in self.args {
-                write_comma_fmt(&mut *f, &mut comma)?;
-                write!(f, r#"\"{}\""#, arg_name)?;
-            }
-            write!(f, "]\"")?;
-            write!(f, r#","argumentdefaults":"["#)?;
-            let mut comma = false;
-            for _ in self.args {
-                write_comma_fmt(&mut *f, &mut comma)?;
-                write!(f, r#"\"\""#,)?;
-            }
-            write!(f, "]\"")?;
+        let arg_names: Vec<String> = mutation
+            .args
+            .iter()
+            .map(|(arg_name, _)| arg_name.to_string())
+            .collect();
+        Self {
+            warp: Some(mutation.warp),
+            proccode: Some(proccode),
+            argumentids: Some(arg_names.clone()),
+            argumentnames: (!mutation.is_call).then_some(arg_names),
+            argumentdefaults: (!mutation.is_call).then(|| vec!["".into(); mutation.args.len()]),
+            ..Default::default()
         }
-        write!(f, "}}") // mutation
     }
 }
@@ -1,6 +1,5 @@
-use std::fmt::{self, Display};
-
 use super::node_id::NodeID;
+use crate::sb3;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Node {
@@ -46,32 +45,20 @@ impl Node {
     pub fn some_parent_id(self, parent_id: Option<NodeID>) -> Self {
         Self { parent_id, ..self }
     }
+
+    pub fn this_id(&self) -> NodeID {
+        self.this_id
+    }
 }
 
-impl Display for Node {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{{", self.this_id)?;
-        write!(f, "\"opcode\":\"{}\"", self.opcode)?;
-        if let Some(next_id) = self.next_id {
-            write!(f, ",\"next\":{next_id}")?;
-        } else {
-            write!(f, ",\"next\":null")?;
-        }
-        if let Some(parent_id) = self.parent_id {
-            write!(f, ",\"parent\":{parent_id}")?;
-        } else {
-            write!(f, ",\"parent\":null")?;
-        }
-        if self.top_level {
-            write!(f, ",\"topLevel\":true")?;
-        } else {
-            write!(f, ",\"topLevel\":false")?;
-        }
-        if self.shadow {
-            write!(f, ",\"shadow\":true")?;
-        } else {
-            write!(f, ",\"shadow\":false")?;
+impl From<Node> for sb3::Block {
+    fn from(node: Node) -> Self {
+        Self {
+            next: node.next_id.map(|next_id| next_id.to_string()),
+            parent: node.parent_id.map(|parent_id| parent_id.to_string()),
+            top_level: node.top_level,
+            shadow: node.shadow,
+            ..Self::new(node.opcode)
         }
-        Ok(())
     }
 }
@@ -1,18 +1,35 @@
 use std::fmt::{self, Display};
 
+use crate::sb3;
+
+/// Made of a hash of the sprite's name, a hash of its script's procedure or function name
+/// (or for an event, its opcode and how many events with that opcode come before it), and
+/// a counter. Moving or editing one script does not change the IDs of the others.
 #[derive(Debug, Copy, Clone)]
 pub struct NodeID {
+    sprite: u32,
+    origin: u32,
     value: usize,
 }
 
 impl NodeID {
-    pub fn new(value: usize) -> Self {
-        Self { value }
+    pub fn new(sprite: u32, origin: u32, value: usize) -> Self {
+        Self {
+            sprite,
+            origin,
+            value,
+        }
     }
 }
 
 impl Display for NodeID {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "\"{}\"", self.value)
+        write!(f, "{:08x}:{:08x}:{}", self.sprite, self.origin, self.value)
+    }
+}
+
+impl From<NodeID> for sb3::InputValue {
+    fn from(id: NodeID) -> Self {
+        Self::Block(id.to_string())
     }
 }
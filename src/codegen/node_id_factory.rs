@@ -2,22 +2,34 @@ use super::node_id::NodeID;
 
 #[derive(Debug)]
 pub struct NodeIDFactory {
+    sprite: u32,
+    origin: u32,
     value: usize,
 }
 
 impl NodeIDFactory {
     pub fn new() -> Self {
-        Self { value: 0 }
+        Self {
+            sprite: 0,
+            origin: 0,
+            value: 0,
+        }
     }
 
-    pub fn reset(&mut self) {
+    pub fn set_sprite(&mut self, name: &str) {
+        self.sprite = fxhash::hash32(name);
+        self.set_origin("");
+    }
+
+    pub fn set_origin(&mut self, script: &str) {
+        self.origin = fxhash::hash32(script);
         self.value = 0;
     }
 
     pub fn new_id(&mut self) -> NodeID {
         let value = self.value;
         self.value += 1;
-        NodeID::new(value)
+        NodeID::new(self.sprite, self.origin, value)
     }
 }
 
@@ -0,0 +1,99 @@
+use anyhow::bail;
+
+use crate::{
+    config::{Config, CostumeRef, RotationStyle, SpriteProperties, StageProperties, VideoState},
+    sb3,
+};
+
+pub fn properties(config: &Config, targets: &mut [sb3::Target]) -> anyhow::Result<()> {
+    for name in config.sprites.keys() {
+        if !targets
+            .iter()
+            .any(|target| !target.is_stage && target.name == *name)
+        {
+            bail!("goboscript.toml: sprites.{name}: there is no sprite named {name:?}");
+        }
+    }
+    let no_stage = StageProperties::default();
+    let no_sprite = SpriteProperties::default();
+    for target in targets.iter_mut() {
+        if target.is_stage {
+            let stage = config.stage.as_ref().unwrap_or(&no_stage);
+            let costume = costume_index(target, stage.current_costume.as_ref(), "stage")?;
+            target.current_costume = Some(costume);
+            target.tempo = Some(stage.tempo.unwrap_or(60.0));
+            target.volume = Some(stage.volume.unwrap_or(100.0));
+            target.video_state = Some(
+                stage
+                    .video_state
+                    .unwrap_or(VideoState::On)
+                    .as_str()
+                    .to_owned(),
+            );
+            target.video_transparency = Some(stage.video_transparency.unwrap_or(50.0));
+            target.text_to_speech_language = stage.text_to_speech_language.clone();
+            target.layer_order = Some(0);
+        } else {
+            let sprite = config.sprites.get(&target.name).unwrap_or(&no_sprite);
+            let key = format!("sprites.{}", target.name);
+            let costume = costume_index(target, sprite.current_costume.as_ref(), &key)?;
+            target.current_costume = Some(costume);
+            target.visible = Some(sprite.visible.unwrap_or(true));
+            target.x = Some(sprite.x.unwrap_or(0.0));
+            target.y = Some(sprite.y.unwrap_or(0.0));
+            target.size = Some(sprite.size.unwrap_or(100.0));
+            target.direction = Some(sprite.direction.unwrap_or(90.0));
+            target.draggable = Some(sprite.draggable.unwrap_or(false));
+            let rotation_style = sprite.rotation_style.unwrap_or(RotationStyle::AllAround);
+            target.rotation_style = Some(rotation_style.as_str().to_owned());
+            target.volume = Some(sprite.volume.unwrap_or(100.0));
+        }
+    }
+    // Targets are in order of name, which the sort keeps for sprites with the same
+    // layer order, or none.
+    let mut sprites: Vec<&mut sb3::Target> = targets
+        .iter_mut()
+        .filter(|target| !target.is_stage)
+        .collect();
+    sprites.sort_by_key(|target| {
+        let layer_order = config
+            .sprites
+            .get(&target.name)
+            .and_then(|sprite| sprite.layer_order);
+        (layer_order.is_none(), layer_order)
+    });
+    for (layer_order, sprite) in sprites.into_iter().enumerate() {
+        sprite.layer_order = Some(layer_order + 1);
+    }
+    Ok(())
+}
+
+fn costume_index(
+    target: &sb3::Target,
+    costume: Option<&CostumeRef>,
+    key: &str,
+) -> anyhow::Result<usize> {
+    match costume {
+        None => Ok(0),
+        Some(CostumeRef::Index(index)) if *index < target.costumes.len() => Ok(*index),
+        Some(CostumeRef::Index(index)) => bail!(
+            "goboscript.toml: {key}.current_costume: {} has {} costume(s), so there is no \
+             costume {index}",
+            target.name,
+            target.costumes.len()
+        ),
+        Some(CostumeRef::Name(name)) => {
+            match target
+                .costumes
+                .iter()
+                .position(|costume| costume.name == *name)
+            {
+                Some(index) => Ok(index),
+                None => bail!(
+                    "goboscript.toml: {key}.current_costume: {} has no costume named {name:?}",
+                    target.name
+                ),
+            }
+        }
+    }
+}
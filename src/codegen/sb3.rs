@@ -6,26 +6,33 @@ use std::{
 };
 
 use fxhash::FxHashMap;
+use indexmap::IndexMap;
 use logos::Span;
 use md5::{Digest, Md5};
-use serde_json::json;
 use smol_str::SmolStr;
 use zip::{
     write::{FileOptions, SimpleFileOptions},
-    ZipWriter,
+    CompressionMethod, DateTime, ZipWriter,
 };
 
 use super::{
-    cmd::cmd_to_list, node::Node, node_id::NodeID, node_id_factory::NodeIDFactory,
+    cmd::cmd_to_list,
+    const_expr::{const_values, json_value},
+    monitor,
+    node::Node,
+    node_id::NodeID,
+    node_id_factory::NodeIDFactory,
+    properties,
     turbowarp_config::TurbowarpConfig,
 };
 use crate::{
     ast::*,
-    blocks::Block,
+    blocks::{Block, Repr},
     codegen::mutation::Mutation,
-    config::Config,
-    diagnostic::{DiagnosticKind, SpriteDiagnostics},
-    misc::write_comma_io,
+    config::{Compression, Config},
+    diagnostic::{suggest, DiagnosticKind, SpriteDiagnostics},
+    misc::in_source_order,
+    sb3,
 };
 
 const STAGE_NAME: &str = "Stage";
@@ -45,6 +52,27 @@ pub enum QualifiedName {
     List(SmolStr, Type),
 }
 
+fn var_order<'a>(var: &&'a Var) -> (usize, &'a SmolStr) {
+    (var.span.start, &var.name)
+}
+
+/// Entries have a fixed timestamp, so that building the same project twice gives the
+/// same file.
+pub fn file_options(config: &Config, release: bool) -> SimpleFileOptions {
+    let options = SimpleFileOptions::default().last_modified_time(DateTime::default());
+    match config.compression(release) {
+        Compression::Store => options.compression_method(CompressionMethod::Stored),
+        Compression::Deflate => options
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(config.compression_level),
+    }
+}
+
+/// Asserts are only checked by `goboscript test`, so no blocks are made for them.
+pub fn has_blocks(body: &[Stmt]) -> bool {
+    body.iter().any(|stmt| !matches!(stmt, Stmt::Assert { .. }))
+}
+
 pub fn qualify_local_var_name(proc_name: &str, var_name: &str) -> SmolStr {
     format!("{}:{}", proc_name, var_name).into()
 }
@@ -53,6 +81,42 @@ pub fn qualify_struct_var_name(field_name: &str, var_name: &str) -> SmolStr {
     format!("{}.{}", var_name, field_name).into()
 }
 
+pub fn check_struct_literal_fields(
+    d: D,
+    struct_: &Struct,
+    struct_literal_span: &Span,
+    struct_literal_fields: &[StructLiteralField],
+) -> bool {
+    let mut ok = true;
+    if struct_literal_fields.len() != struct_.fields.len() {
+        d.report(
+            DiagnosticKind::StructFieldsCountMismatch {
+                struct_: struct_.name.clone(),
+                expected: struct_.fields.len(),
+                given: struct_literal_fields.len(),
+            },
+            struct_literal_span,
+        );
+        ok = false;
+    }
+    for (struct_field, struct_literal_field) in struct_.fields.iter().zip(struct_literal_fields) {
+        if struct_field.name != struct_literal_field.name {
+            d.report(
+                DiagnosticKind::UnrecognizedStructField {
+                    name: struct_literal_field.name.clone(),
+                    suggestion: suggest(
+                        &struct_literal_field.name,
+                        struct_.fields.iter().map(|field| field.name.as_str()),
+                    ),
+                },
+                &struct_literal_field.span,
+            );
+            ok = false;
+        }
+    }
+    ok
+}
+
 impl<'a> S<'a> {
     pub fn is_name_list(&self, name: &Name) -> bool {
         self.sprite.lists.contains_key(name.basename())
@@ -95,6 +159,21 @@ impl<'a> S<'a> {
             .or_else(|| self.stage.and_then(|stage| stage.enums.get(name)))
     }
 
+    pub fn visible_names(&self) -> impl Iterator<Item = &str> {
+        let locals = self
+            .proc
+            .map(|proc| &proc.locals)
+            .or_else(|| self.func.map(|func| &func.locals));
+        locals
+            .into_iter()
+            .flat_map(|locals| locals.keys())
+            .chain(self.sprite.vars.keys())
+            .chain(self.sprite.lists.keys())
+            .chain(self.stage.into_iter().flat_map(|stage| stage.vars.keys()))
+            .chain(self.stage.into_iter().flat_map(|stage| stage.lists.keys()))
+            .map(SmolStr::as_str)
+    }
+
     fn qualify_field<T>(
         &self,
         d: D,
@@ -126,6 +205,10 @@ impl<'a> S<'a> {
                         d.report(
                             DiagnosticKind::StructDoesNotHaveField {
                                 type_name: type_name.clone(),
+                                suggestion: suggest(
+                                    &field_name,
+                                    struct_.fields.iter().map(|field| field.name.as_str()),
+                                ),
                                 field_name: field_name.clone(),
                             },
                             type_span,
@@ -181,8 +264,23 @@ impl<'a> S<'a> {
                 QualifiedName::Var,
             );
         }
+        let suggestion = suggest(
+            basename,
+            self.visible_names()
+                .chain(Repr::all_names().iter().copied()),
+        )
+        .map(|suggestion| {
+            if self.visible_names().any(|name| name == suggestion) {
+                suggestion
+            } else {
+                format!("{suggestion}()").into()
+            }
+        });
         d.report(
-            DiagnosticKind::UnrecognizedVariable(basename.clone()),
+            DiagnosticKind::UnrecognizedVariable {
+                name: basename.clone(),
+                suggestion,
+            },
             &name.span(),
         );
         None
@@ -205,7 +303,7 @@ impl Stmt {
             Stmt::Repeat { .. } => "control_repeat",
             Stmt::Forever { .. } => "control_forever",
             Stmt::Branch { else_body, .. } => {
-                if else_body.is_empty() {
+                if !has_blocks(else_body) {
                     "control_if"
                 } else {
                     "control_if_else"
@@ -238,6 +336,8 @@ impl Stmt {
             Stmt::ProcCall { .. } => "procedures_call",
             Stmt::FuncCall { .. } => "procedures_call",
             Stmt::Return { .. } => "data_setvariableto",
+            // Asserts are skipped by `Sb3::stmts`.
+            Stmt::Assert { .. } => unreachable!(),
         }
     }
 }
@@ -248,80 +348,89 @@ where T: Write + Seek
 {
     pub zip: ZipWriter<T>,
     pub id: NodeIDFactory,
-    pub node_comma: bool,
-    pub inputs_comma: bool,
+    pub blocks: IndexMap<String, sb3::Node>,
+    pub call_sites: Vec<usize>,
     pub costumes: FxHashMap<SmolStr, SmolStr>,
-}
-
-impl<T> Write for Sb3<T>
-where T: Write + Seek
-{
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.zip.write(buf)
-    }
-
-    fn flush(&mut self) -> io::Result<()> {
-        self.zip.flush()
-    }
+    pub options: SimpleFileOptions,
 }
 
 impl<T> Sb3<T>
 where T: Write + Seek
 {
-    pub fn new(file: T) -> Self {
+    pub fn new(file: T, options: SimpleFileOptions) -> Self {
         Self {
             zip: ZipWriter::new(file),
             id: NodeIDFactory::new(),
-            node_comma: false,
-            inputs_comma: false,
+            blocks: IndexMap::new(),
+            call_sites: Vec::new(),
             costumes: FxHashMap::default(),
+            options,
         }
     }
 
     fn assets(&mut self, input: &Path) -> io::Result<()> {
-        for (path, hash) in &self.costumes {
-            let (_, extension) = path.rsplit_once('.').unwrap();
-            self.zip
-                .start_file(format!("{hash}.{extension}"), SimpleFileOptions::default())?;
+        let mut assets: Vec<(String, &SmolStr)> = self
+            .costumes
+            .iter()
+            .map(|(path, hash)| {
+                let (_, extension) = path.rsplit_once('.').unwrap();
+                (format!("{hash}.{extension}"), path)
+            })
+            .collect();
+        // Costumes with the same contents share an asset, which is only written once.
+        assets.sort();
+        assets.dedup_by(|(a, _), (b, _)| a == b);
+        for (asset, path) in assets {
+            self.zip.start_file(asset, self.options)?;
             let file = File::open(input.join(path.as_str()));
             io::copy(&mut file?, &mut self.zip)?;
         }
         Ok(())
     }
 
-    pub fn begin_node(&mut self, node: Node) -> io::Result<()> {
-        write_comma_io(&mut self.zip, &mut self.node_comma)?;
-        write!(self, "{node}")
+    pub fn add_node(&mut self, node: Node) {
+        self.blocks.insert(
+            node.this_id().to_string(),
+            sb3::Node::Block(Box::new(node.into())),
+        );
     }
 
-    pub fn end_obj(&mut self) -> io::Result<()> {
-        self.write_all(b"}")
+    fn current(&mut self) -> &mut sb3::Block {
+        match self.blocks.last_mut() {
+            Some((_, sb3::Node::Block(block))) => block,
+            _ => panic!("attempted to write to a block before adding one"),
+        }
     }
 
-    pub fn begin_inputs(&mut self) -> io::Result<()> {
-        self.inputs_comma = false;
-        self.write_all(br#","inputs":{"#)
+    pub fn set_input(&mut self, name: &str, input: sb3::Input) {
+        self.current().inputs.insert(name.to_owned(), input);
     }
 
-    pub fn single_field(&mut self, name: &'static str, value: &str) -> io::Result<()> {
-        write!(self, r#","fields":{{"{name}":[{},null]}}"#, json!(value))
+    pub fn single_field(&mut self, name: &'static str, value: &str) {
+        self.current()
+            .fields
+            .insert(name.to_owned(), sb3::Field::new(value));
     }
 
-    pub fn single_field_id(&mut self, name: &'static str, value: &str) -> io::Result<()> {
-        write!(
-            self,
-            r#","fields":{{"{name}":[{},{}]}}"#,
-            json!(value),
-            json!(value)
-        )
+    pub fn single_field_id(&mut self, name: &'static str, value: &str) {
+        self.current()
+            .fields
+            .insert(name.to_owned(), sb3::Field::with_id(value));
+    }
+
+    pub fn fields(&mut self, fields: &str) {
+        self.current().fields = serde_json::from_str(fields).unwrap();
+    }
+
+    pub fn mutation(&mut self, mutation: Mutation) {
+        self.current().mutation = Some(mutation.into());
     }
 
-    pub fn substack(&mut self, name: &str, this_id: Option<NodeID>) -> io::Result<()> {
+    pub fn substack(&mut self, name: &str, this_id: Option<NodeID>) {
         let Some(this_id) = this_id else {
-            return Ok(());
+            return;
         };
-        write_comma_io(&mut self.zip, &mut self.inputs_comma)?;
-        write!(self, r#""{name}":[2,{this_id}]"#)
+        self.set_input(name, sb3::Input::NoShadow(Some(this_id.into())));
     }
 
     pub fn project(
@@ -331,46 +440,41 @@ where T: Write + Seek
         config: &Config,
         stage_diagnostics: D,
         sprites_diagnostics: &mut FxHashMap<SmolStr, SpriteDiagnostics>,
-    ) -> io::Result<()> {
-        // TODO: switch to deflate compression
-        // this should be configurable, use store in debug (because it would be
-        // faster?), use deflate in release (because it would be smaller?)
-        self.zip
-            .start_file("project.json", SimpleFileOptions::default())?;
-        write!(self, "{{")?;
-        write!(self, r#""targets":["#)?;
-        self.sprite(
+    ) -> anyhow::Result<()> {
+        let mut targets = vec![self.sprite(
             input,
             STAGE_NAME,
             &project.stage,
             None,
             config,
             stage_diagnostics,
-        )?;
-        for (sprite_name, sprite) in &project.sprites {
-            write!(self, r#","#)?;
-            self.sprite(
+        )?];
+        for (sprite_name, sprite) in in_source_order(&project.sprites, |(name, _)| *name) {
+            targets.push(self.sprite(
                 input,
                 sprite_name,
                 sprite,
                 Some(&project.stage),
                 config,
                 sprites_diagnostics.get_mut(sprite_name).unwrap(),
-            )?;
-        }
-        write!(self, "]")?; // targets
-        write!(self, r#","monitors":[]"#)?;
-        write!(self, r#","extensions":[]"#)?;
-        write!(self, r#","meta":{{"#)?;
-        write!(self, r#""semver":"3.0.0""#)?;
-        write!(self, r#","vm":"0.2.0""#)?;
-        write!(
-            self,
-            r#","agent":"goboscript v{}""#,
-            env!("CARGO_PKG_VERSION")
-        )?;
-        write!(self, "}}")?; // meta
-        write!(self, "}}")?; // project
+            )?);
+        }
+        properties::properties(config, &mut targets)?;
+        let monitors = monitor::monitors(config, &targets)?;
+        let project = sb3::Project {
+            targets,
+            monitors,
+            extensions: vec![],
+            meta: sb3::Meta {
+                semver: "3.0.0".to_owned(),
+                vm: "0.2.0".to_owned(),
+                agent: format!("goboscript v{}", env!("CARGO_PKG_VERSION")),
+                extra: Default::default(),
+            },
+            extra: Default::default(),
+        };
+        self.zip.start_file("project.json", self.options)?;
+        serde_json::to_writer(&mut self.zip, &project)?;
         self.assets(input)?;
         Ok(())
     }
@@ -383,53 +487,50 @@ where T: Write + Seek
         stage: Option<&Sprite>,
         config: &Config,
         d: D,
-    ) -> io::Result<()> {
-        self.id.reset();
-        write!(self, "{{")?;
-        write!(self, r#""isStage":{}"#, name == STAGE_NAME)?;
-        write!(self, r#","name":{}"#, json!(name))?;
+    ) -> io::Result<sb3::Target> {
+        self.id.set_sprite(name);
+        let mut target = sb3::Target {
+            is_stage: name == STAGE_NAME,
+            name: name.to_owned(),
+            ..Default::default()
+        };
         if name == STAGE_NAME {
-            write!(self, r#","comments":{{"#)?;
-            write!(self, r#""twconfig":{{"#)?;
-            write!(self, r#""blockId":null"#)?;
-            write!(self, r#","x":0"#)?;
-            write!(self, r#","y":0"#)?;
-            write!(self, r#","width":350"#)?;
-            write!(self, r#","height":170"#)?;
-            write!(self, r#","minimized":false"#)?;
-            write!(
-                self,
-                r#","text":{}"#,
-                json!(TurbowarpConfig::from(config).to_string())
-            )?;
-            write!(self, "}}")?; // twconfig
-            write!(self, "}}")?; // comments
-        }
-        write!(self, r#","variables":{{"#)?;
-        let mut comma = false;
-        for proc in sprite.procs.values() {
-            for var in proc.locals.values() {
-                self.local_var_declaration(sprite, &proc.name, var, &mut comma, d)?;
+            target.comments.insert(
+                "twconfig".to_owned(),
+                sb3::Comment {
+                    block_id: None,
+                    x: 0.0,
+                    y: 0.0,
+                    width: 350.0,
+                    height: 170.0,
+                    minimized: false,
+                    text: TurbowarpConfig::from(config).to_string(),
+                },
+            );
+        }
+        let procs = in_source_order(sprite.procs.values(), |proc| proc.span.start);
+        let funcs = in_source_order(sprite.funcs.values(), |func| func.span.start);
+        for proc in &procs {
+            for var in in_source_order(proc.locals.values(), var_order) {
+                local_var_declaration(&mut target.variables, sprite, &proc.name, var, d);
             }
         }
-        for func in sprite.funcs.values() {
-            for var in func.locals.values() {
-                self.local_var_declaration(sprite, &func.name, var, &mut comma, d)?;
+        for func in &funcs {
+            for var in in_source_order(func.locals.values(), var_order) {
+                local_var_declaration(&mut target.variables, sprite, &func.name, var, d);
             }
         }
-        for var in sprite.vars.values() {
-            self.var_declaration(sprite, var, &mut comma, d)?;
+        for var in in_source_order(sprite.vars.values(), var_order) {
+            var_declaration(&mut target.variables, sprite, var, d);
+        }
+        // A function returns its value in a variable named after it.
+        for func in &funcs {
+            json_var_declaration(&mut target.variables, &func.name, false, None);
         }
-        write!(self, "}}")?; // variables
-        write!(self, r#","lists":{{"#)?;
-        let mut comma = false;
-        for list in sprite.lists.values() {
-            self.list_declaration(input, sprite, list, &mut comma, d)?;
+        for list in in_source_order(sprite.lists.values(), |list| (list.span.start, &list.name)) {
+            list_declaration(&mut target.lists, input, config, sprite, list, d);
         }
-        write!(self, "}}")?; // lists
-        write!(self, r#","blocks":{{"#)?;
-        self.node_comma = false;
-        for proc in sprite.procs.values() {
+        for proc in procs {
             self.proc(
                 S {
                     stage,
@@ -439,9 +540,9 @@ where T: Write + Seek
                 },
                 d,
                 proc,
-            )?;
+            );
         }
-        for func in sprite.funcs.values() {
+        for func in funcs {
             self.func(
                 S {
                     stage,
@@ -451,9 +552,11 @@ where T: Write + Seek
                 },
                 d,
                 func,
-            )?;
+            );
         }
+        let mut ordinals: FxHashMap<&str, usize> = FxHashMap::default();
         for event in &sprite.events {
+            let ordinal = ordinals.entry(event.kind.opcode()).or_default();
             self.event(
                 S {
                     stage,
@@ -463,164 +566,21 @@ where T: Write + Seek
                 },
                 d,
                 event,
-            )?;
-        }
-        write!(self, "}}")?; // blocks
-        write!(self, r#","costumes":["#)?;
-        let mut comma = false;
-        for costume in &sprite.costumes {
-            write_comma_io(&mut self.zip, &mut comma)?;
-            self.costume(input, costume, d)?;
-        }
-        write!(self, "]")?; // costumes
-        write!(self, r#","sounds":["#)?;
-        write!(self, "]")?; // sounds
-        write!(self, "}}")?; // sprite
-        Ok(())
-    }
-
-    pub fn json_var_declaration(
-        &mut self,
-        var_name: &str,
-        is_cloud: bool,
-        comma: &mut bool,
-    ) -> io::Result<()> {
-        write_comma_io(&mut self.zip, comma)?;
-        if is_cloud {
-            write!(self, "\"{}\":[\"\u{2601} {}\",0,true]", var_name, var_name)
-        } else {
-            write!(self, "\"{}\":[\"{}\",0]", var_name, var_name)
+                *ordinal,
+            );
+            *ordinal += 1;
         }
-    }
-
-    pub fn var_declaration(
-        &mut self,
-        sprite: &Sprite,
-        var: &Var,
-        comma: &mut bool,
-        d: D,
-    ) -> io::Result<()> {
-        match &var.type_ {
-            Type::Value => {
-                self.json_var_declaration(&var.name, var.is_cloud, comma)?;
-            }
-            Type::Struct {
-                name: type_name,
-                span: type_span,
-            } => {
-                let Some(struct_) = sprite.structs.get(type_name) else {
-                    d.report(
-                        DiagnosticKind::UnrecognizedStruct(type_name.clone()),
-                        type_span,
-                    );
-                    return Ok(());
-                };
-                for field in &struct_.fields {
-                    let qualified_var_name = qualify_struct_var_name(&field.name, &var.name);
-                    self.json_var_declaration(&qualified_var_name, false, comma)?;
-                }
-            }
+        target.blocks = std::mem::take(&mut self.blocks);
+        for id in std::mem::take(&mut self.call_sites) {
+            json_var_declaration(&mut target.variables, &format!("c{id}"), false, None);
         }
-        Ok(())
-    }
-
-    pub fn local_var_declaration(
-        &mut self,
-        sprite: &Sprite,
-        proc_name: &str,
-        var: &Var,
-        comma: &mut bool,
-        d: D,
-    ) -> io::Result<()> {
-        match &var.type_ {
-            Type::Value => {
-                let qualified_var_name = qualify_local_var_name(proc_name, &var.name);
-                self.json_var_declaration(&qualified_var_name, false, comma)?;
-            }
-            Type::Struct {
-                name: type_name,
-                span: type_span,
-            } => {
-                let Some(struct_) = sprite.structs.get(type_name) else {
-                    d.report(
-                        DiagnosticKind::UnrecognizedStruct(type_name.clone()),
-                        type_span,
-                    );
-                    return Ok(());
-                };
-                for field in &struct_.fields {
-                    let qualified_var_name = qualify_local_var_name(
-                        proc_name,
-                        &qualify_struct_var_name(&field.name, &var.name),
-                    );
-                    self.json_var_declaration(&qualified_var_name, false, comma)?;
-                }
-            }
-        }
-        Ok(())
-    }
-
-    pub fn list_declaration(
-        &mut self,
-        input: &Path,
-        sprite: &Sprite,
-        list: &List,
-        comma: &mut bool,
-        d: D,
-    ) -> io::Result<()> {
-        let data = list.cmd.as_ref().and_then(|cmd| {
-            cmd_to_list(cmd, input)
-                .map_err(|err| d.diagnostics.push(err))
-                .ok()
-        });
-        match &list.type_ {
-            Type::Value => {
-                write_comma_io(&mut self.zip, comma)?;
-                if let Some(cmd) = data {
-                    write!(self, r#""{}":["{}",{}]"#, list.name, list.name, json!(cmd))?;
-                } else {
-                    write!(self, r#""{}":["{}",[]]"#, list.name, list.name)?;
-                }
-            }
-            Type::Struct {
-                name: type_name,
-                span: type_span,
-            } => {
-                let Some(struct_) = sprite.structs.get(type_name) else {
-                    d.report(
-                        DiagnosticKind::UnrecognizedStruct(type_name.clone()),
-                        type_span,
-                    );
-                    return Ok(());
-                };
-                for (i, field) in struct_.fields.iter().enumerate() {
-                    let qualified_list_name = qualify_struct_var_name(&field.name, &list.name);
-                    write_comma_io(&mut self.zip, comma)?;
-                    if let Some(cmd) = &data {
-                        let column = (0..(cmd.len() / struct_.fields.len()))
-                            .map(|j| &cmd[j * struct_.fields.len() + i])
-                            .collect::<Vec<_>>();
-                        write!(
-                            self,
-                            r#""{}":["{}",{}]"#,
-                            qualified_list_name,
-                            qualified_list_name,
-                            json!(column)
-                        )?;
-                    } else {
-                        write!(
-                            self,
-                            r#""{}":["{}",[]]"#,
-                            qualified_list_name, qualified_list_name
-                        )?;
-                    }
-                }
-            }
+        for costume in &sprite.costumes {
+            target.costumes.push(self.costume(input, costume, d)?);
         }
-        Ok(())
+        Ok(target)
     }
 
-    pub fn costume(&mut self, input: &Path, costume: &Costume, d: D) -> io::Result<()> {
+    pub fn costume(&mut self, input: &Path, costume: &Costume, d: D) -> io::Result<sb3::Costume> {
         let path = input.join(&costume.path);
         let hash = self
             .costumes
@@ -643,39 +603,43 @@ where T: Write + Seek
                 Ok(hash)
             })?;
         let (_, extension) = costume.path.rsplit_once('.').unwrap();
-        write!(self, "{{")?;
-        write!(self, r#""name":{}"#, json!(*costume.name))?;
-        write!(self, r#","assetId":"{hash}""#)?;
-        write!(self, r#","dataFormat":"{extension}""#)?;
-        write!(self, r#","md5ext":"{hash}.{extension}""#)?;
-        write!(self, "}}") // costume
+        Ok(sb3::Costume {
+            name: costume.name.to_string(),
+            asset_id: hash.to_string(),
+            data_format: extension.to_owned(),
+            md5ext: Some(format!("{hash}.{extension}")),
+            bitmap_resolution: None,
+            rotation_center_x: None,
+            rotation_center_y: None,
+            extra: Default::default(),
+        })
     }
 
-    pub fn proc(&mut self, s: S, d: D, proc: &Proc) -> io::Result<()> {
+    pub fn proc(&mut self, s: S, d: D, proc: &Proc) {
+        self.id.set_origin(&format!("proc {}", proc.name));
         let this_id = self.id.new_id();
         let prototype_id = self.id.new_id();
         let next_id = self.id.new_id();
-        self.begin_node(
+        self.add_node(
             Node::new("procedures_definition", this_id)
-                .some_next_id((!proc.body.is_empty()).then_some(next_id))
+                .some_next_id(has_blocks(&proc.body).then_some(next_id))
                 .top_level(true),
-        )?;
-        self.begin_inputs()?;
-        write!(self, r#""custom_block":[1,{prototype_id}]"#)?;
-        self.end_obj()?; // inputs
-        self.end_obj()?; // node
+        );
+        self.set_input(
+            "custom_block",
+            sb3::Input::Shadow(Some(prototype_id.into())),
+        );
         let mut qualified_args: Vec<(SmolStr, NodeID)> = Vec::new();
         for arg in &proc.args {
             match &arg.type_ {
                 Type::Value => {
                     let arg_id = self.id.new_id();
-                    self.begin_node(
+                    self.add_node(
                         Node::new("argument_reporter_string_number", arg_id)
                             .parent_id(prototype_id)
                             .shadow(true),
-                    )?;
-                    self.single_field("VALUE", &arg.name)?;
-                    self.end_obj()?; // node
+                    );
+                    self.single_field("VALUE", &arg.name);
                     qualified_args.push((arg.name.clone(), arg_id));
                 }
                 Type::Struct {
@@ -692,64 +656,61 @@ where T: Write + Seek
                     for field in &struct_.fields {
                         let qualified_arg_name = qualify_struct_var_name(&field.name, &arg.name);
                         let arg_id = self.id.new_id();
-                        self.begin_node(
+                        self.add_node(
                             Node::new("argument_reporter_string_number", arg_id)
                                 .parent_id(prototype_id)
                                 .shadow(true),
-                        )?;
-                        self.single_field("VALUE", &qualified_arg_name)?;
-                        self.end_obj()?; // node
+                        );
+                        self.single_field("VALUE", &qualified_arg_name);
                         qualified_args.push((qualified_arg_name, arg_id));
                     }
                 }
             }
         }
-        self.begin_node(
+        self.add_node(
             Node::new("procedures_prototype", prototype_id)
                 .parent_id(this_id)
                 .shadow(true),
-        )?;
-        self.begin_inputs()?;
-        let mut comma = false;
+        );
         for (qualified_arg_name, arg_id) in &qualified_args {
-            write_comma_io(&mut self.zip, &mut comma)?;
-            write!(self, r#"{}:[2,{arg_id}]"#, json!(**qualified_arg_name))?;
+            self.set_input(
+                qualified_arg_name,
+                sb3::Input::NoShadow(Some((*arg_id).into())),
+            );
         }
-        self.end_obj()?; // inputs
-        write!(
-            self,
-            "{}",
-            Mutation::prototype(proc.name.clone(), &qualified_args, proc.warp)
-        )?;
-        self.end_obj()?; // node
+        self.mutation(Mutation::prototype(
+            proc.name.clone(),
+            &qualified_args,
+            proc.warp,
+        ));
         self.stmts(s, d, &proc.body, next_id, Some(this_id))
     }
 
-    pub fn func(&mut self, s: S, d: D, func: &Func) -> io::Result<()> {
+    pub fn func(&mut self, s: S, d: D, func: &Func) {
+        self.id.set_origin(&format!("func {}", func.name));
         let this_id = self.id.new_id();
         let prototype_id = self.id.new_id();
         let next_id = self.id.new_id();
-        self.begin_node(
+        self.add_node(
             Node::new("procedures_definition", this_id)
-                .some_next_id((!func.body.is_empty()).then_some(next_id))
+                .some_next_id(has_blocks(&func.body).then_some(next_id))
                 .top_level(true),
-        )?;
-        self.begin_inputs()?;
-        write!(self, r#""custom_block":[1,{prototype_id}]"#)?;
-        self.end_obj()?; // inputs
-        self.end_obj()?; // node
+        );
+        self.set_input(
+            "custom_block",
+            sb3::Input::Shadow(Some(prototype_id.into())),
+        );
         let mut qualified_args: Vec<(SmolStr, NodeID)> = Vec::new();
         for arg in &func.args {
             match &arg.type_ {
                 Type::Value => {
                     let arg_id = self.id.new_id();
-                    self.begin_node(
+                    self.add_node(
                         Node::new("argument_reporter_string_number", arg_id)
                             .parent_id(prototype_id)
                             .shadow(true),
-                    )?;
-                    self.single_field("VALUE", &arg.name)?;
-                    self.end_obj()?; // node
+                    );
+                    self.single_field("VALUE", &arg.name);
                     qualified_args.push((arg.name.clone(), arg_id));
                 }
                 Type::Struct {
@@ -766,47 +727,46 @@ where T: Write + Seek
                     for field in &struct_.fields {
                         let qualified_arg_name = qualify_struct_var_name(&field.name, &arg.name);
                         let arg_id = self.id.new_id();
-                        self.begin_node(
+                        self.add_node(
                             Node::new("argument_reporter_string_number", arg_id)
                                 .parent_id(prototype_id)
                                 .shadow(true),
-                        )?;
-                        self.single_field("VALUE", &qualified_arg_name)?;
-                        self.end_obj()?; // node
+                        );
+                        self.single_field("VALUE", &qualified_arg_name);
                         qualified_args.push((qualified_arg_name, arg_id));
                     }
                 }
             }
         }
-        self.begin_node(
+        self.add_node(
             Node::new("procedures_prototype", prototype_id)
                 .parent_id(this_id)
                 .shadow(true),
-        )?;
-        self.begin_inputs()?;
-        let mut comma = false;
+        );
         for (qualified_arg_name, arg_id) in &qualified_args {
-            write_comma_io(&mut self.zip, &mut comma)?;
-            write!(self, r#"{}:[2,{arg_id}]"#, json!(**qualified_arg_name))?;
+            self.set_input(
+                qualified_arg_name,
+                sb3::Input::NoShadow(Some((*arg_id).into())),
+            );
         }
-        self.end_obj()?; // inputs
-        write!(
-            self,
-            "{}",
-            Mutation::prototype(func.name.clone(), &qualified_args, true)
-        )?;
-        self.end_obj()?; // node
+        self.mutation(Mutation::prototype(
+            func.name.clone(),
+            &qualified_args,
+            true,
+        ));
         self.stmts(s, d, &func.body, next_id, Some(this_id))
     }
 
-    pub fn event(&mut self, s: S, d: D, event: &Event) -> io::Result<()> {
+    pub fn event(&mut self, s: S, d: D, event: &Event, ordinal: usize) {
+        self.id
+            .set_origin(&format!("{} {ordinal}", event.kind.opcode()));
         let this_id = self.id.new_id();
         let next_id = self.id.new_id();
-        self.begin_node(
+        self.add_node(
             Node::new(event.kind.opcode(), this_id)
-                .some_next_id((!event.body.is_empty()).then_some(next_id))
+                .some_next_id(has_blocks(&event.body).then_some(next_id))
                 .top_level(true),
-        )?;
+        );
         match &event.kind {
             EventKind::OnFlag => self.on_flag(s, d, this_id),
             EventKind::OnKey { key, span } => self.on_key(s, d, this_id, key, span),
@@ -817,7 +777,7 @@ where T: Write + Seek
             EventKind::OnLoudnessGt { value } => self.on_loudness_gt(s, d, this_id, value),
             EventKind::OnTimerGt { value } => self.on_timer_gt(s, d, this_id, value),
             EventKind::OnClone => self.on_clone(s, d, this_id),
-        }?;
+        };
         self.stmts(s, d, &event.body, next_id, Some(this_id))
     }
 
@@ -828,22 +788,25 @@ where T: Write + Seek
         stmts: &[Stmt],
         mut this_id: NodeID,
         mut parent_id: Option<NodeID>,
-    ) -> io::Result<()> {
-        for (i, stmt) in stmts.iter().enumerate() {
+    ) {
+        let stmts: Vec<&Stmt> = stmts
+            .iter()
+            .filter(|stmt| !matches!(stmt, Stmt::Assert { .. }))
+            .collect();
+        for (i, stmt) in stmts.iter().copied().enumerate() {
             let is_last = i == stmts.len() - 1;
             if is_last || stmt.is_terminator() {
-                self.stmt(s, d, stmt, this_id, None, parent_id)?;
+                self.stmt(s, d, stmt, this_id, None, parent_id);
                 if !is_last {
                     d.report(DiagnosticKind::FollowedByUnreachableCode, stmt.span());
                 }
                 break;
             }
             let next_id = self.id.new_id();
-            self.stmt(s, d, stmt, this_id, Some(next_id), parent_id)?;
+            self.stmt(s, d, stmt, this_id, Some(next_id), parent_id);
             parent_id = Some(this_id);
             this_id = next_id;
         }
-        Ok(())
     }
 
     pub fn stmt(
@@ -854,12 +817,12 @@ where T: Write + Seek
         this_id: NodeID,
         next_id: Option<NodeID>,
         parent_id: Option<NodeID>,
-    ) -> io::Result<()> {
-        self.begin_node(
+    ) {
+        self.add_node(
             Node::new(stmt.opcode(s), this_id)
                 .some_next_id(next_id)
                 .some_parent_id(parent_id),
-        )?;
+        );
         match stmt {
             Stmt::Repeat { times, body } => self.repeat(s, d, this_id, times, body),
             Stmt::Forever { body, span } => self.forever(s, d, this_id, body, span),
@@ -895,21 +858,15 @@ where T: Write + Seek
             Stmt::ProcCall { name, span, args } => self.proc_call(s, d, this_id, name, span, args),
             Stmt::FuncCall { name, span, args } => self.func_call(s, d, this_id, name, span, args),
             Stmt::Return { value } => self.return_(s, d, this_id, value),
+            Stmt::Assert { .. } => unreachable!(),
         }
     }
 
-    pub fn expr(
-        &mut self,
-        s: S,
-        d: D,
-        expr: &Expr,
-        this_id: NodeID,
-        parent_id: NodeID,
-    ) -> io::Result<()> {
+    pub fn expr(&mut self, s: S, d: D, expr: &Expr, this_id: NodeID, parent_id: NodeID) {
         match expr {
-            Expr::CallSite { .. } => Ok(()),
-            Expr::Value { .. } => Ok(()),
-            Expr::Name { .. } => Ok(()),
+            Expr::CallSite { .. } => {}
+            Expr::Value { .. } => {}
+            Expr::Name { .. } => {}
             Expr::Arg(name) => self.arg(s, d, this_id, parent_id, name),
             Expr::Repr { repr, span, args } => {
                 self.repr(s, d, this_id, parent_id, repr, span, args)
@@ -932,7 +889,6 @@ where T: Write + Seek
                     },
                     &expr.span(),
                 );
-                Ok(())
             }
             Expr::Dot { lhs, rhs, rhs_span } => self.expr_dot(
                 s,
@@ -946,3 +902,166 @@ where T: Write + Seek
         }
     }
 }
+
+fn json_var_declaration(
+    variables: &mut IndexMap<String, sb3::Variable>,
+    var_name: &str,
+    is_cloud: bool,
+    value: Option<&Value>,
+) {
+    let value = value.map_or(0.into(), json_value);
+    let variable = if is_cloud {
+        sb3::Variable {
+            is_cloud: true,
+            ..sb3::Variable::new(format!("\u{2601} {var_name}"), value)
+        }
+    } else {
+        sb3::Variable::new(var_name, value)
+    };
+    variables.insert(var_name.to_owned(), variable);
+}
+
+fn var_declaration(
+    variables: &mut IndexMap<String, sb3::Variable>,
+    sprite: &Sprite,
+    var: &Var,
+    d: D,
+) {
+    let initial_values = |d: D| {
+        var.initial_value
+            .as_ref()
+            .and_then(|initial_value| {
+                const_values(sprite, &var.type_, initial_value)
+                    .map_err(|err| d.diagnostics.push(err))
+                    .ok()
+            })
+            .unwrap_or_default()
+    };
+    match &var.type_ {
+        Type::Value => {
+            let values = initial_values(d);
+            json_var_declaration(variables, &var.name, var.is_cloud, values.first());
+        }
+        Type::Struct {
+            name: type_name,
+            span: type_span,
+        } => {
+            let Some(struct_) = sprite.structs.get(type_name) else {
+                d.report(
+                    DiagnosticKind::UnrecognizedStruct(type_name.clone()),
+                    type_span,
+                );
+                return;
+            };
+            let values = initial_values(d);
+            for (i, field) in struct_.fields.iter().enumerate() {
+                let qualified_var_name = qualify_struct_var_name(&field.name, &var.name);
+                json_var_declaration(variables, &qualified_var_name, false, values.get(i));
+            }
+        }
+    }
+}
+
+fn local_var_declaration(
+    variables: &mut IndexMap<String, sb3::Variable>,
+    sprite: &Sprite,
+    proc_name: &str,
+    var: &Var,
+    d: D,
+) {
+    match &var.type_ {
+        Type::Value => {
+            let qualified_var_name = qualify_local_var_name(proc_name, &var.name);
+            json_var_declaration(variables, &qualified_var_name, false, None);
+        }
+        Type::Struct {
+            name: type_name,
+            span: type_span,
+        } => {
+            let Some(struct_) = sprite.structs.get(type_name) else {
+                d.report(
+                    DiagnosticKind::UnrecognizedStruct(type_name.clone()),
+                    type_span,
+                );
+                return;
+            };
+            for field in &struct_.fields {
+                let qualified_var_name = qualify_local_var_name(
+                    proc_name,
+                    &qualify_struct_var_name(&field.name, &var.name),
+                );
+                json_var_declaration(variables, &qualified_var_name, false, None);
+            }
+        }
+    }
+}
+
+fn list_declaration(
+    lists: &mut IndexMap<String, sb3::List>,
+    input: &Path,
+    config: &Config,
+    sprite: &Sprite,
+    list: &List,
+    d: D,
+) {
+    let struct_ = match &list.type_ {
+        Type::Value => None,
+        Type::Struct {
+            name: type_name,
+            span: type_span,
+        } => {
+            let Some(struct_) = sprite.structs.get(type_name) else {
+                d.report(
+                    DiagnosticKind::UnrecognizedStruct(type_name.clone()),
+                    type_span,
+                );
+                return;
+            };
+            Some(struct_)
+        }
+    };
+    // The items, row by row for struct lists.
+    let data: Vec<serde_json::Value> = match &list.cmd {
+        Some(cmd) => cmd_to_list(cmd, input, struct_, config)
+            .map_err(|err| d.diagnostics.push(err))
+            .unwrap_or_default()
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        None => list
+            .initial_items
+            .iter()
+            .filter_map(|item| {
+                const_values(sprite, &list.type_, item)
+                    .map_err(|err| d.diagnostics.push(err))
+                    .ok()
+            })
+            .flatten()
+            .map(|value| json_value(&value))
+            .collect(),
+    };
+    let Some(struct_) = struct_ else {
+        lists.insert(
+            list.name.to_string(),
+            sb3::List {
+                name: list.name.to_string(),
+                items: data,
+            },
+        );
+        return;
+    };
+    for (i, field) in struct_.fields.iter().enumerate() {
+        let qualified_list_name = qualify_struct_var_name(&field.name, &list.name);
+        let items = data
+            .chunks_exact(struct_.fields.len())
+            .map(|row| row[i].clone())
+            .collect();
+        lists.insert(
+            qualified_list_name.to_string(),
+            sb3::List {
+                name: qualified_list_name.to_string(),
+                items,
+            },
+        );
+    }
+}
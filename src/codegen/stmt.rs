@@ -1,57 +1,39 @@
-use std::io::{self, Seek, Write};
+use std::io::{Seek, Write};
 
 use logos::Span;
-use serde_json::json;
 use smol_str::SmolStr;
 
 use super::{
     node::Node,
     node_id::NodeID,
-    sb3::{qualify_struct_var_name, QualifiedName, Sb3, D, S},
+    sb3::{
+        check_struct_literal_fields, has_blocks, qualify_struct_var_name, QualifiedName, Sb3, D, S,
+    },
 };
 use crate::{
     ast::{Expr, Kwarg, Name, Stmt, Type},
     blocks::Block,
     codegen::mutation::Mutation,
-    diagnostic::DiagnosticKind,
-    misc::{write_comma_io, Rrc},
+    diagnostic::{suggest, DiagnosticKind},
+    misc::Rrc,
+    sb3,
 };
 
 impl<T> Sb3<T>
 where T: Write + Seek
 {
-    pub fn repeat(
-        &mut self,
-        s: S,
-        d: D,
-        this_id: NodeID,
-        times: &Rrc<Expr>,
-        body: &[Stmt],
-    ) -> io::Result<()> {
+    pub fn repeat(&mut self, s: S, d: D, this_id: NodeID, times: &Rrc<Expr>, body: &[Stmt]) {
         let times_id = self.id.new_id();
         let body_id = self.id.new_id();
-        self.begin_inputs()?;
-        self.input(s, d, "TIMES", &times.borrow(), times_id)?;
-        self.substack("SUBSTACK", (!body.is_empty()).then_some(body_id))?;
-        self.end_obj()?; // inputs
-        self.end_obj()?; // node
-        self.expr(s, d, &times.borrow(), times_id, this_id)?;
+        self.input(s, d, "TIMES", &times.borrow(), times_id);
+        self.substack("SUBSTACK", has_blocks(body).then_some(body_id));
+        self.expr(s, d, &times.borrow(), times_id, this_id);
         self.stmts(s, d, body, body_id, Some(this_id))
     }
 
-    pub fn forever(
-        &mut self,
-        s: S,
-        d: D,
-        this_id: NodeID,
-        body: &[Stmt],
-        _span: &Span,
-    ) -> io::Result<()> {
+    pub fn forever(&mut self, s: S, d: D, this_id: NodeID, body: &[Stmt], _span: &Span) {
         let body_id = self.id.new_id();
-        self.begin_inputs()?;
-        self.substack("SUBSTACK", (!body.is_empty()).then_some(body_id))?;
-        self.end_obj()?; // inputs
-        self.end_obj()?; // node
+        self.substack("SUBSTACK", has_blocks(body).then_some(body_id));
         self.stmts(s, d, body, body_id, Some(this_id))
     }
 
@@ -63,37 +45,24 @@ where T: Write + Seek
         cond: &Rrc<Expr>,
         if_body: &[Stmt],
         else_body: &[Stmt],
-    ) -> io::Result<()> {
+    ) {
         let cond_id = self.id.new_id();
         let if_body_id = self.id.new_id();
         let else_body_id = self.id.new_id();
-        self.begin_inputs()?;
-        self.input(s, d, "CONDITION", &cond.borrow(), cond_id)?;
-        self.substack("SUBSTACK", (!if_body.is_empty()).then_some(if_body_id))?;
-        self.substack("SUBSTACK2", (!else_body.is_empty()).then_some(else_body_id))?;
-        self.end_obj()?; // inputs
-        self.end_obj()?; // node
-        self.expr(s, d, &cond.borrow(), cond_id, this_id)?;
-        self.stmts(s, d, if_body, if_body_id, Some(this_id))?;
+        self.input(s, d, "CONDITION", &cond.borrow(), cond_id);
+        self.substack("SUBSTACK", has_blocks(if_body).then_some(if_body_id));
+        self.substack("SUBSTACK2", has_blocks(else_body).then_some(else_body_id));
+        self.expr(s, d, &cond.borrow(), cond_id, this_id);
+        self.stmts(s, d, if_body, if_body_id, Some(this_id));
         self.stmts(s, d, else_body, else_body_id, Some(this_id))
     }
 
-    pub fn until(
-        &mut self,
-        s: S,
-        d: D,
-        this_id: NodeID,
-        cond: &Rrc<Expr>,
-        body: &[Stmt],
-    ) -> io::Result<()> {
+    pub fn until(&mut self, s: S, d: D, this_id: NodeID, cond: &Rrc<Expr>, body: &[Stmt]) {
         let cond_id = self.id.new_id();
         let body_id = self.id.new_id();
-        self.begin_inputs()?;
-        self.input(s, d, "CONDITION", &cond.borrow(), cond_id)?;
-        self.substack("SUBSTACK", (!body.is_empty()).then_some(body_id))?;
-        self.end_obj()?; // inputs
-        self.end_obj()?; // node
-        self.expr(s, d, &cond.borrow(), cond_id, this_id)?;
+        self.input(s, d, "CONDITION", &cond.borrow(), cond_id);
+        self.substack("SUBSTACK", has_blocks(body).then_some(body_id));
+        self.expr(s, d, &cond.borrow(), cond_id, this_id);
         self.stmts(s, d, body, body_id, Some(this_id))
     }
 
@@ -107,89 +76,70 @@ where T: Write + Seek
         _type: &Type,
         _is_local: &bool,
         _is_cloud: &bool,
-    ) -> io::Result<()> {
+    ) {
         let value_id = self.id.new_id();
-        self.begin_inputs()?;
-        self.input(s, d, "VALUE", &value.borrow(), value_id)?;
-        self.end_obj()?; // inputs
+        self.input(s, d, "VALUE", &value.borrow(), value_id);
         match s.qualify_name(d, name) {
             Some(QualifiedName::Var(qualified_name, _)) => {
-                self.single_field_id("VARIABLE", &qualified_name)?
+                self.single_field_id("VARIABLE", &qualified_name)
             }
             Some(QualifiedName::List(..)) => {
                 d.report(
-                    DiagnosticKind::UnrecognizedVariable(name.basename().clone()),
+                    DiagnosticKind::UnrecognizedVariable {
+                        name: name.basename().clone(),
+                        suggestion: None,
+                    },
                     &name.span(),
                 );
             }
             None => {}
         }
-        self.end_obj()?; // node
         self.expr(s, d, &value.borrow(), value_id, this_id)
     }
 
-    pub fn change_var(
-        &mut self,
-        s: S,
-        d: D,
-        this_id: NodeID,
-        name: &Name,
-        value: &Rrc<Expr>,
-    ) -> io::Result<()> {
+    pub fn change_var(&mut self, s: S, d: D, this_id: NodeID, name: &Name, value: &Rrc<Expr>) {
         let value_id = self.id.new_id();
-        self.begin_inputs()?;
-        self.input(s, d, "VALUE", &value.borrow(), value_id)?;
-        self.end_obj()?; // inputs
+        self.input(s, d, "VALUE", &value.borrow(), value_id);
         match s.qualify_name(d, name) {
             Some(QualifiedName::Var(qualified_name, _)) => {
-                self.single_field_id("VARIABLE", &qualified_name)?
+                self.single_field_id("VARIABLE", &qualified_name)
             }
             Some(QualifiedName::List(..)) => {
                 d.report(
-                    DiagnosticKind::UnrecognizedVariable(name.basename().clone()),
+                    DiagnosticKind::UnrecognizedVariable {
+                        name: name.basename().clone(),
+                        suggestion: None,
+                    },
                     &name.span(),
                 );
             }
             None => {}
         }
-        self.end_obj()?; // node
         self.expr(s, d, &value.borrow(), value_id, this_id)
     }
 
-    pub fn show(&mut self, s: S, d: D, name: &Name) -> io::Result<()> {
-        self.begin_inputs()?;
-        self.end_obj()?; // inputs
+    pub fn show(&mut self, s: S, d: D, name: &Name) {
         match s.qualify_name(d, name) {
             Some(QualifiedName::Var(qualified_name, _)) => {
-                self.single_field_id("VARIABLE", &qualified_name)?
+                self.single_field_id("VARIABLE", &qualified_name)
             }
             Some(QualifiedName::List(qualified_name, _)) => {
-                self.single_field_id("LIST", &qualified_name)?
+                self.single_field_id("LIST", &qualified_name)
             }
             None => {}
         }
-        self.end_obj() // node
     }
 
-    pub fn hide(&mut self, s: S, d: D, name: &Name) -> io::Result<()> {
+    pub fn hide(&mut self, s: S, d: D, name: &Name) {
         self.show(s, d, name)
     }
 
-    pub fn add_to_list(
-        &mut self,
-        s: S,
-        d: D,
-        this_id: NodeID,
-        name: &Name,
-        value: &Rrc<Expr>,
-    ) -> io::Result<()> {
+    pub fn add_to_list(&mut self, s: S, d: D, this_id: NodeID, name: &Name, value: &Rrc<Expr>) {
         let value_id = self.id.new_id();
-        self.begin_inputs()?;
-        self.input(s, d, "ITEM", &value.borrow(), value_id)?;
-        self.end_obj()?; // inputs
+        self.input(s, d, "ITEM", &value.borrow(), value_id);
         match s.qualify_name(d, name) {
             Some(QualifiedName::List(qualified_name, _)) => {
-                self.single_field_id("LIST", &qualified_name)?
+                self.single_field_id("LIST", &qualified_name)
             }
             Some(QualifiedName::Var(..)) => {
                 d.report(
@@ -199,7 +149,6 @@ where T: Write + Seek
             }
             None => {}
         }
-        self.end_obj()?; // node
         self.expr(s, d, &value.borrow(), value_id, this_id)
     }
 
@@ -210,14 +159,12 @@ where T: Write + Seek
         this_id: NodeID,
         name: &Name,
         index: &Rrc<Expr>,
-    ) -> io::Result<()> {
+    ) {
         let index_id = self.id.new_id();
-        self.begin_inputs()?;
-        self.input(s, d, "INDEX", &index.borrow(), index_id)?;
-        self.end_obj()?; // inputs
+        self.input(s, d, "INDEX", &index.borrow(), index_id);
         match s.qualify_name(d, name) {
             Some(QualifiedName::List(qualified_name, _)) => {
-                self.single_field_id("LIST", &qualified_name)?
+                self.single_field_id("LIST", &qualified_name)
             }
             Some(QualifiedName::Var(..)) => {
                 d.report(
@@ -227,16 +174,13 @@ where T: Write + Seek
             }
             None => {}
         }
-        self.end_obj()?; // node
         self.expr(s, d, &index.borrow(), index_id, this_id)
     }
 
-    pub fn delete_list(&mut self, s: S, d: D, name: &Name) -> io::Result<()> {
-        self.begin_inputs()?;
-        self.end_obj()?; // inputs
+    pub fn delete_list(&mut self, s: S, d: D, name: &Name) {
         match s.qualify_name(d, name) {
             Some(QualifiedName::List(qualified_name, _)) => {
-                self.single_field_id("LIST", &qualified_name)?
+                self.single_field_id("LIST", &qualified_name)
             }
             Some(QualifiedName::Var(..)) => {
                 d.report(
@@ -246,7 +190,6 @@ where T: Write + Seek
             }
             None => {}
         }
-        self.end_obj() // node
     }
 
     pub fn list_insert(
@@ -257,16 +200,14 @@ where T: Write + Seek
         name: &Name,
         index: &Rrc<Expr>,
         value: &Rrc<Expr>,
-    ) -> io::Result<()> {
+    ) {
         let index_id = self.id.new_id();
         let value_id = self.id.new_id();
-        self.begin_inputs()?;
-        self.input(s, d, "INDEX", &index.borrow(), index_id)?;
-        self.input(s, d, "ITEM", &value.borrow(), value_id)?;
-        self.end_obj()?; // inputs
+        self.input(s, d, "INDEX", &index.borrow(), index_id);
+        self.input(s, d, "ITEM", &value.borrow(), value_id);
         match s.qualify_name(d, name) {
             Some(QualifiedName::List(qualified_name, _)) => {
-                self.single_field_id("LIST", &qualified_name)?
+                self.single_field_id("LIST", &qualified_name)
             }
             Some(QualifiedName::Var(..)) => {
                 d.report(
@@ -276,8 +217,7 @@ where T: Write + Seek
             }
             None => {}
         }
-        self.end_obj()?; // node
-        self.expr(s, d, &index.borrow(), index_id, this_id)?;
+        self.expr(s, d, &index.borrow(), index_id, this_id);
         self.expr(s, d, &value.borrow(), value_id, this_id)
     }
 
@@ -289,7 +229,7 @@ where T: Write + Seek
         name: &Name,
         index: &Rrc<Expr>,
         value: &Rrc<Expr>,
-    ) -> io::Result<()> {
+    ) {
         self.list_insert(s, d, this_id, name, index, value)
     }
 
@@ -301,7 +241,7 @@ where T: Write + Seek
         block: &Block,
         span: &Span,
         args: &Vec<Kwarg>,
-    ) -> io::Result<()> {
+    ) {
         if block.args().len() != args.len() {
             d.report(
                 DiagnosticKind::BlockArgsCountMismatch {
@@ -311,7 +251,6 @@ where T: Write + Seek
                 span,
             )
         }
-        self.begin_inputs()?;
         let arg_ids: Vec<NodeID> = (&mut self.id).take(args.len()).collect();
         let menu_id = block.menu().map(|_| self.id.new_id());
         let mut menu_value = None;
@@ -330,43 +269,36 @@ where T: Write + Seek
                         &arg_value.value.borrow(),
                         arg_id,
                         menu_id.unwrap(),
-                    )?;
+                    );
                 }
             } else {
-                self.input(s, d, arg_name, &arg_value.value.borrow(), arg_id)?;
+                self.input(s, d, arg_name, &arg_value.value.borrow(), arg_id);
             }
         }
         if menu_is_default {
-            write_comma_io(&mut self.zip, &mut self.inputs_comma)?;
-            write!(
-                self,
-                r#""{}":[1,{}]"#,
+            self.set_input(
                 block.menu().unwrap().input,
-                menu_id.unwrap()
-            )?;
+                sb3::Input::Shadow(Some(menu_id.unwrap().into())),
+            );
         }
-        self.end_obj()?; // inputs
         if let Some(fields) = block.fields() {
-            write!(self, r#","fields":{fields}"#)?;
+            self.fields(fields);
         }
-        self.end_obj()?; // node
         for (kwarg, arg_id) in args.iter().zip(arg_ids) {
-            self.expr(s, d, &kwarg.value.borrow(), arg_id, this_id)?;
+            self.expr(s, d, &kwarg.value.borrow(), arg_id, this_id);
         }
         if let Some(menu) = block.menu() {
-            self.begin_node(
+            self.add_node(
                 Node::new(menu.opcode, menu_id.unwrap())
                     .parent_id(this_id)
                     .shadow(true),
-            )?;
+            );
             if let Some(menu_value) = menu_value {
-                self.single_field(menu.input, &menu_value.to_string())?;
+                self.single_field(menu.input, &menu_value.to_string());
             } else {
-                self.single_field(menu.input, menu.default)?;
+                self.single_field(menu.input, menu.default);
             }
-            self.end_obj()?; // node
         }
-        Ok(())
     }
 
     pub fn proc_call(
@@ -377,10 +309,32 @@ where T: Write + Seek
         name: &SmolStr,
         span: &Span,
         args: &Vec<Kwarg>,
-    ) -> io::Result<()> {
+    ) {
         let Some(proc) = s.sprite.procs.get(name) else {
-            d.report(DiagnosticKind::UnrecognizedProcedure(name.clone()), span);
-            return Ok(());
+            let suggestion = suggest(
+                name,
+                s.sprite
+                    .procs
+                    .keys()
+                    .map(SmolStr::as_str)
+                    .chain(Block::all_names().iter().copied()),
+            );
+            let kind = if suggestion
+                .as_ref()
+                .is_some_and(|suggestion| !s.sprite.procs.contains_key(suggestion))
+            {
+                DiagnosticKind::UnrecognizedBlock {
+                    name: name.clone(),
+                    suggestion,
+                }
+            } else {
+                DiagnosticKind::UnrecognizedProcedure {
+                    name: name.clone(),
+                    suggestion,
+                }
+            };
+            d.report(kind, span);
+            return;
         };
         if proc.args.len() != args.len() {
             d.report(
@@ -393,12 +347,11 @@ where T: Write + Seek
         }
         let mut qualified_args: Vec<(SmolStr, NodeID)> = Vec::new();
         let mut qualified_arg_values: Vec<Rrc<Expr>> = Vec::new();
-        self.begin_inputs()?;
         for (arg, kwarg) in proc.args.iter().zip(args) {
             match &arg.type_ {
                 Type::Value => {
                     let arg_id = self.id.new_id();
-                    self.input(s, d, &arg.name, &kwarg.value.borrow(), arg_id)?;
+                    self.input(s, d, &arg.name, &kwarg.value.borrow(), arg_id);
                     qualified_args.push((arg.name.clone(), arg_id));
                     qualified_arg_values.push(kwarg.value.clone());
                 }
@@ -429,15 +382,13 @@ where T: Write + Seek
                                 );
                                 continue;
                             }
-                            if struct_literal_fields.len() != struct_.fields.len() {
-                                panic!()
-                            }
-                            for (struct_field, struct_literal_field) in
-                                struct_.fields.iter().zip(struct_literal_fields)
-                            {
-                                if struct_field.name != struct_literal_field.name {
-                                    panic!()
-                                }
+                            if !check_struct_literal_fields(
+                                d,
+                                struct_,
+                                struct_literal_span,
+                                struct_literal_fields,
+                            ) {
+                                continue;
                             }
                             struct_literal_fields
                         }
@@ -456,48 +407,42 @@ where T: Write + Seek
                             &qualified_arg_name,
                             &struct_literal_field.value.borrow(),
                             arg_id,
-                        )?;
+                        );
                         qualified_args.push((qualified_arg_name, arg_id));
                         qualified_arg_values.push(struct_literal_field.value.clone());
                     }
                 }
             }
         }
-        self.end_obj()?; // inputs
-        write!(
-            self,
-            "{}",
-            Mutation::call(proc.name.clone(), &qualified_args, proc.warp)
-        )?;
-        self.end_obj()?; // node
+        self.mutation(Mutation::call(
+            proc.name.clone(),
+            &qualified_args,
+            proc.warp,
+        ));
         for (arg, (_, arg_id)) in qualified_arg_values.iter().zip(qualified_args) {
-            self.expr(s, d, &arg.borrow(), arg_id, this_id)?;
+            self.expr(s, d, &arg.borrow(), arg_id, this_id);
         }
-        Ok(())
     }
 
-    pub fn return_(&mut self, s: S, d: D, this_id: NodeID, value: &Rrc<Expr>) -> io::Result<()> {
+    pub fn return_(&mut self, s: S, d: D, this_id: NodeID, value: &Rrc<Expr>) {
         let Some(func) = s.func else { panic!() };
         let value_id = self.id.new_id();
-        self.begin_inputs()?;
-        self.input(s, d, "VALUE", &value.borrow(), value_id)?;
-        self.end_obj()?; // inputs
-        self.single_field_id("VARIABLE", &func.name)?;
-        self.end_obj()?; // node
+        self.input(s, d, "VALUE", &value.borrow(), value_id);
+        self.single_field_id("VARIABLE", &func.name);
         self.expr(s, d, &value.borrow(), value_id, this_id)
     }
 
-    pub fn set_call_site(&mut self, id: usize, func: &SmolStr) -> io::Result<()> {
-        self.begin_inputs()?;
-        write!(self, r#""VALUE":"#)?;
-        write!(
-            self,
-            r#"[3,[12,{},{}],[10, ""]]"#,
-            json!(**func),
-            json!(**func)
-        )?;
-        self.end_obj()?; // inputs
-        self.single_field_id("VARIABLE", &format!("c{id}"))?;
-        self.end_obj() // node
+    pub fn set_call_site(&mut self, id: usize, func: &SmolStr) {
+        self.set_input(
+            "VALUE",
+            sb3::Input::Obscured(
+                Some(sb3::InputValue::Primitive(sb3::Primitive::variable(
+                    &**func,
+                ))),
+                Some(sb3::InputValue::Primitive(sb3::Primitive::text(""))),
+            ),
+        );
+        self.single_field_id("VARIABLE", &format!("c{id}"));
+        self.call_sites.push(id);
     }
 }
@@ -1,6 +1,8 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
 pub struct Config {
     #[serde(default)]
     pub frame_rate: Option<u64>,
@@ -18,4 +20,196 @@ pub struct Config {
     pub stage_width: Option<u64>,
     #[serde(default)]
     pub stage_height: Option<u64>,
+    #[serde(default)]
+    pub debug_compression: Option<Compression>,
+    #[serde(default)]
+    pub release_compression: Option<Compression>,
+    #[serde(default)]
+    pub compression_level: Option<i64>,
+    #[serde(default)]
+    pub allow_exec: Option<bool>,
+    #[serde(default)]
+    pub command_timeout: Option<f64>,
+    #[serde(default)]
+    pub cache: Option<bool>,
+    #[serde(skip)]
+    pub cache_mode: CacheMode,
+    #[serde(default)]
+    pub test_max_steps: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include_paths: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub monitors: BTreeMap<String, BTreeMap<String, Monitor>>,
+    #[serde(default)]
+    pub stage: Option<StageProperties>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sprites: BTreeMap<String, SpriteProperties>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub defines: BTreeMap<String, Define>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
+pub struct StageProperties {
+    #[serde(default)]
+    pub current_costume: Option<CostumeRef>,
+    #[serde(default)]
+    pub tempo: Option<f64>,
+    #[serde(default)]
+    pub volume: Option<f64>,
+    #[serde(default)]
+    pub video_state: Option<VideoState>,
+    #[serde(default)]
+    pub video_transparency: Option<f64>,
+    #[serde(default)]
+    pub text_to_speech_language: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
+pub struct SpriteProperties {
+    #[serde(default)]
+    pub x: Option<f64>,
+    #[serde(default)]
+    pub y: Option<f64>,
+    #[serde(default)]
+    pub size: Option<f64>,
+    #[serde(default)]
+    pub direction: Option<f64>,
+    #[serde(default)]
+    pub visible: Option<bool>,
+    #[serde(default)]
+    pub draggable: Option<bool>,
+    #[serde(default)]
+    pub rotation_style: Option<RotationStyle>,
+    #[serde(default)]
+    pub layer_order: Option<i64>,
+    #[serde(default)]
+    pub current_costume: Option<CostumeRef>,
+    #[serde(default)]
+    pub volume: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum CostumeRef {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum Define {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    #[serde(skip)]
+    Source(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum RotationStyle {
+    #[serde(rename = "all around")]
+    AllAround,
+    #[serde(rename = "left-right")]
+    LeftRight,
+    #[serde(rename = "don't rotate")]
+    DontRotate,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum VideoState {
+    On,
+    Off,
+    OnFlipped,
+}
+
+impl Define {
+    pub fn source(&self) -> String {
+        match self {
+            Define::Bool(bool) => bool.to_string(),
+            Define::Integer(integer) => integer.to_string(),
+            Define::Float(float) => float.to_string(),
+            Define::String(string) => serde_json::to_string(string).unwrap(),
+            Define::Source(source) => source.clone(),
+        }
+    }
+}
+
+impl RotationStyle {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AllAround => "all around",
+            Self::LeftRight => "left-right",
+            Self::DontRotate => "don't rotate",
+        }
+    }
+}
+
+impl VideoState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::On => "on",
+            Self::Off => "off",
+            Self::OnFlipped => "on-flipped",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
+pub struct Monitor {
+    #[serde(default)]
+    pub mode: Option<MonitorMode>,
+    #[serde(default)]
+    pub x: Option<f64>,
+    #[serde(default)]
+    pub y: Option<f64>,
+    #[serde(default)]
+    pub width: Option<f64>,
+    #[serde(default)]
+    pub height: Option<f64>,
+    #[serde(default)]
+    pub visible: Option<bool>,
+    #[serde(default)]
+    pub slider_min: Option<f64>,
+    #[serde(default)]
+    pub slider_max: Option<f64>,
+    #[serde(default)]
+    pub discrete: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorMode {
+    Default,
+    Large,
+    Slider,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Store,
+    Deflate,
+}
+
+/// How a build uses the cache of command output, whatever `cache` is set to.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum CacheMode {
+    #[default]
+    ReadWrite,
+    /// Cached output is used, but nothing is written, for checking a project without
+    /// writing to its directory.
+    ReadOnly,
+    Off,
+}
+
+impl Config {
+    pub fn compression(&self, release: bool) -> Compression {
+        if release {
+            self.release_compression.unwrap_or(Compression::Deflate)
+        } else {
+            self.debug_compression.unwrap_or(Compression::Store)
+        }
+    }
 }
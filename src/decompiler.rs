@@ -0,0 +1,149 @@
+mod names;
+mod shapes;
+mod target;
+
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use names::{sanitize_file_name, Namespace};
+use shapes::Shapes;
+use target::{string, Target};
+
+use crate::sb3::{self, Archive};
+
+pub struct Decompiled {
+    pub files: Vec<(PathBuf, Vec<u8>)>,
+    pub warnings: Vec<Warning>,
+}
+
+pub enum Warning {
+    Unsupported {
+        target: String,
+        id: String,
+        opcode: String,
+    },
+    Renamed {
+        from: String,
+        to: String,
+    },
+    Sounds {
+        target: String,
+        count: usize,
+    },
+    MissingAsset {
+        target: String,
+        costume: String,
+    },
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unsupported { target, id, opcode } => write!(
+                f,
+                "{target}: block {id:?} ({opcode}) cannot be represented in goboscript, and \
+                 was left out"
+            ),
+            Self::Renamed { from, to } => write!(
+                f,
+                "sprite {from:?} was renamed to {to:?}, as its name cannot be the name of a \
+                 sprite's file"
+            ),
+            Self::Sounds { target, count } => write!(
+                f,
+                "{target}: {count} sound(s) were left out, as goboscript does not support \
+                 sounds"
+            ),
+            Self::MissingAsset { target, costume } => write!(
+                f,
+                "{target}: costume {costume:?} is missing from the project, and was left out"
+            ),
+        }
+    }
+}
+
+pub fn decompile(archive: &Archive) -> anyhow::Result<Decompiled> {
+    let shapes = Shapes::new();
+    let targets = &archive.project.targets;
+    let stage = targets
+        .iter()
+        .find(|target| target.is_stage)
+        .context("project.json has no Stage")?;
+    let mut decompiled = Decompiled {
+        files: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let mut file_names = Namespace::ignoring_case();
+    file_names.reserve("stage");
+    let (source, globals, warnings) = Target::new(&shapes, stage, None).decompile();
+    decompiled.add_target(stage, "stage", source, warnings, archive);
+    for sprite in targets.iter().filter(|target| !target.is_stage) {
+        let name = sprite.name.as_str();
+        let file_name = file_names.claim(sanitize_file_name(name));
+        if file_name != name {
+            decompiled.warnings.push(Warning::Renamed {
+                from: name.to_owned(),
+                to: file_name.clone(),
+            });
+        }
+        let (source, _, warnings) = Target::new(&shapes, sprite, Some(&globals)).decompile();
+        decompiled.add_target(sprite, &file_name, source, warnings, archive);
+    }
+    Ok(decompiled)
+}
+
+impl Decompiled {
+    fn add_target(
+        &mut self,
+        target: &sb3::Target,
+        file_name: &str,
+        source: String,
+        warnings: Vec<Warning>,
+        archive: &Archive,
+    ) {
+        let name = target.name.as_str();
+        let mut costumes = Vec::new();
+        let mut costume_names = Namespace::ignoring_case();
+        for costume in &target.costumes {
+            let costume_name = costume.name.as_str();
+            let md5ext = costume.md5ext();
+            let Some(contents) = archive.asset(&md5ext) else {
+                self.warnings.push(Warning::MissingAsset {
+                    target: name.to_owned(),
+                    costume: costume_name.to_owned(),
+                });
+                continue;
+            };
+            let (_, extension) = md5ext.rsplit_once('.').unwrap_or((&md5ext, "svg"));
+            let stem = costume_names.claim(sanitize_file_name(costume_name));
+            let path = format!("{file_name}/{stem}.{extension}");
+            self.files.push((PathBuf::from(&path), contents.to_vec()));
+            if stem == costume_name {
+                costumes.push(string(&path));
+            } else {
+                costumes.push(format!("{} as {}", string(&path), string(costume_name)));
+            }
+        }
+        let mut file = String::new();
+        if !costumes.is_empty() {
+            file.push_str(&format!("costumes {};\n", costumes.join(", ")));
+            if !source.is_empty() {
+                file.push('\n');
+            }
+        }
+        file.push_str(&source);
+        self.files
+            .push((PathBuf::from(format!("{file_name}.gs")), file.into_bytes()));
+        let sounds = target.sounds.len();
+        if sounds > 0 {
+            self.warnings.push(Warning::Sounds {
+                target: name.to_owned(),
+                count: sounds,
+            });
+        }
+        self.warnings.extend(warnings);
+    }
+}
@@ -0,0 +1,84 @@
+use fxhash::FxHashSet;
+use logos::Logos;
+
+use crate::lexer::token::Token;
+
+pub fn is_name(name: &str) -> bool {
+    let mut lexer = Token::lexer(name);
+    matches!(lexer.next(), Some(Ok(Token::Name(_))))
+        && lexer.span() == (0..name.len())
+        && lexer.next().is_none()
+}
+
+pub fn sanitize(name: &str) -> String {
+    let mut sanitized: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if sanitized.is_empty() || sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    if !is_name(&sanitized) {
+        sanitized.push('_');
+    }
+    sanitized
+}
+
+pub fn sanitize_file_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.is_empty() {
+        sanitized.push('_');
+    }
+    sanitized
+}
+
+#[derive(Default, Clone)]
+pub struct Namespace {
+    used: FxHashSet<String>,
+    /// Whether names which only differ in case are the same, as file names are on some
+    /// file systems.
+    ignore_case: bool,
+}
+
+impl Namespace {
+    pub fn ignoring_case() -> Self {
+        Self {
+            ignore_case: true,
+            ..Default::default()
+        }
+    }
+
+    fn key(&self, name: &str) -> String {
+        if self.ignore_case {
+            name.to_lowercase()
+        } else {
+            name.to_owned()
+        }
+    }
+
+    pub fn reserve(&mut self, name: &str) {
+        self.used.insert(self.key(name));
+    }
+
+    pub fn claim(&mut self, name: String) -> String {
+        let mut claimed = name.clone();
+        let mut suffix = 2;
+        while self.used.contains(&self.key(&claimed)) {
+            claimed = format!("{name}_{suffix}");
+            suffix += 1;
+        }
+        self.used.insert(self.key(&claimed));
+        claimed
+    }
+}
@@ -0,0 +1,126 @@
+use fxhash::FxHashMap;
+use indexmap::IndexMap;
+
+use crate::{
+    blocks::{BinOp, Block, Menu, Repr, UnOp},
+    sb3::Field,
+};
+
+pub struct Shape {
+    pub name: &'static str,
+    pub args: &'static [&'static str],
+    pub fields: Option<IndexMap<String, Field>>,
+    pub menu: Option<Menu>,
+}
+
+impl Shape {
+    fn has_fixed_menu(&self) -> bool {
+        self.menu
+            .as_ref()
+            .is_some_and(|menu| !self.args.contains(&menu.input))
+    }
+}
+
+pub struct Shapes {
+    pub blocks: FxHashMap<&'static str, Vec<Shape>>,
+    pub reprs: FxHashMap<&'static str, Vec<Shape>>,
+    pub unops: FxHashMap<&'static str, Vec<UnOp>>,
+    pub binops: FxHashMap<&'static str, BinOp>,
+}
+
+macro_rules! shapes {
+    ($type:ident) => {{
+        let mut shapes: FxHashMap<&'static str, Vec<Shape>> = FxHashMap::default();
+        for name in $type::all_names() {
+            let overloads = $type::overloads(name);
+            let variants = if overloads.is_empty() {
+                $type::from_shape(name, 0).into_iter().collect()
+            } else {
+                overloads.to_vec()
+            };
+            for variant in variants {
+                shapes.entry(variant.opcode()).or_default().push(Shape {
+                    name: variant.name(),
+                    args: variant.args(),
+                    fields: variant
+                        .fields()
+                        .map(|fields| serde_json::from_str(fields).unwrap()),
+                    menu: variant.menu(),
+                });
+            }
+        }
+        for shapes in shapes.values_mut() {
+            shapes.sort_by_key(|shape| !shape.has_fixed_menu());
+        }
+        shapes
+    }};
+}
+
+impl Shapes {
+    pub fn new() -> Self {
+        use UnOp::*;
+        let mut unops: FxHashMap<&'static str, Vec<UnOp>> = FxHashMap::default();
+        for op in [
+            Not, Length, Round, Abs, Floor, Ceil, Sqrt, Sin, Cos, Tan, Asin, Acos, Atan, Ln, Log,
+            AntiLn, AntiLog,
+        ] {
+            unops.entry(op.opcode()).or_default().push(op);
+        }
+        use BinOp::*;
+        let binops = [Add, Sub, Mul, Div, Mod, Lt, Gt, Eq, And, Or, Join, In, Of]
+            .into_iter()
+            .map(|op| (op.opcode(), op))
+            .collect();
+        Self {
+            blocks: shapes!(Block),
+            reprs: shapes!(Repr),
+            unops,
+            binops,
+        }
+    }
+}
+
+pub fn unop_keyword(op: UnOp) -> &'static str {
+    match op {
+        UnOp::Not => "not",
+        UnOp::Length => "length",
+        UnOp::Round => "round",
+        UnOp::Abs => "abs",
+        UnOp::Floor => "floor",
+        UnOp::Ceil => "ceil",
+        UnOp::Sqrt => "sqrt",
+        UnOp::Sin => "sin",
+        UnOp::Cos => "cos",
+        UnOp::Tan => "tan",
+        UnOp::Asin => "asin",
+        UnOp::Acos => "acos",
+        UnOp::Atan => "atan",
+        UnOp::Ln => "ln",
+        UnOp::Log => "log",
+        UnOp::AntiLn => "antiln",
+        UnOp::AntiLog => "antilog",
+        UnOp::Minus => "-",
+    }
+}
+
+pub fn binop_syntax(op: BinOp) -> (&'static str, u8, bool) {
+    match op {
+        BinOp::Mul => ("*", 3, false),
+        BinOp::Div => ("/", 3, false),
+        BinOp::FloorDiv => ("//", 3, false),
+        BinOp::Mod => ("%", 3, false),
+        BinOp::Add => ("+", 4, false),
+        BinOp::Sub => ("-", 4, false),
+        BinOp::Lt => ("<", 5, false),
+        BinOp::Le => ("<=", 5, false),
+        BinOp::Gt => (">", 5, false),
+        BinOp::Ge => (">=", 5, false),
+        BinOp::Join => ("&", 6, true),
+        BinOp::In => ("in", 7, false),
+        BinOp::Eq => ("==", 7, false),
+        BinOp::Ne => ("!=", 7, false),
+        BinOp::And => ("and", 8, false),
+        BinOp::Or => ("or", 9, false),
+        BinOp::Of => ("[]", 1, false),
+    }
+}
@@ -0,0 +1,1047 @@
+use std::fmt::Write;
+
+use fxhash::{FxHashMap, FxHashSet};
+use indexmap::IndexMap;
+use serde_json::Value;
+
+use super::{
+    names::{sanitize, Namespace},
+    shapes::{binop_syntax, unop_keyword, Shape, Shapes},
+    Warning,
+};
+use crate::{
+    blocks::{BinOp, Block, Repr, UnOp},
+    sb3::{self, Field, Input, InputValue, Node, Primitive},
+};
+
+/// The name of the procedure which declares the variables that are not set anywhere
+/// else, so that goboscript knows about them. It is never called.
+const DECLARATIONS: &str = "declare_variables";
+
+#[derive(Default)]
+pub struct Globals<'a> {
+    vars: FxHashMap<&'a str, String>,
+    lists: FxHashMap<&'a str, String>,
+    names: Namespace,
+}
+
+struct Arg {
+    id: String,
+    original: String,
+    name: String,
+    is_bool: bool,
+}
+
+struct Proc<'a> {
+    def_id: &'a str,
+    name: String,
+    args: Vec<Arg>,
+    warp: bool,
+}
+
+struct Code {
+    text: String,
+    prec: u8,
+    is_list: bool,
+}
+
+impl Code {
+    fn term(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            prec: 1,
+            is_list: false,
+        }
+    }
+
+    fn op(text: String, prec: u8) -> Self {
+        Self {
+            text,
+            prec,
+            is_list: false,
+        }
+    }
+
+    fn at(self, prec: u8) -> String {
+        if self.prec <= prec {
+            self.text
+        } else {
+            format!("({})", self.text)
+        }
+    }
+
+    /// The code as the operand of a string operator. A list on its own would be taken
+    /// as the list, and not its contents, so it is joined with an empty string.
+    fn into_string(self, prec: u8) -> String {
+        if self.is_list {
+            format!("({} & \"\")", self.text)
+        } else {
+            self.at(prec)
+        }
+    }
+}
+
+pub fn string(text: &str) -> String {
+    serde_json::to_string(text).unwrap()
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+pub fn number(value: &Value) -> String {
+    let text = text(value);
+    let (int, frac) = text.split_once('.').unwrap_or((&text, ""));
+    let is_canonical_int = int == "0"
+        || (!int.is_empty()
+            && int.len() <= 15
+            && !int.starts_with('0')
+            && int.bytes().all(|c| c.is_ascii_digit()));
+    let is_literal = is_canonical_int
+        && (!text.contains('.')
+            || (!frac.is_empty()
+                && frac.bytes().all(|c| c.is_ascii_digit())
+                && text
+                    .parse::<f64>()
+                    .is_ok_and(|float| float.to_string() == text)));
+    if is_literal {
+        text
+    } else {
+        string(&text)
+    }
+}
+
+pub fn literal(value: &Value) -> String {
+    match value {
+        Value::Number(_) => number(value),
+        Value::Null => "0".to_owned(),
+        value => string(&text(value)),
+    }
+}
+
+fn proccode_name(proccode: &str) -> String {
+    proccode
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "%s" | "%n" | "%b"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn field_value<'b>(block: &'b sb3::Block, name: &str) -> Option<&'b Value> {
+    block.fields.get(name).map(|field| &field.value)
+}
+
+/// Orders IDs such as `a:9:1` before `a:10:1`, so that the scripts of a project built by
+/// goboscript come out in the order of its source.
+fn id_order(id: &str) -> Vec<(usize, &str)> {
+    id.split(':').map(|part| (part.len(), part)).collect()
+}
+
+pub struct Target<'a> {
+    shapes: &'a Shapes,
+    name: &'a str,
+    target: &'a sb3::Target,
+    blocks: &'a IndexMap<String, Node>,
+    vars: FxHashMap<&'a str, String>,
+    lists: FxHashMap<&'a str, String>,
+    own_vars: Vec<&'a str>,
+    own_lists: Vec<&'a str>,
+    cloud_vars: FxHashSet<&'a str>,
+    locals: FxHashMap<&'a str, &'a str>,
+    procs: FxHashMap<String, Proc<'a>>,
+    funcs: FxHashMap<String, &'a str>,
+    names: Namespace,
+    declarations: String,
+    // The state of writing the scripts, which are written again if a procedure turns out
+    // not to be a function.
+    proc: Option<String>,
+    declared_locals: FxHashSet<&'a str>,
+    pending: FxHashMap<&'a str, &'a str>,
+    bound: FxHashMap<&'a str, String>,
+    plain: FxHashSet<&'a str>,
+    assigned: FxHashSet<&'a str>,
+    not_funcs: FxHashSet<String>,
+    warnings: Vec<Warning>,
+    out: String,
+    indent: usize,
+}
+
+impl<'a> Target<'a> {
+    pub fn new(shapes: &'a Shapes, target: &'a sb3::Target, globals: Option<&Globals<'a>>) -> Self {
+        let mut this = Self {
+            shapes,
+            name: &target.name,
+            target,
+            blocks: &target.blocks,
+            vars: globals.map(|g| g.vars.clone()).unwrap_or_default(),
+            lists: globals.map(|g| g.lists.clone()).unwrap_or_default(),
+            own_vars: Vec::new(),
+            own_lists: Vec::new(),
+            cloud_vars: FxHashSet::default(),
+            locals: FxHashMap::default(),
+            procs: FxHashMap::default(),
+            funcs: FxHashMap::default(),
+            names: globals.map(|g| g.names.clone()).unwrap_or_default(),
+            declarations: String::new(),
+            proc: None,
+            declared_locals: FxHashSet::default(),
+            pending: FxHashMap::default(),
+            bound: FxHashMap::default(),
+            plain: FxHashSet::default(),
+            assigned: FxHashSet::default(),
+            not_funcs: FxHashSet::default(),
+            warnings: Vec::new(),
+            out: String::new(),
+            indent: 0,
+        };
+        this.declare();
+        this
+    }
+
+    pub fn globals(&self) -> Globals<'a> {
+        Globals {
+            vars: self.vars.clone(),
+            lists: self.lists.clone(),
+            names: self.names.clone(),
+        }
+    }
+
+    fn block(&self, id: &str) -> Option<&'a sb3::Block> {
+        self.target.block(id)
+    }
+
+    fn top(&self, mut id: &'a str) -> &'a str {
+        for _ in 0..self.blocks.len() {
+            match self.block(id).and_then(|block| block.parent.as_deref()) {
+                Some(parent) => id = parent,
+                None => break,
+            }
+        }
+        id
+    }
+
+    fn declare(&mut self) {
+        let mut var_refs: Vec<(&'a str, &'a str, &'a str)> = Vec::new();
+        let mut list_refs: Vec<(&'a str, &'a str)> = Vec::new();
+        let mut set_vars: FxHashSet<&'a str> = FxHashSet::default();
+        for (id, node) in self.blocks {
+            let block = match node {
+                Node::Block(block) => block,
+                Node::Primitive(Primitive::Variable { name, id: var, .. }) => {
+                    var_refs.push((var, name, id));
+                    continue;
+                }
+                Node::Primitive(Primitive::List { name, id: list, .. }) => {
+                    list_refs.push((list, name));
+                    continue;
+                }
+                Node::Primitive(_) => continue,
+            };
+            let top = self.top(id);
+            if let Some(var) = block.field_id("VARIABLE") {
+                var_refs.push((var, block.field("VARIABLE").unwrap_or_default(), top));
+                if block.opcode == "data_setvariableto" {
+                    set_vars.insert(var);
+                }
+            }
+            if let Some(list) = block.field_id("LIST") {
+                list_refs.push((list, block.field("LIST").unwrap_or_default()));
+            }
+            let values = block
+                .inputs
+                .values()
+                .flat_map(|input| [input.value(), input.shadow()])
+                .flatten();
+            for value in values {
+                match value {
+                    InputValue::Primitive(Primitive::Variable { name, id: var, .. }) => {
+                        var_refs.push((var, name, top))
+                    }
+                    InputValue::Primitive(Primitive::List { name, id: list, .. }) => {
+                        list_refs.push((list, name))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut var_names: Vec<(&'a str, &'a str)> = Vec::new();
+        for (id, var) in &self.target.variables {
+            let name = var.name.as_str();
+            if var.is_cloud {
+                self.cloud_vars.insert(id);
+                // goboscript adds the cloud to the name of a cloud variable.
+                var_names.push((id, name.trim_start_matches('\u{2601}').trim_start()));
+            } else {
+                var_names.push((id, name));
+            }
+        }
+        for &(id, name, _) in &var_refs {
+            if !self.vars.contains_key(id) && !var_names.iter().any(|&(var, _)| var == id) {
+                var_names.push((id, name));
+            }
+        }
+        let mut list_names: Vec<(&'a str, &'a str)> = Vec::new();
+        for (id, list) in &self.target.lists {
+            list_names.push((id, &list.name));
+        }
+        for &(id, name) in &list_refs {
+            if !self.lists.contains_key(id) && !list_names.iter().any(|&(list, _)| list == id) {
+                list_names.push((id, name));
+            }
+        }
+
+        let mut proc_names = Namespace::default();
+        for name in Block::all_names().iter().chain(Repr::all_names()) {
+            proc_names.reserve(name);
+        }
+        for id in self.top_level() {
+            let Some(block) = self.block(id) else {
+                continue;
+            };
+            if block.opcode != "procedures_definition" {
+                continue;
+            }
+            let Some(mutation) = block
+                .input_block("custom_block")
+                .and_then(|id| self.block(id))
+                .and_then(|prototype| prototype.mutation.as_ref())
+            else {
+                continue;
+            };
+            let proccode = mutation.proccode.as_deref().unwrap_or_default();
+            let mut arg_names = Namespace::default();
+            let kinds = proccode
+                .split_whitespace()
+                .filter(|word| matches!(*word, "%s" | "%n" | "%b"));
+            let args = mutation
+                .argumentids
+                .iter()
+                .flatten()
+                .cloned()
+                .zip(mutation.argumentnames.iter().flatten().cloned())
+                .zip(kinds)
+                .map(|((id, original), kind)| Arg {
+                    id,
+                    name: arg_names.claim(sanitize(&original)),
+                    original,
+                    is_bool: kind == "%b",
+                })
+                .collect();
+            let name = proc_names.claim(sanitize(&proccode_name(proccode)));
+            self.procs.insert(
+                proccode.to_owned(),
+                Proc {
+                    def_id: id,
+                    name,
+                    args,
+                    warp: mutation.warp == Some(true),
+                },
+            );
+        }
+        self.declarations = proc_names.claim(DECLARATIONS.to_owned());
+
+        for (id, name) in var_names {
+            let refs = || var_refs.iter().filter(|&&(var, _, _)| var == id);
+            let owner = self.procs.iter().find_map(|(proccode, proc)| {
+                let local = name
+                    .strip_prefix(&proccode_name(proccode))?
+                    .strip_prefix(':')?;
+                (!local.is_empty() && refs().all(|&(_, _, top)| top == proc.def_id))
+                    .then_some((proc.def_id, local))
+            });
+            match owner {
+                Some((def_id, local)) if set_vars.contains(id) => {
+                    self.locals.insert(id, def_id);
+                    self.vars.insert(id, self.names.claim(sanitize(local)));
+                }
+                _ => {
+                    self.own_vars.push(id);
+                    self.vars.insert(id, self.names.claim(sanitize(name)));
+                }
+            }
+        }
+        for (id, name) in list_names {
+            self.own_lists.push(id);
+            self.lists.insert(id, self.names.claim(sanitize(name)));
+        }
+
+        for (proccode, proc) in &self.procs {
+            let name = proccode_name(proccode);
+            let var = self.own_vars.iter().find(|&&var| {
+                self.target
+                    .variables
+                    .get(var)
+                    .is_some_and(|var| var.name == name)
+                    || var_refs
+                        .iter()
+                        .any(|&(id, var_name, _)| id == var && var_name == name)
+            });
+            if let (true, Some(var)) = (proc.warp, var) {
+                self.funcs.insert(proccode.clone(), var);
+            }
+        }
+    }
+
+    fn top_level(&self) -> Vec<&'a str> {
+        let mut top_level: Vec<(&'a str, (f64, f64))> = self
+            .blocks
+            .iter()
+            .filter_map(|(id, node)| {
+                let position = match node {
+                    Node::Block(block) if block.top_level => {
+                        (block.x.unwrap_or_default(), block.y.unwrap_or_default())
+                    }
+                    Node::Block(_) => return None,
+                    Node::Primitive(
+                        Primitive::Variable { position, .. } | Primitive::List { position, .. },
+                    ) => position.unwrap_or_default(),
+                    Node::Primitive(_) => (0.0, 0.0),
+                };
+                Some((id.as_str(), position))
+            })
+            .collect();
+        top_level.sort_by(|(a_id, (a_x, a_y)), (b_id, (b_x, b_y))| {
+            a_y.total_cmp(b_y)
+                .then_with(|| a_x.total_cmp(b_x))
+                .then_with(|| id_order(a_id).cmp(&id_order(b_id)))
+        });
+        top_level.into_iter().map(|(id, _)| id).collect()
+    }
+
+    pub fn decompile(mut self) -> (String, Globals<'a>, Vec<Warning>) {
+        loop {
+            self.scripts();
+            let mut not_funcs: Vec<String> = self.not_funcs.drain().collect();
+            for (var, func) in &self.bound {
+                if self.plain.contains(var) {
+                    not_funcs.push(func.clone());
+                }
+            }
+            for (func, var) in &self.funcs {
+                if self.plain.contains(var) {
+                    not_funcs.push(func.clone());
+                }
+            }
+            not_funcs.retain(|func| self.funcs.contains_key(func));
+            if not_funcs.is_empty() {
+                break;
+            }
+            for func in not_funcs {
+                self.funcs.remove(&func);
+            }
+        }
+        let mut source = String::new();
+        for list in &self.own_lists {
+            writeln!(source, "list {};", self.lists[list]).unwrap();
+        }
+        let returns: FxHashSet<&str> = self.funcs.values().copied().collect();
+        let undeclared: Vec<&str> = self
+            .own_vars
+            .iter()
+            .copied()
+            .filter(|var| {
+                !returns.contains(var)
+                    && !self.bound.contains_key(var)
+                    && !self.assigned.contains(var)
+            })
+            .collect();
+        if !undeclared.is_empty() {
+            if !source.is_empty() {
+                source.push('\n');
+            }
+            writeln!(
+                source,
+                "# Declares the variables which are not set anywhere else. It is never called."
+            )
+            .unwrap();
+            writeln!(source, "proc {} {{", self.declarations).unwrap();
+            for var in undeclared {
+                let value = literal(&self.target.variables[var].value);
+                let cloud = if self.cloud_vars.contains(var) {
+                    "cloud "
+                } else {
+                    ""
+                };
+                writeln!(source, "    {cloud}{} = {value};", self.vars[var]).unwrap();
+            }
+            writeln!(source, "}}").unwrap();
+        }
+        if !source.is_empty() && !self.out.is_empty() {
+            source.push('\n');
+        }
+        source.push_str(&self.out);
+        let globals = self.globals();
+        (source, globals, self.warnings)
+    }
+
+    fn scripts(&mut self) {
+        self.declared_locals.clear();
+        self.pending.clear();
+        self.bound.clear();
+        self.plain.clear();
+        self.assigned.clear();
+        self.warnings.clear();
+        self.out.clear();
+        for id in self.top_level() {
+            let block = match &self.blocks[id] {
+                Node::Block(block) => block,
+                // A variable or list reporter, alone on the workspace.
+                Node::Primitive(Primitive::List { .. }) => {
+                    self.unsupported(id, "data_listcontents");
+                    continue;
+                }
+                Node::Primitive(_) => {
+                    self.unsupported(id, "data_variable");
+                    continue;
+                }
+            };
+            let len = self.out.len();
+            if len > 0 {
+                self.out.push('\n');
+            }
+            if block.opcode == "procedures_definition" {
+                self.proc_definition(id, block);
+            } else if let Some(header) = self.hat(block) {
+                self.line(format!("{header} {{"));
+                self.stmts(block.next.as_deref());
+                self.line("}");
+            } else {
+                self.out.truncate(len);
+                self.unsupported(id, &block.opcode);
+            }
+        }
+    }
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(line.as_ref());
+        self.out.push('\n');
+    }
+
+    fn unsupported(&mut self, id: &str, opcode: &str) {
+        self.warnings.push(Warning::Unsupported {
+            target: self.name.to_owned(),
+            id: id.to_owned(),
+            opcode: opcode.to_owned(),
+        });
+    }
+
+    fn proc_definition(&mut self, id: &'a str, block: &'a sb3::Block) {
+        let Some((proccode, proc)) = self.procs.iter().find(|(_, proc)| proc.def_id == id) else {
+            self.unsupported(id, "procedures_definition");
+            return;
+        };
+        let args: Vec<&str> = proc.args.iter().map(|arg| arg.name.as_str()).collect();
+        let header = if self.funcs.contains_key(proccode) {
+            format!("func {}({}) {{", proc.name, args.join(", "))
+        } else {
+            let nowarp = if proc.warp { "" } else { "nowarp " };
+            let args = if args.is_empty() {
+                String::new()
+            } else {
+                format!(" {}", args.join(", "))
+            };
+            format!("{nowarp}proc {}{args} {{", proc.name)
+        };
+        self.proc = Some(proccode.clone());
+        self.line(header);
+        self.declared_locals.clear();
+        self.stmts(block.next.as_deref());
+        self.proc = None;
+        self.line("}");
+    }
+
+    fn hat(&mut self, block: &'a sb3::Block) -> Option<String> {
+        let field = |name: &str| {
+            string(
+                &block
+                    .fields
+                    .get(name)
+                    .map_or_else(String::new, |f| text(&f.value)),
+            )
+        };
+        Some(match block.opcode.as_str() {
+            "event_whenflagclicked" => "onflag".to_owned(),
+            "event_whenkeypressed" => format!("onkey {}", field("KEY_OPTION")),
+            "event_whenthisspriteclicked" | "event_whenstageclicked" => "onclick".to_owned(),
+            "event_whenbackdropswitchesto" => format!("onbackdrop {}", field("BACKDROP_OPTION")),
+            "event_whengreaterthan" => {
+                let event = match block.field("WHENGREATERTHANMENU")? {
+                    "LOUDNESS" => "onloudness",
+                    "TIMER" => "ontimer",
+                    _ => return None,
+                };
+                format!("{event} > {}", self.input(block, "VALUE", "0").text)
+            }
+            "control_start_as_clone" => "onclone".to_owned(),
+            _ => return None,
+        })
+    }
+
+    fn stmts(&mut self, mut next: Option<&'a str>) {
+        self.indent += 1;
+        while let Some(id) = next {
+            let Some(block) = self.block(id) else { break };
+            next = block.next.as_deref();
+            if let Some(after) = self.call_site(id, block) {
+                next = after;
+                continue;
+            }
+            if let Some(after) = self.return_(block) {
+                next = after;
+                self.settle();
+                continue;
+            }
+            self.stmt(id, block);
+            self.settle();
+        }
+        self.settle();
+        self.indent -= 1;
+    }
+
+    fn call_site(&mut self, id: &'a str, block: &'a sb3::Block) -> Option<Option<&'a str>> {
+        if block.opcode != "procedures_call" {
+            return None;
+        }
+        let proccode = block.mutation.as_ref()?.proccode.as_deref()?;
+        let &returned = self.funcs.get(proccode)?;
+        let set = block.next.as_deref().and_then(|id| self.block(id));
+        let var = set.and_then(|set| {
+            if set.opcode != "data_setvariableto" {
+                return None;
+            }
+            let value_var = match set.inputs.get("VALUE")?.value()? {
+                InputValue::Block(id) => self.block(id)?.field_id("VARIABLE"),
+                InputValue::Primitive(Primitive::Variable { id, .. }) => Some(id.as_str()),
+                InputValue::Primitive(_) => None,
+            };
+            (value_var == Some(returned)).then_some(())?;
+            set.field_id("VARIABLE")
+        });
+        let Some(var) = var else {
+            self.not_funcs.insert(proccode.to_owned());
+            return None;
+        };
+        self.pending.insert(var, id);
+        self.bound.insert(var, proccode.to_owned());
+        Some(set.unwrap().next.as_deref())
+    }
+
+    fn return_(&mut self, block: &'a sb3::Block) -> Option<Option<&'a str>> {
+        if block.opcode != "data_setvariableto" {
+            return None;
+        }
+        let proc = self.proc.clone()?;
+        let &returned = self.funcs.get(&proc)?;
+        if block.field_id("VARIABLE") != Some(returned) {
+            return None;
+        }
+        let value = self.input(block, "VALUE", "0").text;
+        self.line(format!("return {value};"));
+        match block.next.as_deref().and_then(|id| self.block(id)) {
+            Some(stop)
+                if stop.opcode == "control_stop"
+                    && stop.field("STOP_OPTION") == Some("this script") =>
+            {
+                Some(stop.next.as_deref())
+            }
+            None if self.indent == 1 => Some(None),
+            _ => {
+                self.not_funcs.insert(proc);
+                Some(block.next.as_deref())
+            }
+        }
+    }
+
+    fn settle(&mut self) {
+        for (var, _) in self.pending.drain() {
+            if let Some(func) = self.bound.get(var) {
+                self.not_funcs.insert(func.clone());
+            }
+        }
+    }
+
+    fn substack(&mut self, block: &'a sb3::Block, name: &str) {
+        self.stmts(block.input_block(name));
+    }
+
+    fn stmt(&mut self, id: &'a str, block: &'a sb3::Block) {
+        let opcode = block.opcode.as_str();
+        match opcode {
+            "control_if" | "control_if_else" => self.branch(block, "if"),
+            "control_repeat" => {
+                let times = self.input(block, "TIMES", "0").text;
+                self.compound(block, format!("repeat {times}"));
+            }
+            "control_repeat_until" => {
+                let cond = self.input(block, "CONDITION", "false").text;
+                self.compound(block, format!("until {cond}"));
+            }
+            "control_while" => {
+                let cond = self.input(block, "CONDITION", "false").at(2);
+                self.compound(block, format!("until not {cond}"));
+            }
+            "control_forever" => self.compound(block, "forever".to_owned()),
+            "data_setvariableto" => self.set_var(block),
+            "data_changevariableby" => {
+                let var = self.var_name(block);
+                let value = self.input(block, "VALUE", "0").text;
+                self.line(format!("{var} += {value};"));
+            }
+            "data_showvariable" => {
+                let var = self.var_name(block);
+                self.line(format!("show {var};"));
+            }
+            "data_hidevariable" => {
+                let var = self.var_name(block);
+                self.line(format!("hide {var};"));
+            }
+            "data_showlist" => {
+                let list = self.list_name(block);
+                self.line(format!("show {list};"));
+            }
+            "data_hidelist" => {
+                let list = self.list_name(block);
+                self.line(format!("hide {list};"));
+            }
+            "data_addtolist" => {
+                let item = self.input(block, "ITEM", "\"\"").text;
+                let list = self.list_name(block);
+                self.line(format!("add {item} to {list};"));
+            }
+            "data_deleteoflist" => {
+                let list = self.list_name(block);
+                let index = self.input(block, "INDEX", "1").text;
+                self.line(format!("delete {list}[{index}];"));
+            }
+            "data_deletealloflist" => {
+                let list = self.list_name(block);
+                self.line(format!("delete {list};"));
+            }
+            "data_insertatlist" => {
+                let item = self.input(block, "ITEM", "\"\"").text;
+                let list = self.list_name(block);
+                let index = self.input(block, "INDEX", "1").text;
+                self.line(format!("insert {item} at {list}[{index}];"));
+            }
+            "data_replaceitemoflist" => {
+                let list = self.list_name(block);
+                let index = self.input(block, "INDEX", "1").text;
+                let item = self.input(block, "ITEM", "\"\"").text;
+                self.line(format!("{list}[{index}] = {item};"));
+            }
+            "procedures_call" => self.proc_call(id, block),
+            _ => match self.shape(&self.shapes.blocks, block) {
+                Some(shape) => {
+                    let args = self.args(block, shape);
+                    if args.is_empty() {
+                        self.line(format!("{};", shape.name));
+                    } else {
+                        self.line(format!("{} {};", shape.name, args.join(", ")));
+                    }
+                }
+                None => {
+                    self.unsupported(id, opcode);
+                    self.line(format!(
+                        "# {opcode} ({id}) cannot be represented in goboscript"
+                    ));
+                }
+            },
+        }
+    }
+
+    fn compound(&mut self, block: &'a sb3::Block, header: String) {
+        self.settle();
+        self.line(format!("{header} {{"));
+        self.substack(block, "SUBSTACK");
+        self.line("}");
+    }
+
+    fn branch(&mut self, block: &'a sb3::Block, keyword: &str) {
+        let cond = self.input(block, "CONDITION", "false").text;
+        self.settle();
+        if keyword == "if" {
+            self.line(format!("if {cond} {{"));
+        } else {
+            self.line(format!("}} elif {cond} {{"));
+        }
+        self.substack(block, "SUBSTACK");
+        if block.opcode == "control_if_else" {
+            let else_id = block.input_block("SUBSTACK2");
+            match else_id.and_then(|id| self.block(id)) {
+                Some(elif)
+                    if matches!(elif.opcode.as_str(), "control_if" | "control_if_else")
+                        && elif.next.is_none() =>
+                {
+                    self.branch(elif, "elif")
+                }
+                _ => {
+                    self.line("} else {");
+                    self.stmts(else_id);
+                }
+            }
+        }
+        if keyword == "if" {
+            self.line("}");
+        }
+    }
+
+    fn set_var(&mut self, block: &'a sb3::Block) {
+        let Some(var) = block.field_id("VARIABLE") else {
+            return;
+        };
+        let value = self.input(block, "VALUE", "0").text;
+        let proc = self.proc.as_ref();
+        let def_id = proc.map(|proc| self.procs[proc].def_id);
+        let name = self.var_name(block);
+        if self
+            .locals
+            .get(var)
+            .is_some_and(|&owner| Some(owner) == def_id)
+            && self.declared_locals.insert(var)
+        {
+            self.line(format!("local {name} = {value};"));
+        } else if self.cloud_vars.contains(var) {
+            // Every assignment declares the variable again, so each must say that it is
+            // a cloud variable.
+            self.assigned.insert(var);
+            self.line(format!("cloud {name} = {value};"));
+        } else {
+            self.assigned.insert(var);
+            self.line(format!("{name} = {value};"));
+        }
+    }
+
+    fn var_name(&mut self, block: &'a sb3::Block) -> String {
+        self.var(
+            block.field_id("VARIABLE").unwrap_or_default(),
+            block.field("VARIABLE").unwrap_or_default(),
+        )
+    }
+
+    fn var(&mut self, id: &'a str, name: &str) -> String {
+        self.plain.insert(id);
+        self.vars.get(id).cloned().unwrap_or_else(|| sanitize(name))
+    }
+
+    fn list_name(&self, block: &'a sb3::Block) -> String {
+        self.list(
+            block.field_id("LIST").unwrap_or_default(),
+            block.field("LIST").unwrap_or_default(),
+        )
+    }
+
+    fn list(&self, id: &str, name: &str) -> String {
+        self.lists
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| sanitize(name))
+    }
+
+    fn proc_call(&mut self, id: &'a str, block: &'a sb3::Block) {
+        let proccode = block
+            .mutation
+            .as_ref()
+            .and_then(|mutation| mutation.proccode.as_deref())
+            .unwrap_or_default();
+        if self.funcs.contains_key(proccode) {
+            self.not_funcs.insert(proccode.to_owned());
+        }
+        match self.call(block) {
+            Some((name, args)) if args.is_empty() => self.line(format!("{name};")),
+            Some((name, args)) => self.line(format!("{name} {};", args.join(", "))),
+            None => self.unsupported(id, "procedures_call"),
+        }
+    }
+
+    fn call(&mut self, block: &'a sb3::Block) -> Option<(String, Vec<String>)> {
+        let proccode = block.mutation.as_ref()?.proccode.as_deref()?;
+        let proc = self.procs.get(proccode)?;
+        let name = proc.name.clone();
+        let args: Vec<(String, &'static str)> = proc
+            .args
+            .iter()
+            .map(|arg| (arg.id.clone(), if arg.is_bool { "false" } else { "\"\"" }))
+            .collect();
+        let args = args
+            .iter()
+            .map(|(id, empty)| self.input(block, id, empty).text)
+            .collect();
+        Some((name, args))
+    }
+
+    fn shape<'s>(
+        &self,
+        shapes: &'s FxHashMap<&str, Vec<Shape>>,
+        block: &sb3::Block,
+    ) -> Option<&'s Shape> {
+        shapes.get(block.opcode.as_str())?.iter().find(|shape| {
+            let fields_match = shape.fields.as_ref().is_none_or(|fields| {
+                fields
+                    .iter()
+                    .all(|(name, field)| field_value(block, name) == Some(&field.value))
+            });
+            let menu_matches = shape.menu.as_ref().is_none_or(|menu| {
+                shape.args.contains(&menu.input)
+                    || self.menu_literal(block, menu.input) == Some(menu.default)
+            });
+            fields_match && menu_matches
+        })
+    }
+
+    fn menu_literal(&self, block: &sb3::Block, input: &str) -> Option<&'a str> {
+        let menu = self.block(block.input_block(input)?)?;
+        if !menu.shadow {
+            return None;
+        }
+        menu.field(input)
+    }
+
+    fn args(&mut self, block: &'a sb3::Block, shape: &Shape) -> Vec<String> {
+        shape
+            .args
+            .iter()
+            .map(|arg| self.input(block, arg, "\"\"").text)
+            .collect()
+    }
+
+    fn input(&mut self, block: &'a sb3::Block, name: &str, empty: &str) -> Code {
+        match block.inputs.get(name).and_then(Input::value) {
+            Some(InputValue::Block(id)) => self.reporter(id),
+            Some(InputValue::Primitive(primitive)) => self.primitive(primitive),
+            None => Code::term(empty),
+        }
+    }
+
+    fn primitive(&mut self, primitive: &'a Primitive) -> Code {
+        match primitive {
+            Primitive::Number(value)
+            | Primitive::PositiveNumber(value)
+            | Primitive::PositiveInteger(value)
+            | Primitive::Integer(value)
+            | Primitive::Angle(value) => Code::term(number(value)),
+            Primitive::Color(value) | Primitive::Text(value) => Code::term(string(&text(value))),
+            Primitive::Broadcast { name, .. } => Code::term(string(name)),
+            Primitive::Variable { name, id, .. } => self.var_reporter(id, name),
+            Primitive::List { name, id, .. } => Code {
+                is_list: true,
+                ..Code::term(self.list(id, name))
+            },
+        }
+    }
+
+    fn var_reporter(&mut self, id: &'a str, name: &str) -> Code {
+        if let Some(call_id) = self.pending.remove(id) {
+            if let Some((name, args)) = self.block(call_id).and_then(|call| self.call(call)) {
+                return Code::term(format!("{name}({})", args.join(", ")));
+            }
+        }
+        Code::term(self.var(id, name))
+    }
+
+    fn reporter(&mut self, id: &'a str) -> Code {
+        let block = match self.blocks.get(id) {
+            Some(Node::Block(block)) => block,
+            Some(Node::Primitive(primitive)) => return self.primitive(primitive),
+            None => return Code::term("\"\""),
+        };
+        let opcode = block.opcode.as_str();
+        if block.shadow && block.fields.len() == 1 {
+            let value = &block.fields[0].value;
+            return Code::term(if opcode.starts_with("math_") {
+                number(value)
+            } else {
+                string(&text(value))
+            });
+        }
+        match opcode {
+            "data_variable" => {
+                return self.var_reporter(
+                    block.field_id("VARIABLE").unwrap_or_default(),
+                    block.field("VARIABLE").unwrap_or_default(),
+                );
+            }
+            "data_listcontents" => {
+                return Code {
+                    is_list: true,
+                    ..Code::term(self.list_name(block))
+                };
+            }
+            "argument_reporter_string_number" | "argument_reporter_boolean" => {
+                let name = block.field("VALUE").unwrap_or_default();
+                let arg = self.proc.as_ref().and_then(|proc| {
+                    self.procs[proc]
+                        .args
+                        .iter()
+                        .find(|arg| arg.original == name)
+                });
+                return match arg {
+                    Some(arg) => Code::term(format!("${}", arg.name)),
+                    // Outside of its definition, an argument reports its default.
+                    None if opcode == "argument_reporter_boolean" => Code::term("false"),
+                    None => Code::term("0"),
+                };
+            }
+            "data_itemoflist" => {
+                let list = self.list_name(block);
+                let index = self.input(block, "INDEX", "1").text;
+                return Code::term(format!("{list}[{index}]"));
+            }
+            "data_lengthoflist" => {
+                let list = self.list_name(block);
+                return Code::op(format!("length {list}"), 2);
+            }
+            _ => {}
+        }
+        if let Some(ops) = self.shapes.unops.get(opcode) {
+            let op = ops.iter().find(|op| {
+                op.fields().is_none_or(|fields| {
+                    let fields: IndexMap<String, Field> = serde_json::from_str(fields).unwrap();
+                    field_value(block, "OPERATOR") == fields.get("OPERATOR").map(|f| &f.value)
+                })
+            });
+            if let Some(&op) = op {
+                let empty = if let UnOp::Not = op { "false" } else { "\"\"" };
+                let opr = self.input(block, op.input(), empty);
+                let opr = if let UnOp::Length = op {
+                    opr.into_string(2)
+                } else {
+                    opr.at(2)
+                };
+                return Code::op(format!("{} {opr}", unop_keyword(op)), 2);
+            }
+        }
+        if let Some(&op) = self.shapes.binops.get(opcode) {
+            let empty = if matches!(op, BinOp::And | BinOp::Or) {
+                "false"
+            } else {
+                "\"\""
+            };
+            let lhs = self.input(block, op.lhs(), empty);
+            let rhs = self.input(block, op.rhs(), empty);
+            if let BinOp::Of = op {
+                return Code::term(format!("{}[{}]", lhs.into_string(1), rhs.text));
+            }
+            let (symbol, prec, is_right) = binop_syntax(op);
+            let (lhs, rhs) = if is_right {
+                (lhs.at(prec - 1), rhs.at(prec))
+            } else {
+                (lhs.at(prec), rhs.at(prec - 1))
+            };
+            return Code::op(format!("{lhs} {symbol} {rhs}"), prec);
+        }
+        if let Some(shape) = self.shape(&self.shapes.reprs, block) {
+            let args = self.args(block, shape);
+            return Code::term(format!("{}({})", shape.name, args.join(", ")));
+        }
+        self.unsupported(id, opcode);
+        Code::term("\"\"")
+    }
+}
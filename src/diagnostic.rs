@@ -1,12 +1,18 @@
 mod diagnostic_kind;
+mod diagnostic_record;
+mod explanation;
 mod project_diagnostics;
 mod sprite_diagnostics;
+mod suggestion;
 
 pub use diagnostic_kind::*;
+pub use diagnostic_record::*;
+pub use explanation::*;
 use lalrpop_util::ParseError;
 use logos::Span;
 pub use project_diagnostics::*;
 pub use sprite_diagnostics::*;
+pub use suggestion::*;
 
 use crate::lexer::token::Token;
 
@@ -16,17 +16,38 @@ pub enum DiagnosticKind {
     UnrecognizedToken(Token, Vec<String>),
     ExtraToken(Token),
     FileNotFound(SmolStr),
-    UnrecognizedReporter(SmolStr),
-    UnrecognizedBlock(SmolStr),
-    UnrecognizedVariable(SmolStr),
+    UnrecognizedReporter {
+        name: SmolStr,
+        suggestion: Option<SmolStr>,
+    },
+    UnrecognizedBlock {
+        name: SmolStr,
+        suggestion: Option<SmolStr>,
+    },
+    UnrecognizedVariable {
+        name: SmolStr,
+        suggestion: Option<SmolStr>,
+    },
     UnrecognizedList(SmolStr),
     UnrecognizedEnum(SmolStr),
     UnrecognizedStruct(SmolStr),
-    UnrecognizedProcedure(SmolStr),
-    UnrecognizedFunction(SmolStr),
+    UnrecognizedProcedure {
+        name: SmolStr,
+        suggestion: Option<SmolStr>,
+    },
+    UnrecognizedFunction {
+        name: SmolStr,
+        suggestion: Option<SmolStr>,
+    },
     UnrecognizedArgument(SmolStr),
-    UnrecognizedStructField(SmolStr),
-    UnrecognizedEnumVariant(SmolStr),
+    UnrecognizedStructField {
+        name: SmolStr,
+        suggestion: Option<SmolStr>,
+    },
+    UnrecognizedEnumVariant {
+        name: SmolStr,
+        suggestion: Option<SmolStr>,
+    },
     UnrecognizedKey(SmolStr),
     NoCostumes,
     BlockArgsCountMismatch {
@@ -41,9 +62,16 @@ pub enum DiagnosticKind {
         proc: SmolStr,
         given: usize,
     },
+    StructFieldsCountMismatch {
+        struct_: SmolStr,
+        expected: usize,
+        given: usize,
+    },
     CommandFailed {
+        reason: String,
         stderr: Vec<u8>,
     },
+    CommandNotAllowed,
     TypeMismatch {
         expected: Type,
         given: Type,
@@ -52,6 +80,16 @@ pub enum DiagnosticKind {
     StructDoesNotHaveField {
         type_name: SmolStr,
         field_name: SmolStr,
+        suggestion: Option<SmolStr>,
+    },
+    InvalidListData(String),
+    InvalidEmbed(String),
+    InvalidDirective(String),
+    InvalidMacroCall(String),
+    InvalidImport(String),
+    PrivateName {
+        module: SmolStr,
+        name: SmolStr,
     },
     // Warnings
     FollowedByUnreachableCode,
@@ -67,7 +105,7 @@ pub enum DiagnosticKind {
 
 impl DiagnosticKind {
     pub fn to_string(&self, project: &Project, sprite_diagnostics: &SpriteDiagnostics) -> String {
-        match self {
+        let message = match self {
             DiagnosticKind::InvalidToken => "invalid token".to_string(),
             DiagnosticKind::UnrecognizedEof(vec) => {
                 format!("unrecognized eof, expected one of {:?}", vec)
@@ -77,19 +115,29 @@ impl DiagnosticKind {
             }
             DiagnosticKind::ExtraToken(token) => format!("extra token {:?}", token),
             DiagnosticKind::FileNotFound(smol_str) => format!("file not found: {:?}", smol_str),
-            DiagnosticKind::UnrecognizedReporter(name) => format!("unrecognized reporter `{name}`"),
-            DiagnosticKind::UnrecognizedBlock(name) => format!("unrecognized block `{name}`"),
-            DiagnosticKind::UnrecognizedVariable(name) => format!("unrecognized variable `{name}`"),
+            DiagnosticKind::UnrecognizedReporter { name, .. } => {
+                format!("unrecognized reporter `{name}`")
+            }
+            DiagnosticKind::UnrecognizedBlock { name, .. } => {
+                format!("unrecognized block `{name}`")
+            }
+            DiagnosticKind::UnrecognizedVariable { name, .. } => {
+                format!("unrecognized variable `{name}`")
+            }
             DiagnosticKind::UnrecognizedList(name) => format!("unrecognized list {name}"),
             DiagnosticKind::UnrecognizedEnum(name) => format!("unrecognized enum {name}"),
             DiagnosticKind::UnrecognizedStruct(name) => format!("unrecognized struct {name}"),
-            DiagnosticKind::UnrecognizedProcedure(name) => format!("unrecognized procedure {name}"),
-            DiagnosticKind::UnrecognizedFunction(name) => format!("unrecognized function {name}"),
+            DiagnosticKind::UnrecognizedProcedure { name, .. } => {
+                format!("unrecognized procedure {name}")
+            }
+            DiagnosticKind::UnrecognizedFunction { name, .. } => {
+                format!("unrecognized function {name}")
+            }
             DiagnosticKind::UnrecognizedArgument(name) => format!("unrecognized argument {name}"),
-            DiagnosticKind::UnrecognizedStructField(name) => {
+            DiagnosticKind::UnrecognizedStructField { name, .. } => {
                 format!("unrecognized struct field {name}")
             }
-            DiagnosticKind::UnrecognizedEnumVariant(name) => {
+            DiagnosticKind::UnrecognizedEnumVariant { name, .. } => {
                 format!("unrecognized enum variant {name}")
             }
             DiagnosticKind::UnrecognizedKey(name) => format!("unrecognized key {name}"),
@@ -116,7 +164,17 @@ impl DiagnosticKind {
                     proc, given
                 )
             }
-            DiagnosticKind::CommandFailed { .. } => "command failed".to_string(),
+            DiagnosticKind::StructFieldsCountMismatch {
+                struct_,
+                expected,
+                given,
+            } => {
+                format!("struct {struct_} has {expected} fields, but {given} were given")
+            }
+            DiagnosticKind::CommandFailed { reason, .. } => format!("command failed: {reason}"),
+            DiagnosticKind::CommandNotAllowed => {
+                "command not run, as running commands is turned off".to_string()
+            }
             DiagnosticKind::TypeMismatch { expected, given } => {
                 format!("type mismatch: expected {}, but got {}", expected, given)
             }
@@ -133,11 +191,141 @@ impl DiagnosticKind {
             DiagnosticKind::StructDoesNotHaveField {
                 type_name,
                 field_name,
+                ..
             } => {
                 format!("struct {type_name} does not have field {field_name}")
             }
+            DiagnosticKind::InvalidListData(reason) => format!("invalid list data: {reason}"),
+            DiagnosticKind::InvalidEmbed(reason) => format!("invalid %embed: {reason}"),
+            DiagnosticKind::InvalidDirective(reason) => format!("invalid directive: {reason}"),
+            DiagnosticKind::InvalidMacroCall(reason) => format!("invalid macro call: {reason}"),
+            DiagnosticKind::InvalidImport(reason) => format!("invalid import: {reason}"),
+            DiagnosticKind::PrivateName { module, name } => {
+                format!("{name} is private to module {module}")
+            }
+        };
+        sprite_diagnostics.preproc.source_names(&message)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiagnosticKind::InvalidToken => "InvalidToken",
+            DiagnosticKind::UnrecognizedEof(..) => "UnrecognizedEof",
+            DiagnosticKind::UnrecognizedToken(..) => "UnrecognizedToken",
+            DiagnosticKind::ExtraToken(..) => "ExtraToken",
+            DiagnosticKind::FileNotFound(..) => "FileNotFound",
+            DiagnosticKind::UnrecognizedReporter { .. } => "UnrecognizedReporter",
+            DiagnosticKind::UnrecognizedBlock { .. } => "UnrecognizedBlock",
+            DiagnosticKind::UnrecognizedVariable { .. } => "UnrecognizedVariable",
+            DiagnosticKind::UnrecognizedList(..) => "UnrecognizedList",
+            DiagnosticKind::UnrecognizedEnum(..) => "UnrecognizedEnum",
+            DiagnosticKind::UnrecognizedStruct(..) => "UnrecognizedStruct",
+            DiagnosticKind::UnrecognizedProcedure { .. } => "UnrecognizedProcedure",
+            DiagnosticKind::UnrecognizedFunction { .. } => "UnrecognizedFunction",
+            DiagnosticKind::UnrecognizedArgument(..) => "UnrecognizedArgument",
+            DiagnosticKind::UnrecognizedStructField { .. } => "UnrecognizedStructField",
+            DiagnosticKind::UnrecognizedEnumVariant { .. } => "UnrecognizedEnumVariant",
+            DiagnosticKind::UnrecognizedKey(..) => "UnrecognizedKey",
+            DiagnosticKind::NoCostumes => "NoCostumes",
+            DiagnosticKind::BlockArgsCountMismatch { .. } => "BlockArgsCountMismatch",
+            DiagnosticKind::ReprArgsCountMismatch { .. } => "ReprArgsCountMismatch",
+            DiagnosticKind::ProcArgsCountMismatch { .. } => "ProcArgsCountMismatch",
+            DiagnosticKind::CommandFailed { .. } => "CommandFailed",
+            DiagnosticKind::CommandNotAllowed => "CommandNotAllowed",
+            DiagnosticKind::TypeMismatch { .. } => "TypeMismatch",
+            DiagnosticKind::NotStruct => "NotStruct",
+            DiagnosticKind::StructDoesNotHaveField { .. } => "StructDoesNotHaveField",
+            DiagnosticKind::InvalidListData(..) => "InvalidListData",
+            DiagnosticKind::InvalidEmbed(..) => "InvalidEmbed",
+            DiagnosticKind::InvalidDirective(..) => "InvalidDirective",
+            DiagnosticKind::InvalidMacroCall(..) => "InvalidMacroCall",
+            DiagnosticKind::InvalidImport(..) => "InvalidImport",
+            DiagnosticKind::PrivateName { .. } => "PrivateName",
+            DiagnosticKind::StructFieldsCountMismatch { .. } => "StructFieldsCountMismatch",
+            DiagnosticKind::FollowedByUnreachableCode => "FollowedByUnreachableCode",
+            DiagnosticKind::UnusedVariable(..) => "UnusedVariable",
+            DiagnosticKind::UnusedList(..) => "UnusedList",
+            DiagnosticKind::UnusedEnum(..) => "UnusedEnum",
+            DiagnosticKind::UnusedStruct(..) => "UnusedStruct",
+            DiagnosticKind::UnusedProcedure(..) => "UnusedProcedure",
+            DiagnosticKind::UnusedArgument(..) => "UnusedArgument",
+            DiagnosticKind::UnusedStructField(..) => "UnusedStructField",
+            DiagnosticKind::UnusedEnumVariant(..) => "UnusedEnumVariant",
         }
     }
+
+    /// Codes are never reused or renumbered, new kinds get the next free code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DiagnosticKind::InvalidToken => "E0001",
+            DiagnosticKind::UnrecognizedEof(..) => "E0002",
+            DiagnosticKind::UnrecognizedToken(..) => "E0003",
+            DiagnosticKind::ExtraToken(..) => "E0004",
+            DiagnosticKind::FileNotFound(..) => "E0005",
+            DiagnosticKind::UnrecognizedReporter { .. } => "E0006",
+            DiagnosticKind::UnrecognizedBlock { .. } => "E0007",
+            DiagnosticKind::UnrecognizedVariable { .. } => "E0008",
+            DiagnosticKind::UnrecognizedList(..) => "E0009",
+            DiagnosticKind::UnrecognizedEnum(..) => "E0010",
+            DiagnosticKind::UnrecognizedStruct(..) => "E0011",
+            DiagnosticKind::UnrecognizedProcedure { .. } => "E0012",
+            DiagnosticKind::UnrecognizedFunction { .. } => "E0013",
+            DiagnosticKind::UnrecognizedArgument(..) => "E0014",
+            DiagnosticKind::UnrecognizedStructField { .. } => "E0015",
+            DiagnosticKind::UnrecognizedEnumVariant { .. } => "E0016",
+            DiagnosticKind::UnrecognizedKey(..) => "E0017",
+            DiagnosticKind::NoCostumes => "E0018",
+            DiagnosticKind::BlockArgsCountMismatch { .. } => "E0019",
+            DiagnosticKind::ReprArgsCountMismatch { .. } => "E0020",
+            DiagnosticKind::ProcArgsCountMismatch { .. } => "E0021",
+            DiagnosticKind::CommandFailed { .. } => "E0022",
+            DiagnosticKind::TypeMismatch { .. } => "E0023",
+            DiagnosticKind::NotStruct => "E0024",
+            DiagnosticKind::StructDoesNotHaveField { .. } => "E0025",
+            DiagnosticKind::InvalidListData(..) => "E0027",
+            DiagnosticKind::CommandNotAllowed => "E0028",
+            DiagnosticKind::InvalidEmbed(..) => "E0029",
+            DiagnosticKind::InvalidDirective(..) => "E0030",
+            DiagnosticKind::InvalidMacroCall(..) => "E0031",
+            DiagnosticKind::InvalidImport(..) => "E0032",
+            DiagnosticKind::PrivateName { .. } => "E0033",
+            DiagnosticKind::StructFieldsCountMismatch { .. } => "E0034",
+            DiagnosticKind::FollowedByUnreachableCode => "W0001",
+            DiagnosticKind::UnusedVariable(..) => "W0002",
+            DiagnosticKind::UnusedList(..) => "W0003",
+            DiagnosticKind::UnusedEnum(..) => "W0004",
+            DiagnosticKind::UnusedStruct(..) => "W0005",
+            DiagnosticKind::UnusedProcedure(..) => "W0006",
+            DiagnosticKind::UnusedArgument(..) => "W0007",
+            DiagnosticKind::UnusedStructField(..) => "W0008",
+            DiagnosticKind::UnusedEnumVariant(..) => "W0009",
+        }
+    }
+
+    pub fn help(&self, sprite_diagnostics: &SpriteDiagnostics) -> Option<String> {
+        let help = match self {
+            | DiagnosticKind::UnrecognizedReporter { suggestion, .. }
+            | DiagnosticKind::UnrecognizedBlock { suggestion, .. }
+            | DiagnosticKind::UnrecognizedVariable { suggestion, .. }
+            | DiagnosticKind::UnrecognizedProcedure { suggestion, .. }
+            | DiagnosticKind::UnrecognizedFunction { suggestion, .. }
+            | DiagnosticKind::UnrecognizedStructField { suggestion, .. }
+            | DiagnosticKind::UnrecognizedEnumVariant { suggestion, .. }
+            | DiagnosticKind::StructDoesNotHaveField { suggestion, .. } => suggestion
+                .as_ref()
+                .map(|suggestion| format!("did you mean `{suggestion}`?")),
+            DiagnosticKind::CommandNotAllowed => Some(
+                "commands are turned off by `--no-exec`, or by `allow_exec = false` in \
+                 goboscript.toml"
+                    .to_string(),
+            ),
+            DiagnosticKind::PrivateName { .. } => {
+                Some("declare it with `pub` in the module to use it here".to_string())
+            }
+            _ => None,
+        };
+        help.map(|help| sprite_diagnostics.preproc.source_names(&help))
+    }
 }
 
 impl From<&DiagnosticKind> for Level {
@@ -148,26 +336,34 @@ impl From<&DiagnosticKind> for Level {
             | DiagnosticKind::UnrecognizedToken(_, _)
             | DiagnosticKind::ExtraToken(_)
             | DiagnosticKind::FileNotFound(_)
-            | DiagnosticKind::UnrecognizedReporter(_)
-            | DiagnosticKind::UnrecognizedBlock(_)
-            | DiagnosticKind::UnrecognizedVariable(_)
+            | DiagnosticKind::UnrecognizedReporter { .. }
+            | DiagnosticKind::UnrecognizedBlock { .. }
+            | DiagnosticKind::UnrecognizedVariable { .. }
             | DiagnosticKind::UnrecognizedList(_)
             | DiagnosticKind::UnrecognizedEnum(_)
             | DiagnosticKind::UnrecognizedStruct(_)
-            | DiagnosticKind::UnrecognizedProcedure(_)
-            | DiagnosticKind::UnrecognizedFunction(_)
+            | DiagnosticKind::UnrecognizedProcedure { .. }
+            | DiagnosticKind::UnrecognizedFunction { .. }
             | DiagnosticKind::UnrecognizedArgument(_)
-            | DiagnosticKind::UnrecognizedStructField(_)
-            | DiagnosticKind::UnrecognizedEnumVariant(_)
+            | DiagnosticKind::UnrecognizedStructField { .. }
+            | DiagnosticKind::UnrecognizedEnumVariant { .. }
             | DiagnosticKind::UnrecognizedKey(_)
             | DiagnosticKind::NoCostumes
             | DiagnosticKind::BlockArgsCountMismatch { .. }
             | DiagnosticKind::ReprArgsCountMismatch { .. }
             | DiagnosticKind::ProcArgsCountMismatch { .. }
             | DiagnosticKind::CommandFailed { .. }
+            | DiagnosticKind::CommandNotAllowed
             | DiagnosticKind::TypeMismatch { .. }
             | DiagnosticKind::NotStruct
-            | DiagnosticKind::StructDoesNotHaveField { .. } => Level::Error,
+            | DiagnosticKind::StructDoesNotHaveField { .. }
+            | DiagnosticKind::InvalidListData(_)
+            | DiagnosticKind::InvalidEmbed(_)
+            | DiagnosticKind::InvalidDirective(_)
+            | DiagnosticKind::InvalidMacroCall(_)
+            | DiagnosticKind::InvalidImport(_)
+            | DiagnosticKind::PrivateName { .. }
+            | DiagnosticKind::StructFieldsCountMismatch { .. } => Level::Error,
 
             | DiagnosticKind::FollowedByUnreachableCode
             | DiagnosticKind::UnusedVariable(_)
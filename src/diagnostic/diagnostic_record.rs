@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use annotate_snippets::Level;
+use serde::Serialize;
+use serde_json::json;
+
+use super::{Diagnostic, SpriteDiagnostics};
+use crate::ast::Project;
+
+#[derive(Debug, Serialize)]
+pub struct DiagnosticRecord {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub level: &'static str,
+    pub message: String,
+    pub help: Option<String>,
+    pub path: PathBuf,
+    pub start: Position,
+    pub end: Position,
+    pub expansions: Vec<ExpansionRecord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExpansionRecord {
+    #[serde(rename = "macro")]
+    pub name: String,
+    pub path: Option<PathBuf>,
+    pub start: Option<Position>,
+    pub end: Option<Position>,
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    fn new(src: &str, offset: usize) -> Self {
+        let before = &src[..offset.min(src.len())];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl SpriteDiagnostics {
+    pub fn record(&self, project: &Project, diagnostic: &Diagnostic) -> DiagnosticRecord {
+        let src = self.preproc.get_translation_unit();
+        let span = self.preproc.call_site(&diagnostic.span);
+        let (start, include) = self.preproc.translate_position(span.start);
+        let (end, _) = self
+            .preproc
+            .translate_position(span.end.saturating_sub(1).max(span.start));
+        let end = (end + 1).max(start);
+        let file = &src[include.range.clone()];
+        let expansions = self
+            .preproc
+            .expansions_of(&diagnostic.span)
+            .into_iter()
+            .map(|(expansion, definition)| {
+                let mut record = ExpansionRecord {
+                    name: expansion.name.clone(),
+                    path: None,
+                    start: None,
+                    end: None,
+                };
+                if let Some(definition) = definition.filter(|definition| !definition.is_empty()) {
+                    let (start, include) = self.preproc.translate_position(definition.start);
+                    let file = &src[include.range.clone()];
+                    record.path = Some(include.path.clone());
+                    record.start = Some(Position::new(file, start));
+                    record.end = Some(Position::new(file, start + definition.len()));
+                }
+                record
+            })
+            .collect();
+        let level = match Level::from(&diagnostic.kind) {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            _ => "note",
+        };
+        DiagnosticRecord {
+            code: diagnostic.kind.code(),
+            name: diagnostic.kind.name(),
+            level,
+            message: diagnostic.kind.to_string(project, self),
+            help: diagnostic.kind.help(self),
+            path: include.path.clone(),
+            start: Position::new(file, start),
+            end: Position::new(file, end),
+            expansions,
+        }
+    }
+
+    pub fn records<'a>(
+        &'a self,
+        project: &'a Project,
+    ) -> impl Iterator<Item = DiagnosticRecord> + 'a {
+        self.diagnostics
+            .iter()
+            .map(|diagnostic| self.record(project, diagnostic))
+    }
+}
+
+pub fn sarif(records: &[DiagnosticRecord]) -> serde_json::Value {
+    let results: Vec<serde_json::Value> = records
+        .iter()
+        .map(|record| {
+            let mut message = record.message.clone();
+            if let Some(help) = &record.help {
+                message.push_str("\nhelp: ");
+                message.push_str(help);
+            }
+            let related_locations: Vec<serde_json::Value> = record
+                .expansions
+                .iter()
+                .filter_map(|expansion| {
+                    let (path, start, end) =
+                        (expansion.path.as_ref()?, expansion.start?, expansion.end?);
+                    Some(json!({
+                        "message": {
+                            "text": format!("in the definition of macro `{}`", expansion.name)
+                        },
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": path.to_string_lossy() },
+                            "region": {
+                                "startLine": start.line,
+                                "startColumn": start.column,
+                                "endLine": end.line,
+                                "endColumn": end.column,
+                            }
+                        }
+                    }))
+                })
+                .collect();
+            json!({
+                "ruleId": record.code,
+                "properties": { "name": record.name },
+                "level": record.level,
+                "message": { "text": message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": record.path.to_string_lossy() },
+                        "region": {
+                            "startLine": record.start.line,
+                            "startColumn": record.start.column,
+                            "endLine": record.end.line,
+                            "endColumn": record.end.column,
+                        }
+                    }
+                }],
+                "relatedLocations": related_locations,
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "goboscript",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/aspizu/goboscript",
+                }
+            },
+            "results": results,
+        }]
+    })
+}
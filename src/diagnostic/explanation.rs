@@ -0,0 +1,20 @@
+macro_rules! explanations {
+    ($($code:literal),* $(,)?) => {
+        pub const CODES: &[&str] = &[$($code),*];
+
+        pub fn explanation(code: &str) -> Option<&'static str> {
+            match code.to_ascii_uppercase().as_str() {
+                $($code => Some(include_str!(concat!("explanations/", $code, ".md"))),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+explanations!(
+    "E0001", "E0002", "E0003", "E0004", "E0005", "E0006", "E0007", "E0008", "E0009", "E0010",
+    "E0011", "E0012", "E0013", "E0014", "E0015", "E0016", "E0017", "E0018", "E0019", "E0020",
+    "E0021", "E0022", "E0023", "E0024", "E0025", "E0027", "E0028", "E0029", "E0030", "E0031",
+    "E0032", "E0033", "E0034", "W0001", "W0002", "W0003", "W0004", "W0005", "W0006", "W0007",
+    "W0008", "W0009",
+);
@@ -2,7 +2,7 @@ use annotate_snippets::Renderer;
 use fxhash::FxHashMap;
 use smol_str::SmolStr;
 
-use super::SpriteDiagnostics;
+use super::{sarif, DiagnosticRecord, SpriteDiagnostics};
 use crate::ast::Project;
 
 pub struct ProjectDiagnostics {
@@ -12,11 +12,63 @@ pub struct ProjectDiagnostics {
 }
 
 impl ProjectDiagnostics {
+    pub fn is_empty(&self) -> bool {
+        self.stage_diagnostics.diagnostics.is_empty()
+            && self
+                .sprites_diagnostics
+                .values()
+                .all(|sprite_diagnostics| sprite_diagnostics.diagnostics.is_empty())
+    }
+
+    fn sprites(&self) -> impl Iterator<Item = &SpriteDiagnostics> {
+        let mut sprites: Vec<_> = self.sprites_diagnostics.iter().collect();
+        sprites.sort_by_key(|(name, _)| *name);
+        sprites
+            .into_iter()
+            .map(|(_, sprite_diagnostics)| sprite_diagnostics)
+    }
+
     pub fn eprint(&self) {
         let renderer = Renderer::styled();
         self.stage_diagnostics.eprint(&renderer, &self.project);
-        for sprite_diagnostics in self.sprites_diagnostics.values() {
+        for sprite_diagnostics in self.sprites() {
             sprite_diagnostics.eprint(&renderer, &self.project);
         }
     }
+
+    pub fn records(&self) -> Vec<DiagnosticRecord> {
+        let mut records: Vec<DiagnosticRecord> =
+            self.stage_diagnostics.records(&self.project).collect();
+        for sprite_diagnostics in self.sprites() {
+            records.extend(sprite_diagnostics.records(&self.project));
+        }
+        records
+    }
+
+    pub fn eprint_short(&self) {
+        for record in self.records() {
+            eprintln!(
+                "{}:{}:{}: {}[{}]: {}",
+                record.path.display(),
+                record.start.line,
+                record.start.column,
+                record.level,
+                record.code,
+                record.message
+            );
+        }
+    }
+
+    pub fn print_json(&self) {
+        for record in self.records() {
+            println!("{}", serde_json::to_string(&record).unwrap());
+        }
+    }
+
+    pub fn print_sarif(&self) {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&sarif(&self.records())).unwrap()
+        );
+    }
 }
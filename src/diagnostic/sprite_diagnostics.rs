@@ -1,11 +1,15 @@
-use std::{io, path::PathBuf};
+use std::{io, path::PathBuf, rc::Rc};
 
 use annotate_snippets::{Level, Renderer, Snippet};
 use colored::Colorize;
+use fxhash::FxHashMap;
 use logos::Span;
 
 use super::{diagnostic_kind::DiagnosticKind, Diagnostic};
-use crate::{ast::Project, preproc::PreProc};
+use crate::{
+    ast::Project,
+    preproc::{Overlay, PreProc},
+};
 
 pub struct SpriteDiagnostics {
     pub path: PathBuf,
@@ -14,14 +18,24 @@ pub struct SpriteDiagnostics {
 }
 
 impl SpriteDiagnostics {
-    pub fn new(path: PathBuf) -> io::Result<Self> {
-        let mut preproc = PreProc::new(path.parent().unwrap().to_path_buf());
+    pub fn new(
+        path: PathBuf,
+        overlay: Rc<Overlay>,
+        defines: &FxHashMap<String, String>,
+        include_paths: &[PathBuf],
+    ) -> io::Result<Self> {
+        let mut preproc = PreProc::new(
+            path.parent().unwrap().to_path_buf(),
+            overlay,
+            defines.clone(),
+            include_paths.to_vec(),
+        );
         preproc.include(path.clone())?;
         preproc.process()?;
         Ok(Self {
             path,
+            diagnostics: std::mem::take(&mut preproc.diagnostics),
             preproc,
-            diagnostics: Vec::new(),
         })
     }
 
@@ -37,22 +51,81 @@ impl SpriteDiagnostics {
         for diagnostic in &self.diagnostics {
             let level: Level = (&diagnostic.kind).into();
             let title = diagnostic.kind.to_string(project, self);
-            let (start, include) = self.preproc.translate_position(diagnostic.span.start);
-            let (end, _) = self.preproc.translate_position(diagnostic.span.end - 1);
+            let span = self.preproc.call_site(&diagnostic.span);
+            let (start, include) = self.preproc.translate_position(span.start);
+            let (end, _) = self.preproc.translate_position(span.end - 1);
             let end = end + 1;
-            let message = level.title(&title).snippet(
+            let help = diagnostic.kind.help(self);
+            let expansions = self.preproc.expansions_of(&diagnostic.span);
+            let invocation = expansions
+                .last()
+                .map(|(expansion, _)| format!("in expansion of macro `{}`", expansion.name));
+            let mut definitions = vec![];
+            let mut notes = vec![];
+            for (expansion, definition) in &expansions {
+                match definition {
+                    Some(definition) if !definition.is_empty() => {
+                        let (start, include) = self.preproc.translate_position(definition.start);
+                        definitions.push((
+                            format!("in the definition of macro `{}`", expansion.name),
+                            include,
+                            start..start + definition.len(),
+                        ));
+                    }
+                    Some(_) => {}
+                    None => notes.push(format!(
+                        "macro `{}` is defined by -D, or by [defines] in goboscript.toml",
+                        expansion.name
+                    )),
+                }
+            }
+            let mut annotation = level.span(start..end);
+            if let Some(invocation) = &invocation {
+                annotation = annotation.label(invocation);
+            }
+            let mut message = level.title(&title).id(diagnostic.kind.code()).snippet(
                 Snippet::source(&src[include.range.clone()])
                     .origin(include.path.to_str().unwrap())
                     .fold(true)
-                    .annotation(level.span(start..end)),
+                    .annotation(annotation),
             );
+            for (label, include, range) in &definitions {
+                message = message.snippet(
+                    Snippet::source(&src[include.range.clone()])
+                        .origin(include.path.to_str().unwrap())
+                        .fold(true)
+                        .annotation(Level::Info.span(range.clone()).label(label)),
+                );
+            }
+            for note in &notes {
+                message = message.footer(Level::Note.title(note));
+            }
+            if let Some(help) = &help {
+                message = message.footer(Level::Help.title(help));
+            }
             eprintln!("{}", renderer.render(message));
-            if let DiagnosticKind::CommandFailed { stderr } = &diagnostic.kind {
-                eprintln!("{}:", "stderr".red().bold());
-                for line in stderr.split(|&b| b == b'\n') {
-                    eprintln!("    {}", std::str::from_utf8(line).unwrap().red());
+            if let DiagnosticKind::CommandFailed { stderr, .. } = &diagnostic.kind {
+                if !stderr.is_empty() {
+                    eprintln!("{}:", "stderr".red().bold());
+                    for line in String::from_utf8_lossy(stderr).lines() {
+                        eprintln!("    {}", line.red());
+                    }
                 }
             }
         }
     }
+
+    pub fn eprint_message(&self, renderer: &Renderer, level: Level, title: &str, span: &Span) {
+        let src = self.preproc.get_translation_unit();
+        let span = self.preproc.call_site(span);
+        let (start, include) = self.preproc.translate_position(span.start);
+        let (end, _) = self.preproc.translate_position(span.end - 1);
+        let message = level.title(title).snippet(
+            Snippet::source(&src[include.range.clone()])
+                .origin(include.path.to_str().unwrap())
+                .fold(true)
+                .annotation(level.span(start..end + 1)),
+        );
+        eprintln!("{}", renderer.render(message));
+    }
 }
@@ -0,0 +1,26 @@
+use smol_str::SmolStr;
+
+/// A candidate is close if its edit distance to `name` is at most a third of the length
+/// of `name`, or if it starts with `name`, which catches abbreviations such as `x_pos`.
+pub fn suggest<'a, I>(name: &str, candidates: I) -> Option<SmolStr>
+where I: IntoIterator<Item = &'a str> {
+    let max_distance = (name.len() / 3).max(1);
+    let mut best: Option<((bool, usize), &str)> = None;
+    for candidate in candidates {
+        if candidate == name {
+            continue;
+        }
+        let distance = strsim::osa_distance(name, candidate);
+        let is_abbreviation = name.len() >= 3 && candidate.starts_with(name);
+        let key = (distance > max_distance, distance);
+        if key.0 && !is_abbreviation {
+            continue;
+        }
+        if best
+            .is_none_or(|(best_key, best_candidate)| (key, candidate) < (best_key, best_candidate))
+        {
+            best = Some((key, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate.into())
+}
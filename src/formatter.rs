@@ -0,0 +1,570 @@
+use std::{mem, ops::Range};
+
+use crate::{
+    lexer::{adaptor::Lexer, token::Token},
+    preproc::directive_end,
+};
+
+const INDENT: &str = "    ";
+
+#[derive(Debug)]
+pub enum FormatError {
+    InvalidToken(Range<usize>),
+    /// The formatted code does not lex back to the same tokens, comments and directives
+    /// as the original. This is a bug in the formatter, and nothing should be written.
+    Unstable,
+}
+
+pub fn format(src: &str) -> Result<String, FormatError> {
+    let original = pieces(src)?;
+    let mut formatter = Formatter::new(src);
+    for (i, piece) in original.iter().enumerate() {
+        formatter.piece(piece, original.get(i + 1));
+    }
+    let formatted = formatter.finish();
+    let reformatted = pieces(&formatted).map_err(|_| FormatError::Unstable)?;
+    let texts = |src: &str, pieces: &[Piece]| -> Vec<String> {
+        pieces
+            .iter()
+            .map(|piece| src[piece.span.clone()].trim_end().to_string())
+            .collect()
+    };
+    if texts(src, &original) != texts(&formatted, &reformatted) {
+        return Err(FormatError::Unstable);
+    }
+    Ok(formatted)
+}
+
+enum PieceKind {
+    Token(Token),
+    Comment,
+    Directive,
+    Verbatim,
+}
+
+struct Piece {
+    kind: PieceKind,
+    span: Range<usize>,
+    spaced: bool,
+    newlines: usize,
+}
+
+fn pieces(src: &str) -> Result<Vec<Piece>, FormatError> {
+    let mut pieces = vec![];
+    let mut spaced = false;
+    let mut newlines = 0;
+    let mut segment_start = 0;
+    let mut line_start = 0;
+    let mut conditions = 0usize;
+    loop {
+        let directive = directive_end(src, line_start);
+        let line_end = directive.unwrap_or_else(|| {
+            src[line_start..]
+                .find('\n')
+                .map_or(src.len(), |i| line_start + i)
+        });
+        if directive.is_some() || line_end == src.len() {
+            let segment_end = if directive.is_some() {
+                line_start
+            } else {
+                src.len()
+            };
+            // Which branch of a `%if` is built depends on the macros defined when building,
+            // and the others may not even lex, so they are all copied as they are.
+            let segment = &src[segment_start..segment_end];
+            if conditions > 0 {
+                let code_start = segment.len() - segment.trim_start().len();
+                let code_end = segment.trim_end().len();
+                if code_start == segment.len() {
+                    newlines += segment.matches('\n').count();
+                } else {
+                    let start = segment[..code_start].rfind('\n').map_or(0, |i| i + 1);
+                    pieces.push(Piece {
+                        kind: PieceKind::Verbatim,
+                        span: segment_start + start..segment_start + code_end,
+                        spaced: mem::take(&mut spaced),
+                        newlines: mem::take(&mut newlines) + segment[..start].matches('\n').count(),
+                    });
+                    newlines += segment[code_end..].matches('\n').count();
+                }
+            }
+            let lexed = if conditions > 0 { "" } else { segment };
+            for token in Lexer::lossless(lexed) {
+                let (start, token, end) = token.map_err(|diagnostic| {
+                    FormatError::InvalidToken(
+                        segment_start + diagnostic.span.start..segment_start + diagnostic.span.end,
+                    )
+                })?;
+                let span = segment_start + start..segment_start + end;
+                let kind = match token {
+                    Token::Whitespace => {
+                        spaced = true;
+                        newlines += src[span].matches('\n').count();
+                        continue;
+                    }
+                    Token::Comment => PieceKind::Comment,
+                    token => PieceKind::Token(token),
+                };
+                pieces.push(Piece {
+                    kind,
+                    span,
+                    spaced: mem::take(&mut spaced),
+                    newlines: mem::take(&mut newlines),
+                });
+            }
+            if directive.is_some() {
+                let line = src[line_start..line_end].trim_start();
+                if line.starts_with("%if") {
+                    conditions += 1;
+                } else if line.starts_with("%endif") {
+                    conditions = conditions.saturating_sub(1);
+                }
+                pieces.push(Piece {
+                    kind: PieceKind::Directive,
+                    span: line_start..line_end,
+                    spaced: mem::take(&mut spaced),
+                    newlines: mem::take(&mut newlines),
+                });
+                segment_start = line_end;
+            }
+        }
+        if line_end == src.len() {
+            break;
+        }
+        line_start = line_end + 1;
+    }
+    Ok(pieces)
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Scope {
+    Block { multiline: bool },
+    List { multiline: bool, declaration: bool },
+    Group,
+}
+
+impl Scope {
+    fn breaks(&self) -> bool {
+        matches!(
+            self,
+            Scope::Block { multiline: true }
+                | Scope::List {
+                    multiline: true,
+                    ..
+                }
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Header {
+    Block,
+    Declaration,
+}
+
+struct Prev {
+    token: Token,
+    span: Range<usize>,
+    starts_statement: bool,
+    unary: bool,
+    closes: Option<Scope>,
+}
+
+impl Prev {
+    fn ends_value(&self) -> bool {
+        match self.token {
+            Token::Name(_)
+            | Token::Arg(_)
+            | Token::Int(_)
+            | Token::Float(_)
+            | Token::Hex(_)
+            | Token::Oct(_)
+            | Token::Bin(_)
+            | Token::Str(_)
+            | Token::Cmd(_)
+            | Token::True
+            | Token::False
+            | Token::RParen
+            | Token::RBracket => true,
+            Token::RBrace => matches!(
+                self.closes,
+                Some(Scope::List {
+                    declaration: false,
+                    ..
+                })
+            ),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Line {
+    indent: usize,
+    code: String,
+    comment: Option<String>,
+}
+
+impl Line {
+    fn is_empty(&self) -> bool {
+        self.code.is_empty() && self.comment.is_none()
+    }
+
+    fn width(&self) -> usize {
+        match self.code.rfind('\n') {
+            Some(i) => self.code[i + 1..].chars().count(),
+            None => self.indent * INDENT.len() + self.code.chars().count(),
+        }
+    }
+}
+
+struct Open {
+    scope: Scope,
+    indent: usize,
+}
+
+struct Formatter<'src> {
+    src: &'src str,
+    lines: Vec<Line>,
+    line: Line,
+    scopes: Vec<Open>,
+    header: Option<Header>,
+    prev: Option<Prev>,
+    break_line: bool,
+    boundary: bool,
+    statement_indent: usize,
+    opened: bool,
+}
+
+impl<'src> Formatter<'src> {
+    fn new(src: &'src str) -> Self {
+        Self {
+            src,
+            lines: vec![],
+            line: Line::default(),
+            scopes: vec![],
+            header: None,
+            prev: None,
+            break_line: false,
+            boundary: true,
+            statement_indent: 0,
+            opened: false,
+        }
+    }
+
+    fn is_statement_level(&self) -> bool {
+        matches!(
+            self.scopes.last(),
+            None | Some(Open {
+                scope: Scope::Block { .. },
+                ..
+            })
+        )
+    }
+
+    fn item_indent(&self) -> usize {
+        self.scopes
+            .iter()
+            .rev()
+            .find(|open| open.scope.breaks())
+            .map_or(0, |open| open.indent + 1)
+    }
+
+    fn continuation_indent(&self) -> usize {
+        match self.scopes.last() {
+            Some(open) if !open.scope.breaks() => open.indent + 1,
+            _ => self.item_indent() + 1,
+        }
+    }
+
+    fn start_line(&mut self, newlines: usize, closes: Option<&Open>) {
+        if !self.line.is_empty() {
+            self.lines.push(mem::take(&mut self.line));
+        }
+        if newlines > 1
+            && self.boundary
+            && !self.opened
+            && closes.is_none()
+            && !self.lines.is_empty()
+        {
+            self.lines.push(Line::default());
+        }
+        self.opened = false;
+        self.break_line = false;
+        self.line.indent = match closes {
+            Some(open) => open.indent,
+            None if self.boundary => self.item_indent(),
+            None => self.continuation_indent(),
+        };
+    }
+
+    fn piece(&mut self, piece: &Piece, next: Option<&Piece>) {
+        let text = &self.src[piece.span.clone()];
+        match &piece.kind {
+            PieceKind::Directive | PieceKind::Verbatim => {
+                self.start_line(piece.newlines, None);
+                self.line.indent = 0;
+                self.line.code = text.trim_end().to_string();
+                self.break_line = true;
+            }
+            PieceKind::Comment => {
+                if piece.newlines > 0 || self.line.code.is_empty() {
+                    self.start_line(piece.newlines, None);
+                }
+                self.line.comment = Some(text.trim_end().to_string());
+                self.break_line = true;
+            }
+            PieceKind::Token(token) => self.token(token, piece, next),
+        }
+    }
+
+    fn token(&mut self, token: &Token, piece: &Piece, next: Option<&Piece>) {
+        let text = &self.src[piece.span.clone()];
+        let mut closes = None;
+        match token {
+            Token::RBrace => {
+                while let Some(Scope::Group) = self.scopes.last().map(|open| open.scope) {
+                    self.scopes.pop();
+                }
+                closes = self.scopes.pop();
+                if closes.as_ref().is_some_and(|open| open.scope.breaks()) {
+                    self.break_line = true;
+                }
+            }
+            Token::RParen | Token::RBracket => {
+                if let Some(Scope::Group) = self.scopes.last().map(|open| open.scope) {
+                    closes = self.scopes.pop();
+                }
+            }
+            _ => {}
+        }
+        let continues_if = matches!(token, Token::Else | Token::Elif)
+            && self.line.comment.is_none()
+            && self
+                .prev
+                .as_ref()
+                .is_some_and(|prev| matches!(prev.closes, Some(Scope::Block { .. })));
+        // Line breaks inside a statement are kept, except before an opening brace or a
+        // separator.
+        let keeps_break = piece.newlines > 0
+            && !self.boundary
+            && !matches!(token, Token::LBrace | Token::Comma | Token::Semicolon);
+        if (self.break_line || keeps_break) && !continues_if {
+            self.start_line(piece.newlines, closes.as_ref());
+        } else if !self.line.code.is_empty() {
+            let prev = self.prev.as_ref().unwrap();
+            if self.is_spaced(prev, token, piece)
+                || !lexes_apart(&self.src[prev.span.clone()], text)
+            {
+                self.line.code.push(' ');
+            }
+        }
+        self.break_line = false;
+        self.line.code.push_str(text);
+
+        let is_statement_level = self.is_statement_level();
+        let starts_statement = self.boundary && is_statement_level;
+        let unary = matches!(token, Token::Minus)
+            && self.prev.as_ref().is_none_or(|prev| {
+                !prev.ends_value() || prev.starts_statement && matches!(prev.token, Token::Name(_))
+            });
+        self.boundary = false;
+        if starts_statement {
+            self.statement_indent = self.line.indent;
+        }
+        match token {
+            Token::LBrace => {
+                let header = if is_statement_level {
+                    self.header.take()
+                } else {
+                    None
+                };
+                let multiline = next.is_some_and(|next| {
+                    next.newlines > 0 || !matches!(next.kind, PieceKind::Token(Token::RBrace))
+                });
+                let scope = match header {
+                    Some(Header::Block) => Scope::Block { multiline },
+                    _ => Scope::List {
+                        multiline: next.is_some_and(|next| {
+                            next.newlines > 0 || matches!(next.kind, PieceKind::Comment)
+                        }),
+                        declaration: header.is_some(),
+                    },
+                };
+                if scope.breaks() {
+                    self.end_line();
+                    self.opened = true;
+                }
+                // A block's body is indented from the line its statement started on, not
+                // from the continuation line of a condition broken over several lines.
+                let indent = match scope {
+                    Scope::Block { .. } => self.statement_indent,
+                    _ => self.line.indent,
+                };
+                self.scopes.push(Open { scope, indent });
+            }
+            Token::RBrace => {
+                if matches!(
+                    closes.as_ref().map(|open| open.scope),
+                    Some(
+                        Scope::Block { .. }
+                            | Scope::List {
+                                declaration: true,
+                                ..
+                            }
+                    )
+                ) {
+                    self.end_line();
+                }
+            }
+            Token::LParen | Token::LBracket => self.scopes.push(Open {
+                scope: Scope::Group,
+                indent: self.line.indent,
+            }),
+            Token::Semicolon if is_statement_level => {
+                self.header = None;
+                self.end_line();
+            }
+            Token::Comma
+                if self.scopes.last().is_some_and(|open| {
+                    matches!(open.scope, Scope::List { .. }) && open.scope.breaks()
+                }) =>
+            {
+                self.end_line()
+            }
+            // `pub` is followed by the declaration it makes public.
+            Token::Pub if starts_statement => self.boundary = true,
+            _ if starts_statement => self.header = header(token),
+            _ => {}
+        }
+        self.prev = Some(Prev {
+            token: token.clone(),
+            span: piece.span.clone(),
+            starts_statement,
+            unary,
+            closes: closes.map(|open| open.scope),
+        });
+    }
+
+    fn end_line(&mut self) {
+        self.break_line = true;
+        self.boundary = true;
+    }
+
+    fn is_spaced(&self, prev: &Prev, token: &Token, piece: &Piece) -> bool {
+        if prev.unary {
+            return false;
+        }
+        match (&prev.token, token) {
+            (
+                _,
+                Token::RParen
+                | Token::RBracket
+                | Token::Comma
+                | Token::Semicolon
+                | Token::Dot
+                | Token::Colon
+                | Token::Increment
+                | Token::Decrement,
+            ) => false,
+            (Token::LParen | Token::LBracket | Token::Dot, _) => false,
+            (Token::LBrace, Token::RBrace) => false,
+            // `a!=b` is lexed as `a!` followed by `=`.
+            (Token::Mac(_), Token::Assign) => false,
+            // A block whose first argument is in parentheses, or a procedure call.
+            (Token::Name(_), Token::LParen) if prev.starts_statement => piece.spaced,
+            (
+                Token::Name(_)
+                | Token::Mac(_)
+                | Token::Length
+                | Token::Round
+                | Token::Abs
+                | Token::Floor
+                | Token::Ceil
+                | Token::Sqrt
+                | Token::Sin
+                | Token::Cos
+                | Token::Tan
+                | Token::Asin
+                | Token::Acos
+                | Token::Atan
+                | Token::Ln
+                | Token::Log
+                | Token::Antiln
+                | Token::Antilog,
+                Token::LParen,
+            ) => false,
+            (_, Token::LBracket) => !prev.ends_value(),
+            _ => true,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if !self.line.is_empty() {
+            self.lines.push(mem::take(&mut self.line));
+        }
+        let mut output = String::new();
+        let mut i = 0;
+        while i < self.lines.len() {
+            // Trailing comments on consecutive lines are aligned with each other.
+            let group = self.lines[i..]
+                .iter()
+                .take_while(|line| !line.code.is_empty() && line.comment.is_some())
+                .count()
+                .max(1);
+            let width = self.lines[i..i + group]
+                .iter()
+                .map(Line::width)
+                .max()
+                .unwrap();
+            for line in &self.lines[i..i + group] {
+                if !line.is_empty() {
+                    output.push_str(&INDENT.repeat(line.indent));
+                }
+                output.push_str(&line.code);
+                if let Some(comment) = &line.comment {
+                    if !line.code.is_empty() {
+                        output.push_str(&" ".repeat(width - line.width() + 1));
+                    }
+                    output.push_str(comment);
+                }
+                output.push('\n');
+            }
+            i += group;
+        }
+        output
+    }
+}
+
+fn header(token: &Token) -> Option<Header> {
+    match token {
+        Token::If
+        | Token::Elif
+        | Token::Else
+        | Token::Repeat
+        | Token::Until
+        | Token::Forever
+        | Token::Proc
+        | Token::NoWarp
+        | Token::Func
+        | Token::On
+        | Token::OnFlag
+        | Token::OnKey
+        | Token::OnClick
+        | Token::OnBackdrop
+        | Token::OnLoudness
+        | Token::OnTimer
+        | Token::OnClone
+        | Token::Test => Some(Header::Block),
+        Token::Struct | Token::Enum => Some(Header::Declaration),
+        _ => None,
+    }
+}
+
+fn lexes_apart(left: &str, right: &str) -> bool {
+    let joined = format!("{left}{right}");
+    matches!(
+        Lexer::lossless(&joined).next(),
+        Some(Ok((_, _, end))) if end == left.len()
+    )
+}
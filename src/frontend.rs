@@ -1,27 +1,138 @@
-mod build;
+pub mod build;
 mod cli;
+mod decompile;
+mod emit;
+mod fmt;
 mod new;
+mod run;
+mod test;
+mod validate;
 
 use std::process::ExitCode;
 
+use annotate_snippets::{Level, Renderer, Snippet};
 use clap::{CommandFactory, Parser};
-use cli::{Cli, Command};
+use cli::{Cli, Command, MessageFormat};
 use colored::Colorize;
+use fmt::FmtError;
 use new::NewError;
 
-use crate::config::Config;
+use crate::{
+    config::Config,
+    diagnostic::{explanation, sarif, DiagnosticKind, CODES},
+    lsp,
+};
 
 pub fn frontend() -> ExitCode {
     match Cli::parse().command {
-        Command::Build { input, output } => match build::build(input, output) {
+        Command::Build {
+            input,
+            output,
+            emit,
+            release,
+            validate,
+            no_exec,
+            no_cache,
+            define,
+            message_format,
+        } => report(
+            build::build(
+                input, output, emit, release, validate, no_exec, no_cache, define,
+            ),
+            message_format,
+        ),
+        Command::Check {
+            input,
+            no_exec,
+            define,
+            message_format,
+        } => report(build::check(input, no_exec, define), message_format),
+        Command::Run {
+            input,
+            no_exec,
+            define,
+        } => report(run::run(input, no_exec, define), MessageFormat::Human),
+        Command::Test {
+            input,
+            filter,
+            no_exec,
+            define,
+            max_steps,
+        } => report(
+            test::test(input, filter, no_exec, define, max_steps),
+            MessageFormat::Human,
+        ),
+        Command::Decompile { input, output } => match decompile::decompile(input, output) {
             Ok(()) => ExitCode::SUCCESS,
-            Err(build::BuildError::AnyhowError(err)) => {
+            Err(err) => {
                 eprintln!("{}: {:?}", "error".red().bold(), err);
                 ExitCode::FAILURE
             }
-            Err(build::BuildError::ProjectDiagnostics(diagnostics)) => {
-                diagnostics.eprint();
-                eprintln!();
+        },
+        Command::Validate { input } => match validate::validate(input) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("{}: {:?}", "error".red().bold(), err);
+                ExitCode::FAILURE
+            }
+        },
+        Command::Fmt { paths, check } => match fmt::fmt(paths, check) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(FmtError::AnyhowError(err)) => {
+                eprintln!("{}: {:?}", "error".red().bold(), err);
+                ExitCode::FAILURE
+            }
+            Err(FmtError::InvalidToken { path, src, span }) => {
+                let message = Level::Error
+                    .title("invalid token")
+                    .id(DiagnosticKind::InvalidToken.code())
+                    .snippet(
+                        Snippet::source(&src)
+                            .origin(path.to_str().unwrap())
+                            .fold(true)
+                            .annotation(Level::Error.span(span)),
+                    );
+                eprintln!("{}", Renderer::styled().render(message));
+                ExitCode::FAILURE
+            }
+            Err(FmtError::Unformatted(paths)) => {
+                for path in &paths {
+                    eprintln!(
+                        "{}: {} is not formatted",
+                        "error".red().bold(),
+                        path.display()
+                    );
+                }
+                eprintln!(
+                    "{}: run `goboscript fmt` to format them",
+                    "hint".blue().bold()
+                );
+                ExitCode::FAILURE
+            }
+        },
+        Command::Explain { code } => match explanation(&code) {
+            Some(explanation) => {
+                print!("{explanation}");
+                ExitCode::SUCCESS
+            }
+            None => {
+                eprintln!(
+                    "{}: {} is not a goboscript diagnostic code",
+                    "error".red().bold(),
+                    code
+                );
+                eprintln!(
+                    "{}: valid codes are {}",
+                    "hint".blue().bold(),
+                    CODES.join(", ")
+                );
+                ExitCode::FAILURE
+            }
+        },
+        Command::Lsp => match lsp::serve() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("{}: {:?}", "error".red().bold(), err);
                 ExitCode::FAILURE
             }
         },
@@ -51,6 +162,7 @@ pub fn frontend() -> ExitCode {
                     high_quality_pen: Some(high_quality_pen),
                     stage_width,
                     stage_height,
+                    ..Default::default()
                 },
             ) {
                 Err(NewError::AnyhowError(err)) => {
@@ -72,3 +184,30 @@ pub fn frontend() -> ExitCode {
         }
     }
 }
+
+fn report(result: Result<(), build::BuildError>, message_format: MessageFormat) -> ExitCode {
+    match result {
+        Ok(()) => {
+            if let MessageFormat::Sarif = message_format {
+                println!("{}", serde_json::to_string_pretty(&sarif(&[])).unwrap());
+            }
+            ExitCode::SUCCESS
+        }
+        Err(build::BuildError::AnyhowError(err)) => {
+            eprintln!("{}: {:?}", "error".red().bold(), err);
+            ExitCode::FAILURE
+        }
+        Err(build::BuildError::ProjectDiagnostics(diagnostics)) => {
+            match message_format {
+                MessageFormat::Human => {
+                    diagnostics.eprint();
+                    eprintln!();
+                }
+                MessageFormat::Short => diagnostics.eprint_short(),
+                MessageFormat::Json => diagnostics.print_json(),
+                MessageFormat::Sarif => diagnostics.print_sarif(),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,26 +1,31 @@
 use std::{
     env,
     fs::{self, File},
-    io::BufWriter,
-    path::PathBuf,
+    io::{BufWriter, Cursor, Seek, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
 };
 
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
 use fxhash::FxHashMap;
 use log::info;
 use smol_str::SmolStr;
 
+use super::{cli::Emit, emit, validate};
 use crate::{
     ast::{Project, Sprite},
-    codegen::sb3::Sb3,
-    config::Config,
+    codegen::sb3::{file_options, Sb3},
+    config::{CacheMode, Config, Define},
     diagnostic::{ProjectDiagnostics, SpriteDiagnostics},
-    parser, visitor,
+    imports, parser,
+    preproc::Overlay,
+    sb3::Archive,
+    stdlib, visitor,
 };
 
 pub enum BuildError {
     AnyhowError(anyhow::Error),
-    ProjectDiagnostics(ProjectDiagnostics),
+    ProjectDiagnostics(Box<ProjectDiagnostics>),
 }
 
 impl<T> From<T> for BuildError
@@ -33,32 +38,287 @@ where T: Into<anyhow::Error>
 
 impl From<ProjectDiagnostics> for BuildError {
     fn from(value: ProjectDiagnostics) -> Self {
-        Self::ProjectDiagnostics(value)
+        Self::ProjectDiagnostics(Box::new(value))
     }
 }
 
-pub fn build(input: Option<PathBuf>, output: Option<PathBuf>) -> Result<(), BuildError> {
+#[allow(clippy::too_many_arguments)]
+pub fn build(
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    emit: Emit,
+    release: bool,
+    validate: bool,
+    no_exec: bool,
+    no_cache: bool,
+    defines: Vec<(String, String)>,
+) -> Result<(), BuildError> {
     let input = input.unwrap_or_else(|| env::current_dir().unwrap());
     let canonical_input = input.canonicalize()?;
     let project_name = canonical_input.file_name().unwrap().to_str().unwrap();
-    let output = output.unwrap_or_else(|| input.join(format!("{project_name}.sb3")));
+    match emit {
+        Emit::Sb3 => {
+            let output = output.unwrap_or_else(|| input.join(format!("{project_name}.sb3")));
+            let temp_output = emit::temp_path(&output);
+            let file = BufWriter::new(File::create(&temp_output)?);
+            let file = match compile(
+                &input,
+                file,
+                Default::default(),
+                release,
+                no_exec,
+                cache_mode(no_cache),
+                &defines,
+            ) {
+                Ok(file) => file,
+                Err(err) => {
+                    let _ = fs::remove_file(&temp_output);
+                    return Err(err);
+                }
+            };
+            file.into_inner().map_err(|err| err.into_error())?;
+            if validate {
+                let checked = fs::read(&temp_output)
+                    .map_err(anyhow::Error::from)
+                    .and_then(Archive::project_json)
+                    .and_then(|project_json| check_built(&project_json));
+                if let Err(err) = checked {
+                    let _ = fs::remove_file(&temp_output);
+                    return Err(err.into());
+                }
+            }
+            fs::rename(&temp_output, &output)?;
+        }
+        Emit::Json => {
+            let (archive, _) = unpack(&input, no_exec, no_cache, &defines)?;
+            if validate {
+                check_built(&serde_json::to_vec(&archive.project)?)?;
+            }
+            emit::json(
+                &archive,
+                &output.unwrap_or_else(|| input.join("project.json")),
+            )?;
+        }
+        Emit::Dir => {
+            let (archive, _) = unpack(&input, no_exec, no_cache, &defines)?;
+            if validate {
+                check_built(&serde_json::to_vec(&archive.project)?)?;
+            }
+            emit::dir(
+                &archive,
+                &output.unwrap_or_else(|| input.join(project_name)),
+            )?;
+        }
+        Emit::Sprite3 => {
+            let (archive, config) = unpack(&input, no_exec, no_cache, &defines)?;
+            let output = output.unwrap_or_else(|| input.clone());
+            if validate {
+                check_built(&serde_json::to_vec(&archive.project)?)?;
+            }
+            emit::sprite3(&archive, &output, file_options(&config, release))?;
+        }
+    }
+    Ok(())
+}
+
+fn cache_mode(no_cache: bool) -> CacheMode {
+    if no_cache {
+        CacheMode::Off
+    } else {
+        CacheMode::ReadWrite
+    }
+}
+
+fn check_built(project_json: &[u8]) -> anyhow::Result<()> {
+    validate::check(project_json).context("the built project is not a valid Scratch 3 project")
+}
+
+fn unpack(
+    input: &Path,
+    no_exec: bool,
+    no_cache: bool,
+    defines: &[(String, String)],
+) -> Result<(Archive, Config), BuildError> {
+    let Compiled { file, config, .. } = compile_project(
+        input,
+        Cursor::new(Vec::new()),
+        Default::default(),
+        false,
+        no_exec,
+        cache_mode(no_cache),
+        defines,
+    )?;
+    Ok((Archive::read(file.into_inner())?, config))
+}
+
+pub fn check(
+    input: Option<PathBuf>,
+    no_exec: bool,
+    defines: Vec<(String, String)>,
+) -> Result<(), BuildError> {
+    let input = input.unwrap_or_else(|| env::current_dir().unwrap());
+    compile(
+        &input,
+        Cursor::new(Vec::new()),
+        Default::default(),
+        false,
+        no_exec,
+        CacheMode::ReadOnly,
+        &defines,
+    )?;
+    Ok(())
+}
+
+pub fn compile<T>(
+    input: &Path,
+    file: T,
+    overlay: Rc<Overlay>,
+    release: bool,
+    no_exec: bool,
+    cache_mode: CacheMode,
+    defines: &[(String, String)],
+) -> Result<T, BuildError>
+where
+    T: Write + Seek,
+{
+    compile_project(input, file, overlay, release, no_exec, cache_mode, defines)
+        .map(|compiled| compiled.file)
+}
+
+pub struct Compiled<T> {
+    pub file: T,
+    pub project: Project,
+    pub config: Config,
+    pub stage_diagnostics: SpriteDiagnostics,
+    pub sprites_diagnostics: FxHashMap<SmolStr, SpriteDiagnostics>,
+}
+
+pub fn compile_project<T>(
+    input: &Path,
+    file: T,
+    overlay: Rc<Overlay>,
+    release: bool,
+    no_exec: bool,
+    cache_mode: CacheMode,
+    defines: &[(String, String)],
+) -> Result<Compiled<T>, BuildError>
+where
+    T: Write + Seek,
+{
+    let mut config = load_config(input)?;
+    if no_exec {
+        config.allow_exec = Some(false);
+    }
+    config.cache_mode = cache_mode;
+    for (name, value) in defines {
+        config
+            .defines
+            .insert(name.clone(), Define::Source(value.clone()));
+    }
+    let diagnostics = parse_project(input, overlay, &config)?;
+    if !diagnostics.is_empty() {
+        return Err(diagnostics.into());
+    }
+    let ProjectDiagnostics {
+        mut project,
+        mut stage_diagnostics,
+        mut sprites_diagnostics,
+    } = diagnostics;
+    info!(target: "parse", "{project:#?}");
+    visitor::pass0::visit_project(&mut project);
+    info!(target: "pass0", "{project:#?}");
+    visitor::pass1::visit_project(
+        &mut project,
+        &mut stage_diagnostics,
+        &mut sprites_diagnostics,
+    );
+    info!(target: "pass1", "{project:#?}");
+    visitor::pass2::visit_project(&mut project);
+    info!(target: "pass2", "{project:#?}");
+    let mut sb3 = Sb3::new(file, file_options(&config, release));
+    sb3.project(
+        input,
+        &project,
+        &config,
+        &mut stage_diagnostics,
+        &mut sprites_diagnostics,
+    )?;
+    let diagnostics = ProjectDiagnostics {
+        project,
+        stage_diagnostics,
+        sprites_diagnostics,
+    };
+    if !diagnostics.is_empty() {
+        return Err(diagnostics.into());
+    }
+    Ok(Compiled {
+        file: sb3.zip.finish()?,
+        project: diagnostics.project,
+        config,
+        stage_diagnostics: diagnostics.stage_diagnostics,
+        sprites_diagnostics: diagnostics.sprites_diagnostics,
+    })
+}
+
+pub fn load_config(input: &Path) -> anyhow::Result<Config> {
     let config_path = input.join("goboscript.toml");
     let config_src = fs::read_to_string(&config_path).unwrap_or_default();
     let config: Config = toml::from_str(&config_src)
         .with_context(|| format!("failed to parse {}", config_path.display()))?;
+    if let Some(level) = config.compression_level {
+        if !(0..=9).contains(&level) {
+            bail!(
+                "failed to parse {}: compression_level must be from 0 to 9, not {level}",
+                config_path.display()
+            );
+        }
+    }
+    if let Some(timeout) = config.command_timeout {
+        if !(timeout > 0.0 && timeout.is_finite()) {
+            bail!(
+                "failed to parse {}: command_timeout must be a number of seconds more than 0, \
+                 not {timeout}",
+                config_path.display()
+            );
+        }
+    }
+    Ok(config)
+}
+
+pub fn parse_project(
+    input: &Path,
+    overlay: Rc<Overlay>,
+    config: &Config,
+) -> anyhow::Result<ProjectDiagnostics> {
     let stage_path = input.join("stage.gs");
     if !stage_path.is_file() {
-        return Err(anyhow!("{} not found", stage_path.display()).into());
+        return Err(anyhow!("{} not found", stage_path.display()));
+    }
+    let defines: FxHashMap<String, String> = config
+        .defines
+        .iter()
+        .map(|(name, value)| (name.clone(), value.source()))
+        .collect();
+    let mut include_paths: Vec<PathBuf> = config
+        .include_paths
+        .iter()
+        .map(|path| input.join(path))
+        .collect();
+    if let Some(paths) = env::var_os("GOBOSCRIPT_PATH") {
+        include_paths.extend(env::split_paths(&paths));
     }
-    let mut stage_diagnostics = SpriteDiagnostics::new(stage_path)?;
-    let stage = parser::parse(&stage_diagnostics.preproc)
+    let mut stage_diagnostics =
+        SpriteDiagnostics::new(stage_path, overlay.clone(), &defines, &include_paths)?;
+    let mut stage = parser::parse(&mut stage_diagnostics.preproc)
         .map_err(|err| {
             stage_diagnostics.diagnostics.push(err);
         })
         .unwrap_or_default();
+    imports::import(&mut stage, &mut stage_diagnostics, &defines);
+    stdlib::prune(&mut stage, &stage_diagnostics.preproc);
     let mut sprites_diagnostics: FxHashMap<SmolStr, SpriteDiagnostics> = Default::default();
     let mut sprites: FxHashMap<SmolStr, Sprite> = Default::default();
-    for sprite_path in fs::read_dir(&input)? {
+    for sprite_path in fs::read_dir(input)? {
         let sprite_path = sprite_path?.path();
         if sprite_path.file_stem().is_some_and(|stem| stem == "stage") {
             continue;
@@ -78,56 +338,80 @@ pub fn build(input: Option<PathBuf>, output: Option<PathBuf>) -> Result<(), Buil
             .to_str()
             .unwrap()
             .into();
-        let mut sprite_diagnostics = SpriteDiagnostics::new(sprite_path)?;
-        let sprite = parser::parse(&sprite_diagnostics.preproc)
+        let mut sprite_diagnostics =
+            SpriteDiagnostics::new(sprite_path, overlay.clone(), &defines, &include_paths)?;
+        let mut sprite = parser::parse(&mut sprite_diagnostics.preproc)
             .map_err(|err| sprite_diagnostics.diagnostics.push(err))
             .unwrap_or_default();
+        imports::import(&mut sprite, &mut sprite_diagnostics, &defines);
+        stdlib::prune(&mut sprite, &sprite_diagnostics.preproc);
         sprites_diagnostics.insert(sprite_name.clone(), sprite_diagnostics);
         sprites.insert(sprite_name, sprite);
     }
-    let mut project = Project { stage, sprites };
-    if !(stage_diagnostics.diagnostics.is_empty()
-        && sprites_diagnostics
-            .values()
-            .all(|sprite_diagnostics| sprite_diagnostics.diagnostics.is_empty()))
-    {
-        return Err(ProjectDiagnostics {
-            project,
-            stage_diagnostics,
-            sprites_diagnostics,
+    Ok(ProjectDiagnostics {
+        project: Project { stage, sprites },
+        stage_diagnostics,
+        sprites_diagnostics,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::*;
+    use crate::misc::normalize_path;
+
+    /// Block IDs of the main sprite of tests/test, grouped by the script they are in.
+    fn scripts(overlay: Overlay) -> BTreeMap<String, BTreeSet<String>> {
+        let input = normalize_path(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test"));
+        let Ok(file) = compile(
+            &input,
+            Cursor::new(Vec::new()),
+            Rc::new(overlay),
+            false,
+            true,
+            CacheMode::Off,
+            &[],
+        ) else {
+            panic!("tests/test does not build");
+        };
+        let archive = Archive::read(file.into_inner()).unwrap();
+        let target = archive
+            .project
+            .targets
+            .iter()
+            .find(|target| target.name == "main")
+            .unwrap();
+        let mut scripts: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for id in target.blocks.keys() {
+            let (sprite_and_script, _) = id.rsplit_once(':').unwrap();
+            scripts
+                .entry(sprite_and_script.to_owned())
+                .or_default()
+                .insert(id.clone());
         }
-        .into());
+        scripts
     }
-    info!(target: "parse", "{project:#?}");
-    visitor::pass0::visit_project(&mut project);
-    info!(target: "pass0", "{project:#?}");
-    visitor::pass1::visit_project(
-        &mut project,
-        &mut stage_diagnostics,
-        &mut sprites_diagnostics,
-    );
-    info!(target: "pass1", "{project:#?}");
-    visitor::pass2::visit_project(&mut project);
-    info!(target: "pass2", "{project:#?}");
-    let mut sb3 = Sb3::new(BufWriter::new(File::create(output)?));
-    sb3.project(
-        &input,
-        &project,
-        &config,
-        &mut stage_diagnostics,
-        &mut sprites_diagnostics,
-    )?;
-    if !(stage_diagnostics.diagnostics.is_empty()
-        && sprites_diagnostics
-            .values()
-            .all(|sprite_diagnostics| sprite_diagnostics.diagnostics.is_empty()))
-    {
-        return Err(ProjectDiagnostics {
-            project,
-            stage_diagnostics,
-            sprites_diagnostics,
-        }
-        .into());
+
+    #[test]
+    fn editing_a_script_keeps_the_ids_of_the_others() {
+        let path =
+            normalize_path(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test/main.gs"));
+        let src = fs::read_to_string(&path).unwrap();
+        let edited = src.replacen(
+            "add $value to stack;",
+            "add $value to stack;\n    add $value to stack;",
+            1,
+        );
+        assert_ne!(src, edited);
+        let before = scripts(Overlay::default());
+        let after = scripts(Overlay::from_iter([(path, edited)]));
+        assert_eq!(before.len(), after.len());
+        let changed = before
+            .iter()
+            .filter(|(script, ids)| after.get(*script) != Some(ids))
+            .count();
+        assert_eq!(changed, 1);
     }
-    Ok(())
 }
@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use clap_derive::{Parser, Subcommand};
+use clap_derive::{Parser, Subcommand, ValueEnum};
 
 #[derive(Debug, Parser)]
 #[command(
@@ -20,8 +20,128 @@ pub enum Command {
         /// Project directory, if not given, the current directory is used.
         input: Option<PathBuf>,
         #[arg(short, long)]
-        /// Output file, if not given, it will be the project directory's name + `.sb3`
+        /// Output file, if not given, it will be the project directory's name + `.sb3`.
+        /// With `--emit json` it is `project.json`, with `--emit dir` it is a directory
+        /// named after the project, and with `--emit sprite3` it is the directory the
+        /// sprites are written to, the project directory if not given.
         output: Option<PathBuf>,
+        /// What to write. `sb3` writes a Scratch project, `json` writes its `project.json`
+        /// pretty-printed, `dir` writes the unpacked project to a directory, for diffing,
+        /// and `sprite3` writes a `.sprite3` archive for every sprite.
+        #[arg(long, value_enum, default_value_t = Emit::Sb3)]
+        emit: Emit,
+        /// Compress archives with `release_compression` from `goboscript.toml`, `deflate`
+        /// if not set, instead of `debug_compression`, `store` if not set.
+        #[arg(long)]
+        release: bool,
+        /// Check the built project against the Scratch 3 project schema, and check that
+        /// its blocks only refer to blocks, variables, lists and procedure arguments which
+        /// exist. Nothing is written if it is not valid.
+        #[arg(long)]
+        validate: bool,
+        /// Do not run commands which load list data, and report them as errors, unless
+        /// their output is cached. Lists are still loaded from files.
+        #[arg(long)]
+        no_exec: bool,
+        /// Run every command which loads list data, instead of using its output from
+        /// `.goboscript/cache`, and do not cache it.
+        #[arg(long)]
+        no_cache: bool,
+        /// Define a macro, as if by `%define NAME VALUE` in every sprite. The value is `1`
+        /// if not given. Overrides `[defines]` in `goboscript.toml`.
+        #[arg(short = 'D', value_name = "NAME[=VALUE]", value_parser = parse_define)]
+        define: Vec<(String, String)>,
+        /// How diagnostics are reported. `human` renders annotated snippets, `short`
+        /// prints one line per diagnostic, `json` prints one JSON object per line and
+        /// `sarif` prints a SARIF 2.1.0 log, for use in CI and editors.
+        #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+        message_format: MessageFormat,
+    },
+
+    /// Check a goboscript project for errors without writing any output.
+    #[command()]
+    Check {
+        #[arg(short, long)]
+        /// Project directory, if not given, the current directory is used.
+        input: Option<PathBuf>,
+        /// Do not run commands which load list data, see `goboscript build --help`.
+        #[arg(long)]
+        no_exec: bool,
+        /// Define a macro, see `goboscript build --help`.
+        #[arg(short = 'D', value_name = "NAME[=VALUE]", value_parser = parse_define)]
+        define: Vec<(String, String)>,
+        /// How diagnostics are reported, see `goboscript build --help`.
+        #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+        message_format: MessageFormat,
+    },
+
+    /// Build a goboscript project, and run it without a browser. Sprites say, think and
+    /// ask on stdout, and answers are read from stdin.
+    #[command()]
+    Run {
+        #[arg(short, long)]
+        /// Project directory, if not given, the current directory is used.
+        input: Option<PathBuf>,
+        /// Do not run commands which load list data, see `goboscript build --help`.
+        #[arg(long)]
+        no_exec: bool,
+        /// Define a macro, see `goboscript build --help`.
+        #[arg(short = 'D', value_name = "NAME[=VALUE]", value_parser = parse_define)]
+        define: Vec<(String, String)>,
+    },
+
+    /// Build a goboscript project, and run its tests without a browser.
+    #[command()]
+    Test {
+        #[arg(short, long)]
+        /// Project directory, if not given, the current directory is used.
+        input: Option<PathBuf>,
+        /// Only run tests whose name, prefixed with the name of their sprite such as
+        /// `main::add`, contains this.
+        filter: Option<String>,
+        /// Do not run commands which load list data, see `goboscript build --help`.
+        #[arg(long)]
+        no_exec: bool,
+        /// Define a macro, see `goboscript build --help`.
+        #[arg(short = 'D', value_name = "NAME[=VALUE]", value_parser = parse_define)]
+        define: Vec<(String, String)>,
+        /// Fail a test which runs more than this many operations, such as one stuck in a
+        /// loop. Overrides `test_max_steps` in goboscript.toml, 10000000 if neither is given.
+        #[arg(long)]
+        max_steps: Option<u64>,
+    },
+
+    /// Reconstruct a goboscript project from an `.sb3` file, with a `.gs` file for every
+    /// sprite and for the Stage, and their costumes. Blocks which goboscript cannot
+    /// represent are left out, and reported.
+    #[command()]
+    Decompile {
+        /// The `.sb3` file to decompile.
+        input: PathBuf,
+        /// Project directory to create, if not given, it will be the `.sb3` file's path
+        /// without its extension. If it exists, it must be empty.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check an `.sb3` file, or a `project.json`, against the Scratch 3 project schema,
+    /// and check that its blocks only refer to blocks, variables, lists and procedure
+    /// arguments which exist.
+    #[command()]
+    Validate {
+        /// The `.sb3` or `project.json` file to check.
+        input: PathBuf,
+    },
+
+    /// Format `.gs` files in place.
+    #[command()]
+    Fmt {
+        /// Files or directories to format, directories are searched recursively. If not
+        /// given, the current directory is used.
+        paths: Vec<PathBuf>,
+        /// Do not write anything, and fail if any file is not formatted, for use in CI.
+        #[arg(long)]
+        check: bool,
     },
 
     /// Create a new goboscript project with a blank backdrop, a main sprite with a
@@ -66,9 +186,47 @@ pub enum Command {
         #[arg(short = 'H', long, alias = "height")]
         stage_height: Option<u64>,
     },
+    /// Print a detailed explanation of a diagnostic code, such as `E0012` or `W0003`.
+    #[command()]
+    Explain {
+        /// The diagnostic code, as shown in brackets after `error` or `warning`.
+        code: String,
+    },
+    /// Run the language server, communicating over stdin and stdout.
+    #[command()]
+    Lsp,
     Completions {
         /// The shell to generate the completions for.
         #[arg(value_enum)]
         shell: clap_complete_command::Shell,
     },
 }
+
+#[derive(Debug, Copy, Clone, Default, ValueEnum)]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    Short,
+    Json,
+    Sarif,
+}
+
+#[derive(Debug, Copy, Clone, Default, ValueEnum)]
+pub enum Emit {
+    #[default]
+    Sb3,
+    Json,
+    Dir,
+    Sprite3,
+}
+
+/// `NAME=VALUE` of `-D`, or `NAME`, which is defined as `1`.
+fn parse_define(arg: &str) -> Result<(String, String), String> {
+    let (name, value) = arg.split_once('=').unwrap_or((arg, "1"));
+    let is_name = name.starts_with(|c: char| c == '_' || c.is_ascii_alphabetic())
+        && name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric());
+    if !is_name {
+        return Err(format!("{name:?} is not a macro name"));
+    }
+    Ok((name.to_owned(), value.to_owned()))
+}
@@ -0,0 +1,27 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::bail;
+use colored::Colorize;
+
+use crate::{decompiler, sb3::Archive};
+
+pub fn decompile(input: PathBuf, output: Option<PathBuf>) -> anyhow::Result<()> {
+    let output = output.unwrap_or_else(|| input.with_extension(""));
+    if output.exists() && (!output.is_dir() || output.read_dir()?.next().is_some()) {
+        bail!(
+            "{} already exists, and is not an empty directory",
+            output.display()
+        );
+    }
+    let archive = Archive::open(&input)?;
+    let decompiled = decompiler::decompile(&archive)?;
+    for (path, contents) in &decompiled.files {
+        let path = output.join(path);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, contents)?;
+    }
+    for warning in &decompiled.warnings {
+        eprintln!("{}: {warning}", "warning".yellow().bold());
+    }
+    Ok(())
+}
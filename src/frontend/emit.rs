@@ -0,0 +1,90 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+use serde::Serialize;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::sb3::Archive;
+
+/// The file a build is written to before it is renamed to `path`, so that a failed build
+/// never leaves a truncated file behind or clobbers the previous one.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = path.to_owned().into_os_string();
+    temp_path.push(".tmp");
+    PathBuf::from(temp_path)
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let temp_path = temp_path(path);
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+fn pretty(value: &impl Serialize) -> String {
+    let mut pretty = serde_json::to_string_pretty(value).unwrap();
+    pretty.push('\n');
+    pretty
+}
+
+pub fn json(archive: &Archive, output: &Path) -> anyhow::Result<()> {
+    write_file(output, pretty(&archive.project).as_bytes())
+}
+
+pub fn dir(archive: &Archive, output: &Path) -> anyhow::Result<()> {
+    if output.exists() && !output.join("project.json").is_file() {
+        bail!(
+            "{} already exists, and is not an unpacked project",
+            output.display()
+        );
+    }
+    let temp_path = temp_path(output);
+    if temp_path.exists() {
+        fs::remove_dir_all(&temp_path)?;
+    }
+    fs::create_dir_all(&temp_path)?;
+    fs::write(temp_path.join("project.json"), pretty(&archive.project))?;
+    for (name, contents) in &archive.assets {
+        fs::write(temp_path.join(name), contents)?;
+    }
+    if output.exists() {
+        fs::remove_dir_all(output)?;
+    }
+    fs::rename(&temp_path, output)?;
+    Ok(())
+}
+
+pub fn sprite3(archive: &Archive, output: &Path, options: SimpleFileOptions) -> anyhow::Result<()> {
+    fs::create_dir_all(output)?;
+    for target in archive
+        .project
+        .targets
+        .iter()
+        .filter(|target| !target.is_stage)
+    {
+        let name = &target.name;
+        let md5exts: BTreeSet<String> = target
+            .costumes
+            .iter()
+            .map(|costume| costume.md5ext())
+            .chain(target.sounds.iter().map(|sound| sound.md5ext()))
+            .collect();
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("sprite.json", options)?;
+        serde_json::to_writer(&mut zip, target)?;
+        for (asset, contents) in &archive.assets {
+            if md5exts.contains(asset) {
+                zip.start_file(asset.as_str(), options)?;
+                zip.write_all(contents)?;
+            }
+        }
+        let sprite3 = zip.finish()?.into_inner();
+        write_file(&output.join(format!("{name}.sprite3")), &sprite3)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::{env, fs, ops::Range, path::PathBuf};
+
+use anyhow::anyhow;
+use glob::glob;
+
+use crate::formatter::{format, FormatError};
+
+pub enum FmtError {
+    AnyhowError(anyhow::Error),
+    InvalidToken {
+        path: PathBuf,
+        src: String,
+        span: Range<usize>,
+    },
+    Unformatted(Vec<PathBuf>),
+}
+
+impl<T> From<T> for FmtError
+where T: Into<anyhow::Error>
+{
+    fn from(value: T) -> Self {
+        Self::AnyhowError(value.into())
+    }
+}
+
+pub fn fmt(paths: Vec<PathBuf>, check: bool) -> Result<(), FmtError> {
+    let paths = if paths.is_empty() {
+        vec![env::current_dir()?]
+    } else {
+        paths
+    };
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            let pattern = path.join("**").join("*.gs");
+            let pattern = pattern
+                .to_str()
+                .ok_or_else(|| anyhow!("{} is not valid UTF-8", path.display()))?;
+            for file in glob(pattern)? {
+                files.push(file?);
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    // Every file is formatted before any is written, so that an error leaves them as
+    // they were.
+    let mut changed = vec![];
+    for path in files {
+        let src = fs::read_to_string(&path)?;
+        let formatted = match format(&src) {
+            Ok(formatted) => formatted,
+            Err(FormatError::InvalidToken(span)) => {
+                return Err(FmtError::InvalidToken { path, src, span });
+            }
+            Err(FormatError::Unstable) => {
+                return Err(anyhow!(
+                    "formatting {} would change its meaning, this is a bug in goboscript",
+                    path.display()
+                )
+                .into());
+            }
+        };
+        if formatted != src {
+            changed.push((path, formatted));
+        }
+    }
+    if check {
+        if changed.is_empty() {
+            return Ok(());
+        }
+        return Err(FmtError::Unformatted(
+            changed.into_iter().map(|(path, _)| path).collect(),
+        ));
+    }
+    for (path, formatted) in changed {
+        fs::write(path, formatted)?;
+    }
+    Ok(())
+}
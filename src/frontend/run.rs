@@ -0,0 +1,25 @@
+use std::{env, io::Cursor, path::PathBuf};
+
+use super::build::{compile_project, BuildError, Compiled};
+use crate::{config::CacheMode, interpreter};
+
+pub fn run(
+    input: Option<PathBuf>,
+    no_exec: bool,
+    defines: Vec<(String, String)>,
+) -> Result<(), BuildError> {
+    let input = input.unwrap_or_else(|| env::current_dir().unwrap());
+    let Compiled {
+        project, config, ..
+    } = compile_project(
+        &input,
+        Cursor::new(Vec::new()),
+        Default::default(),
+        false,
+        no_exec,
+        CacheMode::ReadWrite,
+        &defines,
+    )?;
+    interpreter::run(&input, &project, &config)?;
+    Ok(())
+}
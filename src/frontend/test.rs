@@ -0,0 +1,91 @@
+use std::{env, io::Cursor, path::PathBuf};
+
+use annotate_snippets::{Level, Renderer};
+use anyhow::anyhow;
+use colored::Colorize;
+
+use super::build::{compile_project, BuildError, Compiled};
+use crate::{
+    config::CacheMode,
+    interpreter::{self, Failure},
+};
+
+const DEFAULT_MAX_STEPS: u64 = 10_000_000;
+
+pub fn test(
+    input: Option<PathBuf>,
+    filter: Option<String>,
+    no_exec: bool,
+    defines: Vec<(String, String)>,
+    max_steps: Option<u64>,
+) -> Result<(), BuildError> {
+    let input = input.unwrap_or_else(|| env::current_dir().unwrap());
+    let Compiled {
+        project,
+        config,
+        stage_diagnostics,
+        sprites_diagnostics,
+        ..
+    } = compile_project(
+        &input,
+        Cursor::new(Vec::new()),
+        Default::default(),
+        false,
+        no_exec,
+        CacheMode::ReadWrite,
+        &defines,
+    )?;
+    let max_steps = max_steps
+        .or(config.test_max_steps)
+        .unwrap_or(DEFAULT_MAX_STEPS);
+    let renderer = Renderer::styled();
+    let mut passed = 0;
+    let mut failed = 0;
+    let filtered_out = interpreter::test(
+        &input,
+        &project,
+        &config,
+        filter.as_deref(),
+        max_steps,
+        |outcome| {
+            let Some(failure) = &outcome.failure else {
+                println!("test {} ... {}", outcome.path(), "ok".green());
+                passed += 1;
+                return;
+            };
+            println!("test {} ... {}", outcome.path(), "FAILED".red());
+            failed += 1;
+            let sprite_diagnostics = match outcome.sprite {
+                Some(sprite) => &sprites_diagnostics[sprite],
+                None => &stage_diagnostics,
+            };
+            let (title, span) = match failure {
+                Failure::Assertion {
+                    message: Some(message),
+                    span,
+                } => (format!("assertion failed: {message}"), span),
+                Failure::Assertion {
+                    message: None,
+                    span,
+                } => ("assertion failed".to_string(), span),
+                Failure::TimedOut { steps, span } => {
+                    (format!("timed out after {steps} steps"), span)
+                }
+            };
+            sprite_diagnostics.eprint_message(&renderer, Level::Error, &title, span);
+        },
+    )?;
+    let result = if failed == 0 {
+        "ok".green()
+    } else {
+        "FAILED".red()
+    };
+    println!();
+    println!(
+        "test result: {result}. {passed} passed; {failed} failed; {filtered_out} filtered out"
+    );
+    if failed > 0 {
+        return Err(anyhow!("{failed} of {} tests failed", passed + failed).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{bail, Context};
+
+use crate::{sb3::Archive, validator};
+
+pub fn validate(input: PathBuf) -> anyhow::Result<()> {
+    let contents =
+        fs::read(&input).with_context(|| format!("could not read {}", input.display()))?;
+    let json = if input
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        contents
+    } else {
+        Archive::project_json(contents)
+            .with_context(|| format!("could not read {}", input.display()))?
+    };
+    check(&json).with_context(|| format!("{} is not a valid Scratch 3 project", input.display()))
+}
+
+pub fn check(project_json: &[u8]) -> anyhow::Result<()> {
+    let problems = validator::validate(project_json);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let problems: Vec<String> = problems.iter().map(ToString::to_string).collect();
+    bail!("{}", problems.join("\n"))
+}
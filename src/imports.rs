@@ -0,0 +1,458 @@
+use std::path::PathBuf;
+
+use fxhash::{FxHashMap, FxHashSet};
+use logos::Span;
+use smol_str::SmolStr;
+
+use crate::{
+    ast::{ConstExpr, EventKind, Expr, Import, Name, Sprite, Stmt, Type},
+    diagnostic::{Diagnostic, DiagnosticKind, SpriteDiagnostics},
+    misc::{normalize_path, Rrc},
+    parser,
+    preproc::PreProc,
+};
+
+pub fn import(
+    sprite: &mut Sprite,
+    sprite_diagnostics: &mut SpriteDiagnostics,
+    defines: &FxHashMap<String, String>,
+) {
+    let mut importer = Importer {
+        defines,
+        diagnostics: vec![],
+        stack: vec![normalize_path(&sprite_diagnostics.path)],
+    };
+    importer.import(sprite, &mut sprite_diagnostics.preproc);
+    sprite_diagnostics.diagnostics.extend(importer.diagnostics);
+}
+
+struct Importer<'a> {
+    defines: &'a FxHashMap<String, String>,
+    diagnostics: Vec<Diagnostic>,
+    stack: Vec<PathBuf>,
+}
+
+impl Importer<'_> {
+    fn import(&mut self, sprite: &mut Sprite, preproc: &mut PreProc) {
+        self.check_declarations(sprite);
+        let mut modules = vec![];
+        let mut aliases = FxHashSet::default();
+        for import in std::mem::take(&mut sprite.imports) {
+            if !aliases.insert(import.alias.clone()) {
+                self.report(
+                    DiagnosticKind::InvalidImport(format!(
+                        "module {} is already imported",
+                        import.alias
+                    )),
+                    &import.alias_span,
+                );
+                continue;
+            }
+            if let Some(module) = self.module(&import, preproc) {
+                modules.push((import.alias, module));
+            }
+        }
+        let mut private: FxHashMap<SmolStr, &SmolStr> = FxHashMap::default();
+        for (alias, module) in &modules {
+            for name in item_names(module) {
+                if !module.exports.contains(name) {
+                    private.insert(name.clone(), alias);
+                }
+            }
+        }
+        let mut diagnostics = vec![];
+        Names::new(&mut |name, span| {
+            if let Some(&module) = private.get(name) {
+                diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::PrivateName {
+                        module: module.clone(),
+                        name: name[module.len() + 2..].into(),
+                    },
+                    span: span.clone(),
+                });
+            }
+        })
+        .sprite(sprite);
+        self.diagnostics.extend(diagnostics);
+        sprite.private.extend(private.into_keys());
+        for (_, module) in modules {
+            sprite.procs.extend(module.procs);
+            sprite.funcs.extend(module.funcs);
+            sprite.structs.extend(module.structs);
+            sprite.enums.extend(module.enums);
+            sprite.vars.extend(module.vars);
+            sprite.lists.extend(module.lists);
+            sprite.events.extend(module.events);
+        }
+    }
+
+    fn module(&mut self, import: &Import, preproc: &mut PreProc) -> Option<Sprite> {
+        let span = preproc
+            .call_site(&(import.span.start - preproc.offset..import.span.end - preproc.offset));
+        let (_, include) = preproc.translate_position(span.start);
+        let directory = include.path.parent().unwrap().to_path_buf();
+        if import.path.contains(['*', '?', '[']) {
+            self.report(
+                DiagnosticKind::InvalidImport("a module cannot be a glob pattern".into()),
+                &import.span,
+            );
+            return None;
+        }
+        let Some(path) = preproc.resolve(&directory, &import.path).pop() else {
+            self.report(
+                DiagnosticKind::FileNotFound(import.path.clone()),
+                &import.span,
+            );
+            return None;
+        };
+        if let Some(index) = self.stack.iter().position(|file| *file == path) {
+            let cycle: Vec<String> = self.stack[index..]
+                .iter()
+                .chain([&path])
+                .map(|file| {
+                    file.strip_prefix(&preproc.basepath)
+                        .unwrap_or(file)
+                        .display()
+                        .to_string()
+                })
+                .collect();
+            self.report(
+                DiagnosticKind::InvalidImport(format!("import cycle: {}", cycle.join(" -> "))),
+                &import.span,
+            );
+            return None;
+        }
+        let mut module = PreProc::new(
+            preproc.basepath.clone(),
+            preproc.overlay.clone(),
+            self.defines.clone(),
+            preproc.include_paths.clone(),
+        );
+        module.offset = preproc.offset + preproc.end() + 1;
+        if module
+            .include(path.clone())
+            .and_then(|()| module.process())
+            .is_err()
+        {
+            self.report(
+                DiagnosticKind::FileNotFound(import.path.clone()),
+                &import.span,
+            );
+            return None;
+        }
+        let offset = module.offset;
+        self.diagnostics
+            .extend(module.diagnostics.drain(..).map(|diagnostic| Diagnostic {
+                span: diagnostic.span.start + offset..diagnostic.span.end + offset,
+                kind: diagnostic.kind,
+            }));
+        let mut sprite = parser::parse(&mut module).unwrap_or_else(|err| {
+            self.diagnostics.push(err);
+            Sprite::default()
+        });
+        self.stack.push(path);
+        self.import(&mut sprite, &mut module);
+        self.stack.pop();
+        preproc.append(module);
+        qualify(&mut sprite, &import.alias);
+        Some(sprite)
+    }
+
+    fn check_declarations(&mut self, sprite: &Sprite) {
+        let declarations = sprite
+            .procs
+            .values()
+            .map(|proc| (&proc.name, &proc.span))
+            .chain(sprite.funcs.values().map(|func| (&func.name, &func.span)))
+            .chain(
+                sprite
+                    .structs
+                    .values()
+                    .map(|struct_| (&struct_.name, &struct_.span)),
+            )
+            .chain(
+                sprite
+                    .enums
+                    .values()
+                    .map(|enum_| (&enum_.name, &enum_.span)),
+            )
+            .chain(sprite.vars.values().map(|var| (&var.name, &var.span)))
+            .chain(sprite.lists.values().map(|list| (&list.name, &list.span)));
+        for (name, span) in declarations {
+            if name.contains("::") {
+                self.report(
+                    DiagnosticKind::InvalidImport(format!(
+                        "{name} cannot be declared, as names with `::` are of items of \
+                         imported modules"
+                    )),
+                    span,
+                );
+            }
+        }
+    }
+
+    fn report(&mut self, kind: DiagnosticKind, span: &Span) {
+        self.diagnostics.push(Diagnostic {
+            kind,
+            span: span.clone(),
+        });
+    }
+}
+
+fn item_names(sprite: &Sprite) -> impl Iterator<Item = &SmolStr> {
+    sprite
+        .procs
+        .keys()
+        .chain(sprite.funcs.keys())
+        .chain(sprite.structs.keys())
+        .chain(sprite.enums.keys())
+        .chain(sprite.vars.keys())
+        .chain(sprite.lists.keys())
+}
+
+fn qualify(sprite: &mut Sprite, alias: &str) {
+    Names::new(&mut |name, _| *name = qualified(alias, name)).sprite(sprite);
+    qualify_items(&mut sprite.procs, alias, |proc| &mut proc.name);
+    qualify_items(&mut sprite.funcs, alias, |func| &mut func.name);
+    qualify_items(&mut sprite.structs, alias, |struct_| &mut struct_.name);
+    qualify_items(&mut sprite.enums, alias, |enum_| &mut enum_.name);
+    qualify_items(&mut sprite.vars, alias, |var| &mut var.name);
+    qualify_items(&mut sprite.lists, alias, |list| &mut list.name);
+    sprite.exports = sprite
+        .exports
+        .iter()
+        .map(|name| qualified(alias, name))
+        .collect();
+    sprite.tests.clear();
+    sprite.costumes.clear();
+}
+
+fn qualify_items<T>(
+    items: &mut FxHashMap<SmolStr, T>,
+    alias: &str,
+    name: fn(&mut T) -> &mut SmolStr,
+) {
+    *items = std::mem::take(items)
+        .into_values()
+        .map(|mut item| {
+            let name = name(&mut item);
+            *name = qualified(alias, name);
+            (name.clone(), item)
+        })
+        .collect();
+}
+
+fn qualified(alias: &str, name: &str) -> SmolStr {
+    format!("{alias}::{name}").into()
+}
+
+struct Names<'a> {
+    visit: &'a mut dyn FnMut(&mut SmolStr, &Span),
+    locals: FxHashSet<SmolStr>,
+}
+
+impl<'a> Names<'a> {
+    fn new(visit: &'a mut dyn FnMut(&mut SmolStr, &Span)) -> Self {
+        Self {
+            visit,
+            locals: FxHashSet::default(),
+        }
+    }
+
+    fn sprite(&mut self, sprite: &mut Sprite) {
+        for proc in sprite.procs.values_mut() {
+            for arg in &mut proc.args {
+                self.type_(&mut arg.type_);
+            }
+            self.body(&mut proc.body);
+        }
+        for func in sprite.funcs.values_mut() {
+            self.type_(&mut func.type_);
+            for arg in &mut func.args {
+                self.type_(&mut arg.type_);
+            }
+            self.body(&mut func.body);
+        }
+        for var in sprite.vars.values_mut() {
+            self.type_(&mut var.type_);
+            if let Some(initial_value) = &mut var.initial_value {
+                self.const_expr(initial_value);
+            }
+        }
+        for list in sprite.lists.values_mut() {
+            self.type_(&mut list.type_);
+            for item in &mut list.initial_items {
+                self.const_expr(item);
+            }
+        }
+        for event in &mut sprite.events {
+            self.locals.clear();
+            if let EventKind::OnLoudnessGt { value } | EventKind::OnTimerGt { value } = &event.kind
+            {
+                self.expr(value);
+            }
+            self.body(&mut event.body);
+        }
+        for test in &mut sprite.tests {
+            self.body(&mut test.body);
+        }
+    }
+
+    fn body(&mut self, body: &mut [Stmt]) {
+        self.locals.clear();
+        locals(body, &mut self.locals);
+        self.stmts(body);
+    }
+
+    fn stmts(&mut self, stmts: &mut [Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Repeat { times, body } => {
+                self.expr(times);
+                self.stmts(body);
+            }
+            Stmt::Forever { body, .. } => self.stmts(body),
+            Stmt::Branch {
+                cond,
+                if_body,
+                else_body,
+            } => {
+                self.expr(cond);
+                self.stmts(if_body);
+                self.stmts(else_body);
+            }
+            Stmt::Until { cond, body } => {
+                self.expr(cond);
+                self.stmts(body);
+            }
+            Stmt::SetVar {
+                name, value, type_, ..
+            } => {
+                self.name(name);
+                self.expr(value);
+                self.type_(type_);
+            }
+            Stmt::ChangeVar { name, value } | Stmt::AddToList { name, value } => {
+                self.name(name);
+                self.expr(value);
+            }
+            Stmt::Show(name) | Stmt::Hide(name) | Stmt::DeleteList(name) => self.name(name),
+            Stmt::DeleteListIndex { name, index } => {
+                self.name(name);
+                self.expr(index);
+            }
+            Stmt::InsertAtList { name, index, value }
+            | Stmt::SetListIndex { name, index, value } => {
+                self.name(name);
+                self.expr(index);
+                self.expr(value);
+            }
+            Stmt::Block { args, .. } => {
+                for arg in args {
+                    self.expr(&arg.value);
+                }
+            }
+            Stmt::ProcCall { name, span, args } => {
+                (self.visit)(name, span);
+                for arg in args {
+                    self.expr(&arg.value);
+                }
+            }
+            Stmt::FuncCall { name, span, args } => {
+                (self.visit)(name, span);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Stmt::Return { value } => self.expr(value),
+            Stmt::Assert { cond, message, .. } => {
+                self.expr(cond);
+                if let Some(message) = message {
+                    self.expr(message);
+                }
+            }
+            Stmt::SetCallSite { .. } => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Rrc<Expr>) {
+        match &mut *expr.borrow_mut() {
+            Expr::Name(name) => self.name(name),
+            Expr::Dot { lhs, .. } => self.expr(lhs),
+            Expr::Repr { args, .. } => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::FuncCall { name, span, args } => {
+                (self.visit)(name, span);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::UnOp { opr, .. } => self.expr(opr),
+            Expr::BinOp { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expr::StructLiteral { name, span, fields } => {
+                (self.visit)(name, span);
+                for field in fields {
+                    self.expr(&field.value);
+                }
+            }
+            Expr::Value { .. } | Expr::Arg(_) | Expr::CallSite { .. } => {}
+        }
+    }
+
+    fn name(&mut self, name: &mut Name) {
+        let (name, span) = match name {
+            Name::Name { name, span } => (name, span),
+            Name::DotName { lhs, lhs_span, .. } => (lhs, lhs_span),
+        };
+        if !self.locals.contains(name) {
+            (self.visit)(name, span);
+        }
+    }
+
+    fn type_(&mut self, type_: &mut Type) {
+        if let Type::Struct { name, span } = type_ {
+            (self.visit)(name, span);
+        }
+    }
+
+    fn const_expr(&mut self, const_expr: &mut ConstExpr) {
+        if let ConstExpr::StructLiteral { name, span, .. } = const_expr {
+            (self.visit)(name, span);
+        }
+    }
+}
+
+fn locals(stmts: &[Stmt], names: &mut FxHashSet<SmolStr>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::SetVar {
+                name,
+                is_local: true,
+                ..
+            } => {
+                names.insert(name.basename().clone());
+            }
+            Stmt::Repeat { body, .. } | Stmt::Forever { body, .. } | Stmt::Until { body, .. } => {
+                locals(body, names);
+            }
+            Stmt::Branch {
+                if_body, else_body, ..
+            } => {
+                locals(if_body, names);
+                locals(else_body, names);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,63 @@
+mod block;
+mod lower;
+mod machine;
+mod value;
+
+use std::path::Path;
+
+use lower::Program;
+pub use machine::Failure;
+use machine::Machine;
+use smol_str::SmolStr;
+
+use crate::{ast::Project, config::Config};
+
+pub fn run(input: &Path, project: &Project, config: &Config) -> anyhow::Result<()> {
+    let program = Program::new(input, project, config)?;
+    Machine::new(&program, config).run()?;
+    Ok(())
+}
+
+pub struct TestOutcome<'a> {
+    pub sprite: Option<&'a SmolStr>,
+    pub name: &'a SmolStr,
+    pub failure: Option<Failure>,
+}
+
+impl TestOutcome<'_> {
+    pub fn path(&self) -> String {
+        format!(
+            "{}::{}",
+            self.sprite.map_or("stage", |sprite| sprite),
+            self.name
+        )
+    }
+}
+
+pub fn test(
+    input: &Path,
+    project: &Project,
+    config: &Config,
+    filter: Option<&str>,
+    max_steps: u64,
+    mut report: impl FnMut(TestOutcome),
+) -> anyhow::Result<usize> {
+    let program = Program::new(input, project, config)?;
+    let mut filtered_out = 0;
+    for (index, sprite) in program.sprites.iter().enumerate() {
+        for test in &sprite.tests {
+            let mut outcome = TestOutcome {
+                sprite: (index != 0).then_some(&sprite.name),
+                name: &test.name,
+                failure: None,
+            };
+            if filter.is_some_and(|filter| !outcome.path().contains(filter)) {
+                filtered_out += 1;
+                continue;
+            }
+            outcome.failure = Machine::new(&program, config).test(index, test, max_steps)?;
+            report(outcome);
+        }
+    }
+    Ok(filtered_out)
+}
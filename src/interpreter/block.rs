@@ -0,0 +1,310 @@
+use std::{
+    io::{self, BufRead, Write},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    lower::Hat,
+    machine::{Flow, Machine, State, Thread},
+    value::Value,
+};
+use crate::blocks::{Block, Repr};
+
+impl Machine<'_> {
+    pub fn block(
+        &mut self,
+        thread: &mut Thread,
+        block: Block,
+        args: Vec<Value>,
+    ) -> io::Result<Flow> {
+        let target = thread.target;
+        match block {
+            Block::Say1 | Block::Think1 => {
+                print(&args[0])?;
+                Ok(Flow::Next)
+            }
+            Block::Say2 | Block::Think2 => {
+                print(&args[0])?;
+                sleep(thread, &args[1]);
+                Ok(Flow::Yield)
+            }
+            Block::Ask => {
+                print(&args[0])?;
+                let mut answer = String::new();
+                io::stdin().lock().read_line(&mut answer)?;
+                let answer = answer.strip_suffix('\n').unwrap_or(&answer);
+                self.answer = answer.strip_suffix('\r').unwrap_or(answer).into();
+                Ok(Flow::Next)
+            }
+            Block::Wait => {
+                sleep(thread, &args[0]);
+                Ok(Flow::Yield)
+            }
+            Block::WaitUntil => {
+                if args[0].to_bool() {
+                    Ok(Flow::Next)
+                } else {
+                    Ok(Flow::Retry)
+                }
+            }
+            // goboscript has no hat for receiving a broadcast, so nothing is started.
+            Block::Broadcast | Block::BroadcastAndWait => Ok(Flow::Next),
+            Block::StopAll => {
+                self.stopped = true;
+                for thread in &mut self.threads {
+                    thread.frames.clear();
+                }
+                Ok(Flow::Next)
+            }
+            Block::StopThisScript => Ok(Flow::Stop),
+            Block::StopOtherScripts => {
+                for other in &mut self.threads {
+                    if other.target == target {
+                        other.frames.clear();
+                    }
+                }
+                Ok(Flow::Next)
+            }
+            Block::DeleteThisClone => {
+                if self.targets[target].as_ref().unwrap().is_clone {
+                    self.targets[target] = None;
+                    self.clones -= 1;
+                    for other in &mut self.threads {
+                        if other.target == target {
+                            other.frames.clear();
+                        }
+                    }
+                }
+                Ok(Flow::Next)
+            }
+            Block::Clone0 => {
+                self.clone_target(target);
+                Ok(Flow::Next)
+            }
+            Block::Clone1 => {
+                let option = args[0].to_string();
+                if option == "_myself_" {
+                    self.clone_target(target);
+                } else if let Some(original) = self.targets.iter().position(|other| {
+                    other.as_ref().is_some_and(|other| {
+                        other.sprite != 0
+                            && !other.is_clone
+                            && self.program.sprites[other.sprite].name == option
+                    })
+                }) {
+                    self.clone_target(original);
+                }
+                Ok(Flow::Next)
+            }
+            Block::ResetTimer => {
+                self.timer = Instant::now();
+                Ok(Flow::Next)
+            }
+            Block::SwitchCostume => {
+                self.switch_costume(target, &args[0], false);
+                Ok(Flow::Next)
+            }
+            Block::NextCostume => {
+                self.switch_costume(target, &"next costume".into(), false);
+                Ok(Flow::Next)
+            }
+            Block::SwitchBackdrop => {
+                self.switch_backdrop(&args[0]);
+                Ok(Flow::Next)
+            }
+            Block::NextBackdrop => {
+                self.switch_backdrop(&"next backdrop".into());
+                Ok(Flow::Next)
+            }
+            _ => {
+                self.warn_unsupported(block.name(), "does nothing");
+                Ok(Flow::Next)
+            }
+        }
+    }
+
+    pub fn repr(&mut self, target: usize, repr: Repr, args: Vec<Value>) -> Value {
+        match repr {
+            Repr::CostumeNumber => self.costume(target).0,
+            Repr::CostumeName => self.costume(target).1,
+            Repr::BackdropNumber => self.costume(0).0,
+            Repr::BackdropName => self.costume(0).1,
+            Repr::Timer => self.timer.elapsed().as_secs_f64().into(),
+            Repr::Answer => Value::String(self.answer.clone()),
+            Repr::Username => "".into(),
+            Repr::Random => self.pick_random(&args[0], &args[1]),
+            Repr::DaysSince2000 => {
+                let millis = since_epoch().as_millis() as f64 - 946684800000.0;
+                (millis / 86400000.0).into()
+            }
+            Repr::CurrentYear
+            | Repr::CurrentMonth
+            | Repr::CurrentDate
+            | Repr::CurrentDayOfWeek
+            | Repr::CurrentHour
+            | Repr::CurrentMinute
+            | Repr::CurrentSecond => current(repr).into(),
+            _ => {
+                let value = match repr {
+                    Repr::Direction => Value::Number(90.0),
+                    Repr::Size | Repr::Volume => Value::Number(100.0),
+                    Repr::Loudness => Value::Number(-1.0),
+                    Repr::TouchingMousePointer
+                    | Repr::TouchingEdge
+                    | Repr::Touching
+                    | Repr::KeyPressed
+                    | Repr::MouseDown
+                    | Repr::TouchingColor
+                    | Repr::ColorIsTouchingColor => Value::Bool(false),
+                    _ => Value::Number(0.0),
+                };
+                self.warn_unsupported(repr.name(), &format!("always reports {value}"));
+                value
+            }
+        }
+    }
+
+    fn clone_target(&mut self, original: usize) {
+        if original == 0 || self.clones as f64 >= self.max_clones {
+            return;
+        }
+        let mut clone = self.targets[original].clone().unwrap();
+        clone.is_clone = true;
+        self.targets.push(Some(clone));
+        self.clones += 1;
+        self.start_hats(
+            |hat| matches!(hat, Hat::Clone),
+            Some(self.targets.len() - 1),
+        );
+    }
+
+    fn costume(&self, target: usize) -> (Value, Value) {
+        let target = self.targets[target].as_ref().unwrap();
+        let name = self.program.sprites[target.sprite]
+            .costumes
+            .get(target.costume)
+            .cloned()
+            .unwrap_or_default();
+        ((target.costume as f64 + 1.0).into(), Value::String(name))
+    }
+
+    fn switch_costume(&mut self, target: usize, costume: &Value, backdrop: bool) {
+        let random = self.random();
+        let target = self.targets[target].as_mut().unwrap();
+        let costumes = &self.program.sprites[target.sprite].costumes;
+        if costumes.is_empty() {
+            return;
+        }
+        let (next, previous) = if backdrop {
+            ("next backdrop", "previous backdrop")
+        } else {
+            ("next costume", "previous costume")
+        };
+        let current = target.costume as f64;
+        let index = match costume {
+            Value::Number(number) => number - 1.0,
+            _ => {
+                let name = costume.to_string();
+                if let Some(index) = costumes.iter().position(|costume| *costume == name) {
+                    index as f64
+                } else if name == next {
+                    current + 1.0
+                } else if name == previous {
+                    current - 1.0
+                } else if backdrop && name == "random backdrop" {
+                    if costumes.len() < 2 {
+                        return;
+                    }
+                    let index = (random * (costumes.len() - 1) as f64).floor();
+                    if index == current {
+                        index + 1.0
+                    } else {
+                        index
+                    }
+                } else if costume.js_number().is_nan() || costume.is_whitespace() {
+                    return;
+                } else {
+                    costume.js_number() - 1.0
+                }
+            }
+        };
+        let index = (index + 0.5).floor();
+        let index = if index.is_finite() { index } else { 0.0 };
+        let count = costumes.len() as f64;
+        target.costume = (index - (index / count).floor() * count) as usize;
+    }
+
+    fn switch_backdrop(&mut self, backdrop: &Value) {
+        self.switch_costume(0, backdrop, true);
+        let Value::String(name) = self.costume(0).1 else {
+            unreachable!()
+        };
+        let name = name.to_lowercase();
+        self.start_hats(
+            |hat| matches!(hat, Hat::Backdrop(backdrop) if *backdrop == name),
+            None,
+        );
+    }
+
+    fn pick_random(&mut self, from: &Value, to: &Value) -> Value {
+        let (from_number, to_number) = (from.to_number(), to.to_number());
+        let low = from_number.min(to_number);
+        let high = from_number.max(to_number);
+        if low == high {
+            return low.into();
+        }
+        if from.is_int() && to.is_int() {
+            return (low + (self.random() * (high + 1.0 - low)).floor()).into();
+        }
+        (self.random() * (high - low) + low).into()
+    }
+}
+
+fn print(message: &Value) -> io::Result<()> {
+    let message = message.to_string();
+    if message.is_empty() {
+        return Ok(());
+    }
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{message}")?;
+    stdout.flush()
+}
+
+fn sleep(thread: &mut Thread, seconds: &Value) {
+    let seconds = seconds.to_number().max(0.0).min(u32::MAX as f64);
+    thread.state = State::Sleeping(Instant::now() + Duration::from_secs_f64(seconds));
+}
+
+fn since_epoch() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+fn current(repr: Repr) -> f64 {
+    let seconds = since_epoch().as_secs() as i64;
+    let days = seconds.div_euclid(86400);
+    let time = seconds.rem_euclid(86400);
+    // Converts days since 1970-01-01 to a date in the proleptic Gregorian calendar, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let date = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (match repr {
+        Repr::CurrentYear => year,
+        Repr::CurrentMonth => month,
+        Repr::CurrentDate => date,
+        // 1970-01-01 was a Thursday, and Sunday is 1.
+        Repr::CurrentDayOfWeek => (days + 4).rem_euclid(7) + 1,
+        Repr::CurrentHour => time / 3600,
+        Repr::CurrentMinute => time / 60 % 60,
+        Repr::CurrentSecond => time % 60,
+        _ => unreachable!(),
+    }) as f64
+}
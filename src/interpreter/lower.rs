@@ -0,0 +1,709 @@
+use std::{path::Path, rc::Rc};
+
+use anyhow::anyhow;
+use fxhash::FxHashMap;
+use logos::Span;
+use smol_str::SmolStr;
+
+use super::value::Value;
+use crate::{
+    ast::*,
+    blocks::{BinOp, Block, Repr, UnOp},
+    codegen::{
+        cmd::cmd_to_list,
+        const_expr::const_values,
+        sb3::{qualify_local_var_name, qualify_struct_var_name},
+    },
+    config::Config,
+    misc::Rrc,
+};
+
+#[derive(Debug, Copy, Clone)]
+pub enum Scope {
+    Own,
+    Stage,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Ref {
+    pub scope: Scope,
+    pub index: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Layout {
+    pub names: Vec<SmolStr>,
+    indices: FxHashMap<SmolStr, usize>,
+}
+
+impl Layout {
+    fn intern(&mut self, name: SmolStr) -> usize {
+        if let Some(&index) = self.indices.get(&name) {
+            return index;
+        }
+        self.names.push(name.clone());
+        self.indices.insert(name, self.names.len() - 1);
+        self.names.len() - 1
+    }
+}
+
+#[derive(Debug)]
+pub enum X {
+    Value(Value),
+    Var(Ref),
+    List(Ref),
+    ListItem(Ref, Box<X>),
+    ListLength(Ref),
+    Arg(Option<usize>),
+    Repr(Repr, Vec<X>),
+    UnOp(UnOp, Box<X>),
+    BinOp(BinOp, Box<X>, Box<X>),
+}
+
+#[derive(Debug)]
+pub enum Op {
+    SetVar(Ref, X),
+    ChangeVar(Ref, X),
+    AddToList(Ref, X),
+    DeleteList(Ref),
+    DeleteListIndex(Ref, X),
+    InsertAtList(Ref, X, X),
+    SetListIndex(Ref, X, X),
+    Jump(usize),
+    JumpIf(X, usize),
+    JumpIfNot(X, usize),
+    RepeatStart(X),
+    RepeatNext(usize),
+    Yield,
+    Call(usize, Vec<X>),
+    Block(Block, Vec<X>),
+    Assert(X, Option<X>, Span),
+}
+
+#[derive(Debug)]
+pub enum Hat {
+    Flag,
+    Clone,
+    Backdrop(SmolStr),
+    Never,
+    Test,
+}
+
+#[derive(Debug)]
+pub struct Script {
+    pub hat: Hat,
+    pub code: Rc<Vec<Op>>,
+}
+
+#[derive(Debug)]
+pub struct Procedure {
+    pub code: Rc<Vec<Op>>,
+    pub warp: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct SpriteCode {
+    pub name: SmolStr,
+    pub costumes: Vec<SmolStr>,
+    pub vars: Layout,
+    pub lists: Layout,
+    pub var_data: FxHashMap<usize, Value>,
+    pub list_data: FxHashMap<usize, Vec<Value>>,
+    pub procedures: Vec<Procedure>,
+    pub scripts: Vec<Script>,
+    pub tests: Vec<TestCase>,
+}
+
+#[derive(Debug)]
+pub struct TestCase {
+    pub name: SmolStr,
+    pub span: Span,
+    pub script: usize,
+}
+
+#[derive(Debug)]
+pub struct Program {
+    pub sprites: Vec<SpriteCode>,
+}
+
+impl Program {
+    pub fn new(input: &Path, project: &Project, config: &Config) -> anyhow::Result<Self> {
+        let mut stage = SpriteCode {
+            name: "Stage".into(),
+            ..Default::default()
+        };
+        lower_sprite(input, config, &project.stage, &mut stage, None)?;
+        let mut sprite_names: Vec<&SmolStr> = project.sprites.keys().collect();
+        sprite_names.sort();
+        let mut sprites = vec![];
+        for sprite_name in sprite_names {
+            let mut code = SpriteCode {
+                name: sprite_name.clone(),
+                ..Default::default()
+            };
+            lower_sprite(
+                input,
+                config,
+                &project.sprites[sprite_name],
+                &mut code,
+                Some((&project.stage, &mut stage)),
+            )?;
+            sprites.push(code);
+        }
+        sprites.insert(0, stage);
+        Ok(Self { sprites })
+    }
+}
+
+fn lower_sprite<'a>(
+    input: &Path,
+    config: &Config,
+    sprite: &'a Sprite,
+    code: &mut SpriteCode,
+    mut stage: Option<(&'a Sprite, &mut SpriteCode)>,
+) -> anyhow::Result<()> {
+    code.costumes = sprite
+        .costumes
+        .iter()
+        .map(|costume| costume.name.clone())
+        .collect();
+    for var in sprite.vars.values() {
+        lower_var(sprite, var, code);
+    }
+    for list in sprite.lists.values() {
+        lower_list(input, config, sprite, list, code)?;
+    }
+    // Procedures and functions are numbered before any code is lowered, so that they
+    // can call each other.
+    let procs: FxHashMap<SmolStr, usize> = sprite
+        .procs
+        .keys()
+        .chain(sprite.funcs.keys())
+        .enumerate()
+        .map(|(index, name)| (name.clone(), index))
+        .collect();
+    let mut procedures = vec![];
+    for proc in sprite.procs.values() {
+        let mut l = L::new(sprite, code, stage.as_mut(), &procs);
+        l.callable = Some(Callable::new(
+            sprite,
+            proc.name.clone(),
+            &proc.args,
+            &proc.locals,
+        ));
+        l.stmts(&proc.body);
+        procedures.push(Procedure {
+            code: l.ops.into(),
+            warp: proc.warp,
+        });
+    }
+    for func in sprite.funcs.values() {
+        let mut l = L::new(sprite, code, stage.as_mut(), &procs);
+        l.callable = Some(Callable::new(
+            sprite,
+            func.name.clone(),
+            &func.args,
+            &func.locals,
+        ));
+        l.stmts(&func.body);
+        procedures.push(Procedure {
+            code: l.ops.into(),
+            warp: true,
+        });
+    }
+    let mut scripts = vec![];
+    for event in &sprite.events {
+        let hat = match &event.kind {
+            EventKind::OnFlag => Hat::Flag,
+            EventKind::OnClone => Hat::Clone,
+            EventKind::OnBackdrop { backdrop, .. } => Hat::Backdrop(backdrop.to_lowercase().into()),
+            EventKind::OnKey { .. }
+            | EventKind::OnClick
+            | EventKind::OnLoudnessGt { .. }
+            | EventKind::OnTimerGt { .. } => Hat::Never,
+        };
+        let mut l = L::new(sprite, code, stage.as_mut(), &procs);
+        l.stmts(&event.body);
+        scripts.push(Script {
+            hat,
+            code: l.ops.into(),
+        });
+    }
+    let mut tests = vec![];
+    for test in &sprite.tests {
+        let mut l = L::new(sprite, code, stage.as_mut(), &procs);
+        // Test names are strings, so they cannot clash with the name of a procedure.
+        let name = format!("test {}", test.name).into();
+        l.callable = Some(Callable::new(sprite, name, &[], &test.locals));
+        l.stmts(&test.body);
+        tests.push(TestCase {
+            name: test.name.clone(),
+            span: test.span.clone(),
+            script: scripts.len(),
+        });
+        scripts.push(Script {
+            hat: Hat::Test,
+            code: l.ops.into(),
+        });
+    }
+    code.procedures = procedures;
+    code.scripts = scripts;
+    code.tests = tests;
+    Ok(())
+}
+
+fn lower_var(sprite: &Sprite, var: &Var, code: &mut SpriteCode) {
+    let Some(initial_value) = &var.initial_value else {
+        return;
+    };
+    let Ok(values) = const_values(sprite, &var.type_, initial_value) else {
+        return;
+    };
+    let names: Vec<SmolStr> = match var.type_.struct_() {
+        Some((type_name, _)) => sprite.structs[type_name]
+            .fields
+            .iter()
+            .map(|field| qualify_struct_var_name(&field.name, &var.name))
+            .collect(),
+        None => vec![var.name.clone()],
+    };
+    for (name, value) in names.into_iter().zip(&values) {
+        let index = code.vars.intern(name);
+        code.var_data.insert(index, value.into());
+    }
+}
+
+fn lower_list(
+    input: &Path,
+    config: &Config,
+    sprite: &Sprite,
+    list: &List,
+    code: &mut SpriteCode,
+) -> anyhow::Result<()> {
+    let struct_ = list
+        .type_
+        .struct_()
+        .and_then(|(type_name, _)| sprite.structs.get(type_name));
+    let data: Option<Vec<Value>> = match &list.cmd {
+        Some(cmd) => Some(
+            cmd_to_list(cmd, input, struct_, config)
+                .map_err(|_| anyhow!("failed to load the contents of list {}", list.name))?
+                .into_iter()
+                .map(Value::from)
+                .collect(),
+        ),
+        None if list.initial_items.is_empty() => None,
+        None => Some(
+            list.initial_items
+                .iter()
+                .filter_map(|item| const_values(sprite, &list.type_, item).ok())
+                .flatten()
+                .map(|value| Value::from(&value))
+                .collect(),
+        ),
+    };
+    let Some(struct_) = struct_ else {
+        let index = code.lists.intern(list.name.clone());
+        if let Some(data) = data {
+            code.list_data.insert(index, data);
+        }
+        return Ok(());
+    };
+    // Struct lists are stored as one list per field, and their contents are given row
+    // by row.
+    let width = struct_.fields.len();
+    for (i, field) in struct_.fields.iter().enumerate() {
+        let index = code
+            .lists
+            .intern(qualify_struct_var_name(&field.name, &list.name));
+        if let Some(data) = &data {
+            let column = data
+                .iter()
+                .skip(i)
+                .step_by(width)
+                .take(data.len() / width)
+                .cloned()
+                .collect();
+            code.list_data.insert(index, column);
+        }
+    }
+    Ok(())
+}
+
+struct Callable<'a> {
+    name: SmolStr,
+    args: Vec<SmolStr>,
+    locals: &'a FxHashMap<SmolStr, Var>,
+}
+
+impl<'a> Callable<'a> {
+    fn new(
+        sprite: &Sprite,
+        name: SmolStr,
+        args: &[Arg],
+        locals: &'a FxHashMap<SmolStr, Var>,
+    ) -> Self {
+        let args = args
+            .iter()
+            .flat_map(|arg| match arg.type_.struct_() {
+                Some((type_name, _)) => sprite
+                    .structs
+                    .get(type_name)
+                    .map(|struct_| {
+                        struct_
+                            .fields
+                            .iter()
+                            .map(|field| qualify_struct_var_name(&field.name, &arg.name))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                None => vec![arg.name.clone()],
+            })
+            .collect();
+        Self { name, args, locals }
+    }
+}
+
+struct L<'a, 'b> {
+    sprite: &'a Sprite,
+    code: &'b mut SpriteCode,
+    stage: Option<(&'a Sprite, &'b mut SpriteCode)>,
+    procs: &'b FxHashMap<SmolStr, usize>,
+    callable: Option<Callable<'a>>,
+    ops: Vec<Op>,
+}
+
+impl<'a, 'b> L<'a, 'b> {
+    fn new(
+        sprite: &'a Sprite,
+        code: &'b mut SpriteCode,
+        stage: Option<&'b mut (&'a Sprite, &mut SpriteCode)>,
+        procs: &'b FxHashMap<SmolStr, usize>,
+    ) -> Self {
+        Self {
+            sprite,
+            code,
+            stage: stage.map(|(stage, stage_code)| (*stage, &mut **stage_code)),
+            procs,
+            callable: None,
+            ops: vec![],
+        }
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+
+    fn patch(&mut self, at: usize) {
+        let target = self.ops.len();
+        match &mut self.ops[at] {
+            Op::Jump(to) | Op::JumpIf(_, to) | Op::JumpIfNot(_, to) | Op::RepeatNext(to) => {
+                *to = target
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn var(&mut self, name: SmolStr) -> Ref {
+        Ref {
+            scope: Scope::Own,
+            index: self.code.vars.intern(name),
+        }
+    }
+
+    /// In the same order as codegen: lists, then locals, then variables of the sprite, then
+    /// variables of the Stage.
+    fn resolve(&mut self, name: &Name) -> (Ref, bool) {
+        let basename = name.basename();
+        let qualified = match name.fieldname() {
+            Some(field) => qualify_struct_var_name(field, basename),
+            None => basename.clone(),
+        };
+        if self.sprite.lists.contains_key(basename) {
+            let index = self.code.lists.intern(qualified);
+            return (
+                Ref {
+                    scope: Scope::Own,
+                    index,
+                },
+                true,
+            );
+        }
+        if let Some((stage, stage_code)) = &mut self.stage {
+            if stage.lists.contains_key(basename) {
+                let index = stage_code.lists.intern(qualified);
+                return (
+                    Ref {
+                        scope: Scope::Stage,
+                        index,
+                    },
+                    true,
+                );
+            }
+        }
+        if let Some(callable) = &self.callable {
+            if callable.locals.contains_key(basename) {
+                let name = qualify_local_var_name(&callable.name, &qualified);
+                return (self.var(name), false);
+            }
+        }
+        if !self.sprite.vars.contains_key(basename) {
+            if let Some((stage, stage_code)) = &mut self.stage {
+                if stage.vars.contains_key(basename) {
+                    let index = stage_code.vars.intern(qualified);
+                    return (
+                        Ref {
+                            scope: Scope::Stage,
+                            index,
+                        },
+                        false,
+                    );
+                }
+            }
+        }
+        (self.var(qualified), false)
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Repeat { times, body } => {
+                let times = self.expr(&times.borrow());
+                self.emit(Op::RepeatStart(times));
+                let start = self.emit(Op::RepeatNext(0));
+                self.stmts(body);
+                self.emit(Op::Yield);
+                self.emit(Op::Jump(start));
+                self.patch(start);
+            }
+            Stmt::Forever { body, .. } => {
+                let start = self.ops.len();
+                self.stmts(body);
+                self.emit(Op::Yield);
+                self.emit(Op::Jump(start));
+            }
+            Stmt::Branch {
+                cond,
+                if_body,
+                else_body,
+            } => {
+                let cond = self.expr(&cond.borrow());
+                let jump_to_else = self.emit(Op::JumpIfNot(cond, 0));
+                self.stmts(if_body);
+                if else_body.is_empty() {
+                    self.patch(jump_to_else);
+                } else {
+                    let jump_to_end = self.emit(Op::Jump(0));
+                    self.patch(jump_to_else);
+                    self.stmts(else_body);
+                    self.patch(jump_to_end);
+                }
+            }
+            Stmt::Until { cond, body } => {
+                let start = self.ops.len();
+                let cond = self.expr(&cond.borrow());
+                let jump_to_end = self.emit(Op::JumpIf(cond, 0));
+                self.stmts(body);
+                self.emit(Op::Yield);
+                self.emit(Op::Jump(start));
+                self.patch(jump_to_end);
+            }
+            Stmt::SetVar { name, value, .. } => {
+                let (var, _) = self.resolve(name);
+                let value = self.expr(&value.borrow());
+                self.emit(Op::SetVar(var, value));
+            }
+            Stmt::SetCallSite { id, func } => {
+                let call_site = self.var(format!("c{id}").into());
+                let func = self.var(func.clone());
+                self.emit(Op::SetVar(call_site, X::Var(func)));
+            }
+            Stmt::ChangeVar { name, value } => {
+                let (var, _) = self.resolve(name);
+                let value = self.expr(&value.borrow());
+                self.emit(Op::ChangeVar(var, value));
+            }
+            // There are no monitors to show or hide.
+            Stmt::Show(_) | Stmt::Hide(_) => {}
+            Stmt::AddToList { name, value } => {
+                let (list, _) = self.resolve(name);
+                let value = self.expr(&value.borrow());
+                self.emit(Op::AddToList(list, value));
+            }
+            Stmt::DeleteList(name) => {
+                let (list, _) = self.resolve(name);
+                self.emit(Op::DeleteList(list));
+            }
+            Stmt::DeleteListIndex { name, index } => {
+                let (list, _) = self.resolve(name);
+                let index = self.expr(&index.borrow());
+                self.emit(Op::DeleteListIndex(list, index));
+            }
+            Stmt::InsertAtList { name, index, value } => {
+                let (list, _) = self.resolve(name);
+                let index = self.expr(&index.borrow());
+                let value = self.expr(&value.borrow());
+                self.emit(Op::InsertAtList(list, index, value));
+            }
+            Stmt::SetListIndex { name, index, value } => {
+                let (list, _) = self.resolve(name);
+                let index = self.expr(&index.borrow());
+                let value = self.expr(&value.borrow());
+                self.emit(Op::SetListIndex(list, index, value));
+            }
+            Stmt::Block { block, args, .. } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.expr(&arg.value.borrow()))
+                    .collect();
+                self.emit(Op::Block(*block, args));
+            }
+            Stmt::ProcCall { name, args, .. } => {
+                let Some(proc) = self.sprite.procs.get(name) else {
+                    return;
+                };
+                let args: Vec<_> = args.iter().map(|arg| arg.value.clone()).collect();
+                let args = self.call_args(&proc.args, &args);
+                self.emit(Op::Call(self.procs[name], args));
+            }
+            Stmt::FuncCall { name, args, .. } => {
+                let Some(func) = self.sprite.funcs.get(name) else {
+                    return;
+                };
+                let args = self.call_args(&func.args, args);
+                self.emit(Op::Call(self.procs[name], args));
+            }
+            Stmt::Return { value } => {
+                let func = self.var(self.callable.as_ref().unwrap().name.clone());
+                let value = self.expr(&value.borrow());
+                self.emit(Op::SetVar(func, value));
+            }
+            Stmt::Assert {
+                cond,
+                message,
+                span,
+            } => {
+                let cond = self.expr(&cond.borrow());
+                let message = message.as_ref().map(|message| self.expr(&message.borrow()));
+                self.emit(Op::Assert(cond, message, span.clone()));
+            }
+        }
+    }
+
+    fn call_args(&mut self, params: &[Arg], args: &[Rrc<Expr>]) -> Vec<X> {
+        let mut lowered = vec![];
+        for (param, arg) in params.iter().zip(args) {
+            if param.type_.is_value() {
+                lowered.push(self.expr(&arg.borrow()));
+                continue;
+            }
+            if let Expr::StructLiteral { fields, .. } = &*arg.borrow() {
+                for field in fields {
+                    lowered.push(self.expr(&field.value.borrow()));
+                }
+            }
+        }
+        lowered
+    }
+
+    fn expr(&mut self, expr: &Expr) -> X {
+        match expr {
+            Expr::Value { value, .. } => X::Value(value.into()),
+            Expr::Name(name) => match self.resolve(name) {
+                (list, true) => X::List(list),
+                (var, false) => X::Var(var),
+            },
+            Expr::CallSite { id } => X::Var(self.var(format!("c{id}").into())),
+            Expr::Dot { lhs, rhs, .. } => {
+                // Struct fields are resolved by pass1, so only enum variants are left.
+                if let Expr::Name(name) = &*lhs.borrow() {
+                    let enum_ = self.sprite.enums.get(name.basename()).or_else(|| {
+                        self.stage
+                            .as_ref()
+                            .and_then(|(stage, _)| stage.enums.get(name.basename()))
+                    });
+                    let variant = enum_.and_then(|enum_| {
+                        enum_.variants.iter().find(|variant| &variant.name == rhs)
+                    });
+                    if let Some((value, _)) = variant.and_then(|variant| variant.value.as_ref()) {
+                        return X::Value(value.into());
+                    }
+                }
+                X::Value(Value::default())
+            }
+            Expr::Arg(name) => {
+                let qualified = match name.fieldname() {
+                    Some(field) => qualify_struct_var_name(field, name.basename()),
+                    None => name.basename().clone(),
+                };
+                X::Arg(
+                    self.callable.as_ref().and_then(|callable| {
+                        callable.args.iter().position(|arg| *arg == qualified)
+                    }),
+                )
+            }
+            Expr::Repr { repr, args, .. } => X::Repr(
+                *repr,
+                args.iter().map(|arg| self.expr(&arg.borrow())).collect(),
+            ),
+            Expr::FuncCall { name, .. } => unreachable!("attempted to run {name:#?}"),
+            Expr::UnOp { op, opr, .. } => {
+                if let (UnOp::Length, Expr::Name(Name::Name { name, .. })) = (op, &*opr.borrow()) {
+                    if let Some(list) = self.list_length(name) {
+                        return X::ListLength(list);
+                    }
+                }
+                X::UnOp(*op, self.expr(&opr.borrow()).into())
+            }
+            Expr::BinOp { op, lhs, rhs, .. } => {
+                if let (BinOp::Of, Expr::Name(name)) = (op, &*lhs.borrow()) {
+                    if let (list, true) = self.resolve(name) {
+                        return X::ListItem(list, self.expr(&rhs.borrow()).into());
+                    }
+                }
+                X::BinOp(
+                    *op,
+                    self.expr(&lhs.borrow()).into(),
+                    self.expr(&rhs.borrow()).into(),
+                )
+            }
+            // Codegen reports struct literals used as values.
+            Expr::StructLiteral { .. } => X::Value(Value::default()),
+        }
+    }
+
+    /// The length of a struct list is the length of the list of its first field.
+    fn list_length(&mut self, name: &SmolStr) -> Option<Ref> {
+        let (sprite, list) = match self.sprite.lists.get(name) {
+            Some(list) => (self.sprite, list),
+            None => {
+                let (stage, _) = self.stage.as_ref()?;
+                (*stage, stage.lists.get(name)?)
+            }
+        };
+        let field = list
+            .type_
+            .struct_()
+            .and_then(|(type_name, _)| sprite.structs.get(type_name))
+            .and_then(|struct_| struct_.fields.first());
+        let name = match field {
+            Some(field) => Name::DotName {
+                lhs: name.clone(),
+                lhs_span: 0..0,
+                rhs: field.name.clone(),
+                rhs_span: 0..0,
+            },
+            None => Name::Name {
+                name: name.clone(),
+                span: 0..0,
+            },
+        };
+        Some(self.resolve(&name).0)
+    }
+}
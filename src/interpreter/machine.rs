@@ -0,0 +1,644 @@
+use std::{
+    f64::consts::PI,
+    io, mem,
+    rc::Rc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use colored::Colorize;
+use fxhash::FxHashSet;
+use logos::Span;
+use smol_str::SmolStr;
+
+use super::{
+    lower::{Hat, Op, Program, Ref, Scope, TestCase, X},
+    value::Value,
+};
+use crate::{
+    blocks::{BinOp, UnOp},
+    config::Config,
+};
+
+const LIST_ITEM_LIMIT: usize = 200000;
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub sprite: usize,
+    pub vars: Vec<Value>,
+    pub lists: Vec<Vec<Value>>,
+    pub costume: usize,
+    pub is_clone: bool,
+}
+
+#[derive(Debug)]
+pub struct Frame {
+    pub code: Rc<Vec<Op>>,
+    pub pc: usize,
+    pub args: Vec<Value>,
+    pub warp: bool,
+    pub loops: Vec<f64>,
+}
+
+impl Frame {
+    fn new(code: Rc<Vec<Op>>, args: Vec<Value>, warp: bool) -> Self {
+        Self {
+            code,
+            pc: 0,
+            args,
+            warp,
+            loops: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub enum State {
+    #[default]
+    Running,
+    Sleeping(Instant),
+}
+
+#[derive(Debug, Default)]
+pub struct Thread {
+    pub id: u64,
+    pub target: usize,
+    pub script: usize,
+    pub frames: Vec<Frame>,
+    pub state: State,
+}
+
+pub enum Flow {
+    Next,
+    Yield,
+    Retry,
+    Stop,
+}
+
+#[derive(Debug)]
+pub enum Failure {
+    Assertion { message: Option<String>, span: Span },
+    TimedOut { steps: u64, span: Span },
+}
+
+pub struct Machine<'a> {
+    pub program: &'a Program,
+    /// Deleted clones are `None`, so that the index of a target never changes.
+    pub targets: Vec<Option<Target>>,
+    pub threads: Vec<Thread>,
+    next_thread_id: u64,
+    running: Option<(usize, usize, u64)>,
+    restart_running: bool,
+    pub stopped: bool,
+    pub clones: usize,
+    pub max_clones: f64,
+    pub answer: SmolStr,
+    pub timer: Instant,
+    rng: u64,
+    warned: FxHashSet<&'static str>,
+    pub failure: Option<Failure>,
+    steps_left: Option<u64>,
+    /// `goboscript run` skips asserts, as the compiled project does not have them.
+    testing: bool,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(program: &'a Program, config: &Config) -> Self {
+        let targets = program
+            .sprites
+            .iter()
+            .enumerate()
+            .map(|(index, sprite)| {
+                let mut lists = vec![vec![]; sprite.lists.names.len()];
+                for (&list, data) in &sprite.list_data {
+                    lists[list] = data.clone();
+                }
+                let mut vars = vec![Value::default(); sprite.vars.names.len()];
+                for (&var, value) in &sprite.var_data {
+                    vars[var] = value.clone();
+                }
+                Some(Target {
+                    sprite: index,
+                    vars,
+                    lists,
+                    costume: 0,
+                    is_clone: false,
+                })
+            })
+            .collect();
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        Self {
+            program,
+            targets,
+            threads: vec![],
+            next_thread_id: 0,
+            running: None,
+            restart_running: false,
+            stopped: false,
+            clones: 0,
+            max_clones: config.max_clones.unwrap_or(300.0),
+            answer: SmolStr::default(),
+            timer: Instant::now(),
+            rng: seed | 1,
+            warned: FxHashSet::default(),
+            failure: None,
+            steps_left: None,
+            testing: false,
+        }
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        self.start_hats(|hat| matches!(hat, Hat::Flag), None);
+        while !self.threads.is_empty() {
+            self.tick()?;
+            self.threads.retain(|thread| !thread.frames.is_empty());
+            self.sleep();
+        }
+        Ok(())
+    }
+
+    /// Scripts which the test started, such as clones, are not waited for.
+    pub fn test(
+        &mut self,
+        sprite: usize,
+        test: &TestCase,
+        max_steps: u64,
+    ) -> io::Result<Option<Failure>> {
+        self.steps_left = Some(max_steps);
+        self.testing = true;
+        let id = self.spawn(sprite, test.script);
+        while !self.stopped && self.is_running(id) {
+            self.tick()?;
+            self.threads.retain(|thread| !thread.frames.is_empty());
+            self.sleep();
+        }
+        if self.stopped && self.failure.is_none() && self.steps_left == Some(0) {
+            return Ok(Some(Failure::TimedOut {
+                steps: max_steps,
+                span: test.span.clone(),
+            }));
+        }
+        Ok(self.failure.take())
+    }
+
+    fn tick(&mut self) -> io::Result<()> {
+        // Threads started during the tick are added to the end, and run in the same tick.
+        let mut i = 0;
+        while i < self.threads.len() {
+            let mut thread = mem::take(&mut self.threads[i]);
+            let result = self.step(&mut thread);
+            self.threads[i] = thread;
+            result?;
+            i += 1;
+        }
+        Ok(())
+    }
+
+    fn sleep(&self) {
+        let mut wake = None;
+        for thread in &self.threads {
+            match &thread.state {
+                State::Running => return,
+                State::Sleeping(until) => {
+                    wake = Some(wake.map_or(*until, |wake: Instant| wake.min(*until)))
+                }
+            }
+        }
+        if let Some(wake) = wake {
+            std::thread::sleep(wake.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    fn is_running(&self, id: u64) -> bool {
+        self.threads
+            .iter()
+            .any(|thread| thread.id == id && !thread.frames.is_empty())
+    }
+
+    pub fn start_hats<F>(&mut self, filter: F, target: Option<usize>) -> Vec<u64>
+    where F: Fn(&Hat) -> bool {
+        let program = self.program;
+        let mut ids = vec![];
+        for target_index in 0..self.targets.len() {
+            if target.is_some_and(|target| target != target_index) {
+                continue;
+            }
+            let Some(sprite) = self.targets[target_index].as_ref().map(|t| t.sprite) else {
+                continue;
+            };
+            for (script_index, script) in program.sprites[sprite].scripts.iter().enumerate() {
+                if !filter(&script.hat) {
+                    continue;
+                }
+                if let Some((running_target, running_script, id)) = self.running {
+                    if (running_target, running_script) == (target_index, script_index) {
+                        self.restart_running = true;
+                        ids.push(id);
+                        continue;
+                    }
+                }
+                let frame = Frame::new(script.code.clone(), vec![], false);
+                if let Some(thread) = self.threads.iter_mut().find(|thread| {
+                    thread.target == target_index
+                        && thread.script == script_index
+                        && !thread.frames.is_empty()
+                }) {
+                    thread.frames = vec![frame];
+                    thread.state = State::Running;
+                    ids.push(thread.id);
+                    continue;
+                }
+                ids.push(self.spawn(target_index, script_index));
+            }
+        }
+        ids
+    }
+
+    fn spawn(&mut self, target: usize, script: usize) -> u64 {
+        let sprite = self.targets[target].as_ref().unwrap().sprite;
+        let code = self.program.sprites[sprite].scripts[script].code.clone();
+        self.next_thread_id += 1;
+        self.threads.push(Thread {
+            id: self.next_thread_id,
+            target,
+            script,
+            frames: vec![Frame::new(code, vec![], false)],
+            state: State::Running,
+        });
+        self.next_thread_id
+    }
+
+    fn step(&mut self, thread: &mut Thread) -> io::Result<()> {
+        match &thread.state {
+            State::Running => {}
+            State::Sleeping(until) => {
+                if Instant::now() < *until {
+                    return Ok(());
+                }
+            }
+        }
+        thread.state = State::Running;
+        self.running = Some((thread.target, thread.script, thread.id));
+        self.restart_running = false;
+        let result = self.run_thread(thread);
+        self.running = None;
+        if self.restart_running && !thread.frames.is_empty() {
+            let sprite = self.targets[thread.target].as_ref().unwrap().sprite;
+            let code = self.program.sprites[sprite].scripts[thread.script]
+                .code
+                .clone();
+            thread.frames = vec![Frame::new(code, vec![], false)];
+            thread.state = State::Running;
+        }
+        result
+    }
+
+    fn run_thread(&mut self, thread: &mut Thread) -> io::Result<()> {
+        let target = thread.target;
+        loop {
+            if self.stopped || self.restart_running || self.targets[target].is_none() {
+                if !self.restart_running {
+                    thread.frames.clear();
+                }
+                return Ok(());
+            }
+            if let Some(steps_left) = &mut self.steps_left {
+                if *steps_left == 0 {
+                    self.stopped = true;
+                    continue;
+                }
+                *steps_left -= 1;
+            }
+            let Some(frame) = thread.frames.last_mut() else {
+                return Ok(());
+            };
+            let code = frame.code.clone();
+            let Some(op) = code.get(frame.pc) else {
+                thread.frames.pop();
+                continue;
+            };
+            frame.pc += 1;
+            match op {
+                Op::SetVar(var, value) => {
+                    let value = self.eval(target, &frame.args, value);
+                    *self.var_mut(target, *var) = value;
+                }
+                Op::ChangeVar(var, value) => {
+                    let value = self.eval(target, &frame.args, value).to_number();
+                    let var = self.var_mut(target, *var);
+                    *var = Value::Number(var.to_number() + value);
+                }
+                Op::AddToList(list, value) => {
+                    let value = self.eval(target, &frame.args, value);
+                    let list = self.list_mut(target, *list);
+                    if list.len() < LIST_ITEM_LIMIT {
+                        list.push(value);
+                    }
+                }
+                Op::DeleteList(list) => self.list_mut(target, *list).clear(),
+                Op::DeleteListIndex(list, index) => {
+                    let index = self.eval(target, &frame.args, index);
+                    let length = self.list(target, *list).len();
+                    match self.list_index(&index, length, true) {
+                        ListIndex::All => self.list_mut(target, *list).clear(),
+                        ListIndex::Index(index) => {
+                            self.list_mut(target, *list).remove(index);
+                        }
+                        ListIndex::Invalid => {}
+                    }
+                }
+                Op::InsertAtList(list, index, value) => {
+                    let index = self.eval(target, &frame.args, index);
+                    let value = self.eval(target, &frame.args, value);
+                    let length = self.list(target, *list).len();
+                    if let ListIndex::Index(index) = self.list_index(&index, length + 1, false) {
+                        let list = self.list_mut(target, *list);
+                        if index < LIST_ITEM_LIMIT {
+                            list.insert(index, value);
+                            list.truncate(LIST_ITEM_LIMIT);
+                        }
+                    }
+                }
+                Op::SetListIndex(list, index, value) => {
+                    let index = self.eval(target, &frame.args, index);
+                    let value = self.eval(target, &frame.args, value);
+                    let length = self.list(target, *list).len();
+                    if let ListIndex::Index(index) = self.list_index(&index, length, false) {
+                        self.list_mut(target, *list)[index] = value;
+                    }
+                }
+                Op::Jump(to) => frame.pc = *to,
+                Op::JumpIf(cond, to) => {
+                    if self.eval(target, &frame.args, cond).to_bool() {
+                        frame.pc = *to;
+                    }
+                }
+                Op::JumpIfNot(cond, to) => {
+                    if !self.eval(target, &frame.args, cond).to_bool() {
+                        frame.pc = *to;
+                    }
+                }
+                Op::RepeatStart(times) => {
+                    let times = self.eval(target, &frame.args, times).to_number();
+                    frame.loops.push((times + 0.5).floor());
+                }
+                Op::RepeatNext(end) => {
+                    let remaining = frame.loops.last_mut().unwrap();
+                    if *remaining >= 1.0 {
+                        *remaining -= 1.0;
+                    } else {
+                        frame.loops.pop();
+                        frame.pc = *end;
+                    }
+                }
+                Op::Yield => {
+                    if !frame.warp {
+                        return Ok(());
+                    }
+                }
+                Op::Call(procedure, args) => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.eval(target, &frame.args, arg))
+                        .collect();
+                    let sprite = self.targets[target].as_ref().unwrap().sprite;
+                    let procedure = &self.program.sprites[sprite].procedures[*procedure];
+                    let warp = frame.warp || procedure.warp;
+                    thread
+                        .frames
+                        .push(Frame::new(procedure.code.clone(), args, warp));
+                }
+                Op::Block(block, args) => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.eval(target, &frame.args, arg))
+                        .collect();
+                    match self.block(thread, *block, args)? {
+                        Flow::Next => {}
+                        Flow::Yield => return Ok(()),
+                        Flow::Retry => {
+                            thread.frames.last_mut().unwrap().pc -= 1;
+                            return Ok(());
+                        }
+                        Flow::Stop => {
+                            thread.frames.pop();
+                        }
+                    }
+                }
+                Op::Assert(cond, message, span) => {
+                    if self.testing && !self.eval(target, &frame.args, cond).to_bool() {
+                        let message = message
+                            .as_ref()
+                            .map(|message| self.eval(target, &frame.args, message).to_string());
+                        self.failure = Some(Failure::Assertion {
+                            message,
+                            span: span.clone(),
+                        });
+                        self.stopped = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn target_index(target: usize, scope: Scope) -> usize {
+        match scope {
+            Scope::Own => target,
+            Scope::Stage => 0,
+        }
+    }
+
+    fn var(&self, target: usize, var: Ref) -> &Value {
+        let target = Self::target_index(target, var.scope);
+        &self.targets[target].as_ref().unwrap().vars[var.index]
+    }
+
+    fn var_mut(&mut self, target: usize, var: Ref) -> &mut Value {
+        let target = Self::target_index(target, var.scope);
+        &mut self.targets[target].as_mut().unwrap().vars[var.index]
+    }
+
+    fn list(&self, target: usize, list: Ref) -> &Vec<Value> {
+        let target = Self::target_index(target, list.scope);
+        &self.targets[target].as_ref().unwrap().lists[list.index]
+    }
+
+    fn list_mut(&mut self, target: usize, list: Ref) -> &mut Vec<Value> {
+        let target = Self::target_index(target, list.scope);
+        &mut self.targets[target].as_mut().unwrap().lists[list.index]
+    }
+
+    fn list_index(&mut self, index: &Value, length: usize, accept_all: bool) -> ListIndex {
+        if let Value::String(string) = index {
+            match string.as_str() {
+                "all" if accept_all => return ListIndex::All,
+                "all" => return ListIndex::Invalid,
+                "last" if length > 0 => return ListIndex::Index(length - 1),
+                "random" | "any" if length > 0 => {
+                    return ListIndex::Index((self.random() * length as f64) as usize);
+                }
+                "last" | "random" | "any" => return ListIndex::Invalid,
+                _ => {}
+            }
+        }
+        let index = index.to_number().floor();
+        if index < 1.0 || index > length as f64 {
+            return ListIndex::Invalid;
+        }
+        ListIndex::Index(index as usize - 1)
+    }
+
+    pub fn random(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545f4914f6cdd1d) >> 11;
+        bits as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn warn_unsupported(&mut self, name: &'static str, reason: &str) {
+        if self.warned.insert(name) {
+            eprintln!(
+                "{}: `{name}` {reason} when running without a browser",
+                "warning".yellow().bold()
+            );
+        }
+    }
+
+    pub fn eval(&mut self, target: usize, args: &[Value], x: &X) -> Value {
+        match x {
+            X::Value(value) => value.clone(),
+            X::Var(var) => self.var(target, *var).clone(),
+            X::List(list) => {
+                let list = self.list(target, *list);
+                let single_letters = list.iter().all(
+                    |item| matches!(item, Value::String(string) if string.chars().count() == 1),
+                );
+                let separator = if single_letters { "" } else { " " };
+                let items: Vec<String> = list.iter().map(Value::to_string).collect();
+                items.join(separator).into()
+            }
+            X::ListItem(list, index) => {
+                let index = self.eval(target, args, index);
+                let length = self.list(target, *list).len();
+                match self.list_index(&index, length, false) {
+                    ListIndex::Index(index) => self.list(target, *list)[index].clone(),
+                    _ => "".into(),
+                }
+            }
+            X::ListLength(list) => (self.list(target, *list).len() as f64).into(),
+            X::Arg(arg) => arg
+                .and_then(|arg| args.get(arg))
+                .cloned()
+                .unwrap_or_default(),
+            X::Repr(repr, repr_args) => {
+                let repr_args = repr_args
+                    .iter()
+                    .map(|arg| self.eval(target, args, arg))
+                    .collect();
+                self.repr(target, *repr, repr_args)
+            }
+            X::UnOp(op, opr) => {
+                let opr = self.eval(target, args, opr);
+                un_op(*op, &opr)
+            }
+            X::BinOp(op, lhs, rhs) => {
+                let lhs = self.eval(target, args, lhs);
+                let rhs = self.eval(target, args, rhs);
+                bin_op(*op, &lhs, &rhs)
+            }
+        }
+    }
+}
+
+enum ListIndex {
+    Index(usize),
+    All,
+    Invalid,
+}
+
+/// Rounds the result of a trigonometric function to 10 decimal places, so that
+/// `sin(180)` is 0, the same as in Scratch.
+fn round_trig(number: f64) -> f64 {
+    (number * 1e10).round() / 1e10
+}
+
+fn un_op(op: UnOp, opr: &Value) -> Value {
+    let number = || opr.to_number();
+    match op {
+        UnOp::Not => (!opr.to_bool()).into(),
+        UnOp::Length => (opr.to_string().chars().count() as f64).into(),
+        UnOp::Round => (number() + 0.5).floor().into(),
+        UnOp::Abs => number().abs().into(),
+        UnOp::Floor => number().floor().into(),
+        UnOp::Ceil => number().ceil().into(),
+        UnOp::Sqrt => number().sqrt().into(),
+        UnOp::Sin => round_trig((PI * number() / 180.0).sin()).into(),
+        UnOp::Cos => round_trig((PI * number() / 180.0).cos()).into(),
+        UnOp::Tan => {
+            let angle = number() % 360.0;
+            match angle {
+                -270.0 | 90.0 => f64::INFINITY,
+                -90.0 | 270.0 => f64::NEG_INFINITY,
+                _ => round_trig((PI * angle / 180.0).tan()),
+            }
+            .into()
+        }
+        UnOp::Asin => (number().asin() * 180.0 / PI).into(),
+        UnOp::Acos => (number().acos() * 180.0 / PI).into(),
+        UnOp::Atan => (number().atan() * 180.0 / PI).into(),
+        UnOp::Ln => number().ln().into(),
+        UnOp::Log => number().log10().into(),
+        UnOp::AntiLn => number().exp().into(),
+        UnOp::AntiLog => 10f64.powf(number()).into(),
+        UnOp::Minus => (-number()).into(),
+    }
+}
+
+fn bin_op(op: BinOp, lhs: &Value, rhs: &Value) -> Value {
+    match op {
+        BinOp::Add => (lhs.to_number() + rhs.to_number()).into(),
+        BinOp::Sub => (lhs.to_number() - rhs.to_number()).into(),
+        BinOp::Mul => (lhs.to_number() * rhs.to_number()).into(),
+        BinOp::Div => (lhs.to_number() / rhs.to_number()).into(),
+        BinOp::Mod => {
+            let modulus = rhs.to_number();
+            let mut result = lhs.to_number() % modulus;
+            if result / modulus < 0.0 {
+                result += modulus;
+            }
+            result.into()
+        }
+        BinOp::Lt => lhs.compare(rhs).is_lt().into(),
+        BinOp::Gt => lhs.compare(rhs).is_gt().into(),
+        BinOp::Eq => lhs.compare(rhs).is_eq().into(),
+        BinOp::And => (lhs.to_bool() && rhs.to_bool()).into(),
+        BinOp::Or => (lhs.to_bool() || rhs.to_bool()).into(),
+        BinOp::Join => format!("{lhs}{rhs}").into(),
+        BinOp::In => rhs
+            .to_string()
+            .to_lowercase()
+            .contains(&lhs.to_string().to_lowercase())
+            .into(),
+        BinOp::Of => {
+            let index = rhs.to_number() - 1.0;
+            let string = lhs.to_string();
+            if index < 0.0 {
+                return "".into();
+            }
+            string
+                .chars()
+                .nth(index as usize)
+                .map(String::from)
+                .unwrap_or_default()
+                .into()
+        }
+        // Rewritten by pass1.
+        BinOp::Le => (!lhs.compare(rhs).is_gt()).into(),
+        BinOp::Ge => (!lhs.compare(rhs).is_lt()).into(),
+        BinOp::Ne => (!lhs.compare(rhs).is_eq()).into(),
+        BinOp::FloorDiv => (lhs.to_number() / rhs.to_number()).floor().into(),
+    }
+}
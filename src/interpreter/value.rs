@@ -0,0 +1,201 @@
+use std::{cmp::Ordering, fmt};
+
+use smol_str::SmolStr;
+
+use crate::ast;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Number(f64),
+    String(SmolStr),
+    Bool(bool),
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Self::Number(0.0)
+    }
+}
+
+impl From<&ast::Value> for Value {
+    fn from(value: &ast::Value) -> Self {
+        match value {
+            ast::Value::Int(int) => Self::Number(*int as f64),
+            ast::Value::Float(float) => Self::Number(*float),
+            ast::Value::String(string) => Self::String(string.clone()),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Self {
+        Self::Number(number)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(bool: bool) -> Self {
+        Self::Bool(bool)
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Self {
+        Self::String(string.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Self::String(string.into())
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Number(number) => f.write_str(&number_to_string(*number)),
+            Self::String(string) => f.write_str(string),
+            Self::Bool(bool) => write!(f, "{bool}"),
+        }
+    }
+}
+
+impl Value {
+    pub fn js_number(&self) -> f64 {
+        match self {
+            Self::Number(number) => *number,
+            Self::String(string) => parse_number(string),
+            Self::Bool(bool) => *bool as u8 as f64,
+        }
+    }
+
+    pub fn to_number(&self) -> f64 {
+        let number = self.js_number();
+        if number.is_nan() {
+            0.0
+        } else {
+            number
+        }
+    }
+
+    pub fn to_bool(&self) -> bool {
+        match self {
+            Self::Number(number) => *number != 0.0 && !number.is_nan(),
+            Self::String(string) => {
+                !(string.is_empty() || string == "0" || string.eq_ignore_ascii_case("false"))
+            }
+            Self::Bool(bool) => *bool,
+        }
+    }
+
+    pub fn is_whitespace(&self) -> bool {
+        matches!(self, Self::String(string) if string.trim().is_empty())
+    }
+
+    pub fn is_int(&self) -> bool {
+        match self {
+            Self::Number(number) => number.is_nan() || number.fract() == 0.0,
+            Self::String(string) => !string.contains('.'),
+            Self::Bool(_) => true,
+        }
+    }
+
+    pub fn compare(&self, other: &Value) -> Ordering {
+        let mut lhs = self.js_number();
+        let mut rhs = other.js_number();
+        if lhs == 0.0 && self.is_whitespace() {
+            lhs = f64::NAN;
+        } else if rhs == 0.0 && other.is_whitespace() {
+            rhs = f64::NAN;
+        }
+        if lhs.is_nan() || rhs.is_nan() {
+            let lhs = self.to_string().to_lowercase();
+            let rhs = other.to_string().to_lowercase();
+            return lhs.cmp(&rhs);
+        }
+        lhs.partial_cmp(&rhs).unwrap()
+    }
+}
+
+/// Parses a string the same way as JavaScript's `Number(string)`.
+fn parse_number(string: &str) -> f64 {
+    let string = string.trim().trim_matches('\u{feff}');
+    if string.is_empty() {
+        return 0.0;
+    }
+    match string {
+        "Infinity" | "+Infinity" => return f64::INFINITY,
+        "-Infinity" => return f64::NEG_INFINITY,
+        _ => {}
+    }
+    let radix = match string.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0o" | "0O") => Some(8),
+        Some("0b" | "0B") => Some(2),
+        _ => None,
+    };
+    if let Some(radix) = radix {
+        let digits = &string[2..];
+        if digits.is_empty() {
+            return f64::NAN;
+        }
+        return digits
+            .chars()
+            .try_fold(0.0, |number, ch| {
+                ch.to_digit(radix)
+                    .map(|digit| number * radix as f64 + digit as f64)
+            })
+            .unwrap_or(f64::NAN);
+    }
+    // Rust accepts `inf` and `nan`, JavaScript does not.
+    if !string
+        .chars()
+        .all(|ch| ch.is_ascii_digit() || matches!(ch, '.' | 'e' | 'E' | '+' | '-'))
+    {
+        return f64::NAN;
+    }
+    string.parse().unwrap_or(f64::NAN)
+}
+
+/// Formats a number the same way as JavaScript's `String(number)`.
+pub fn number_to_string(number: f64) -> String {
+    if number.is_nan() {
+        return "NaN".into();
+    }
+    if number == 0.0 {
+        return "0".into();
+    }
+    if number.is_infinite() {
+        return if number > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }
+        .into();
+    }
+    let sign = if number < 0.0 { "-" } else { "" };
+    // `{:e}` gives the shortest digits which round-trip, which is what JavaScript uses.
+    let scientific = format!("{:e}", number.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    let n = exponent.parse::<i32>().unwrap() + 1;
+    if k <= n && n <= 21 {
+        format!("{sign}{digits}{}", "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        let (integer, fraction) = digits.split_at(n as usize);
+        format!("{sign}{integer}.{fraction}")
+    } else if -6 < n && n <= 0 {
+        format!("{sign}0.{}{digits}", "0".repeat(-n as usize))
+    } else {
+        let exponent_sign = if n - 1 < 0 { "-" } else { "+" };
+        let (first, rest) = digits.split_at(1);
+        let rest = if rest.is_empty() {
+            String::new()
+        } else {
+            format!(".{rest}")
+        };
+        format!("{sign}{first}{rest}e{exponent_sign}{}", (n - 1).abs())
+    }
+}
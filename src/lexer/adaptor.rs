@@ -5,12 +5,23 @@ use crate::diagnostic::{Diagnostic, DiagnosticKind};
 
 pub struct Lexer<'source> {
     token_stream: SpannedIter<'source, Token>,
+    lossless: bool,
 }
 
 impl<'source> Lexer<'source> {
     pub fn new(source: &'source str) -> Self {
         Self {
             token_stream: Token::lexer(source).spanned(),
+            lossless: false,
+        }
+    }
+
+    /// A lexer which also produces whitespace and comment tokens, so that every byte of
+    /// `source` is covered by exactly one token.
+    pub fn lossless(source: &'source str) -> Self {
+        Self {
+            token_stream: Token::lexer(source).spanned(),
+            lossless: true,
         }
     }
 }
@@ -25,13 +36,17 @@ impl<'source> Iterator for Lexer<'source> {
     type Item = Result<(usize, Token, usize), Diagnostic>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.token_stream.next().map(|(token, span)| {
+        let (token, span) = self
+            .token_stream
+            .by_ref()
+            .find(|(token, _)| self.lossless || !token.as_ref().is_ok_and(Token::is_trivia))?;
+        Some(
             token
                 .map(|token| (span.start, token, span.end))
                 .map_err(|_| Diagnostic {
                     kind: DiagnosticKind::InvalidToken,
                     span: span.clone(),
-                })
-        })
+                }),
+        )
     }
 }
@@ -1,82 +1,334 @@
+use std::{io, iter::Peekable, ops::Range, path::PathBuf};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fxhash::FxHashSet;
+use logos::Span;
+use smol_str::{format_smolstr, SmolStr};
+
 use super::{adaptor::Lexer, token::Token};
-use crate::preproc::PreProc;
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticKind},
+    preproc::{Expansion, PreProc},
+};
 
-pub fn preproc(preproc: &PreProc) -> Vec<(usize, Token, usize)> {
-    let mut tokens = vec![];
-    let mut lex = Lexer::new(preproc.get_translation_unit()).flatten();
-    while let Some((start, token, end)) = lex.next() {
-        match &token {
-            Token::Name(name) => {
-                if let Some(value) = preproc.defines.get(name.as_str()) {
-                    tokens.extend(Lexer::new(value).flatten());
-                } else if let Some(mac) = preproc.macros.get(name.as_str()) {
-                    if let Some((start, token, end)) = lex.next() {
-                        if let Token::LParen = &token {
-                        } else {
-                            tokens.push((start, token, end));
-                            continue;
-                        }
-                    } else {
-                        continue;
-                    }
-                    let mut parens = 1;
-                    let mut braces = 0;
-                    let mut brackets = 0;
+type Spanned = (usize, Token, usize);
+
+enum Encoding {
+    Text,
+    Base64,
+    Hex,
+    Lines,
+    Split(SmolStr),
+}
 
-                    let mut args = vec![];
-                    'args: loop {
-                        let mut arg = vec![];
-                        for (start, token, end) in lex.by_ref() {
-                            match &token {
-                                Token::LParen => parens += 1,
-                                Token::RParen => {
-                                    parens -= 1;
-                                    if parens == 0 && braces == 0 && brackets == 0 {
-                                        args.push(arg);
-                                        break 'args;
-                                    }
-                                }
-                                Token::LBrace => braces += 1,
-                                Token::RBrace => braces -= 1,
-                                Token::LBracket => brackets += 1,
-                                Token::RBracket => brackets -= 1,
-                                Token::Comma => {
-                                    if parens == 1 && braces == 0 && brackets == 0 {
-                                        break;
-                                    }
-                                }
-                                _ => {}
-                            }
-                            arg.push((start, token, end));
-                        }
-                        args.push(arg);
+pub fn preproc(
+    preproc: &PreProc,
+    embeds: &mut Vec<PathBuf>,
+) -> (Result<Vec<Spanned>, Diagnostic>, Vec<Expansion>) {
+    let mut expander = Expander {
+        preproc,
+        expansions: vec![],
+        next: preproc.buffer.len() + 1,
+        active: vec![],
+    };
+    let source = preproc.lexer_source();
+    let tokens = embed(preproc, embeds, Lexer::new(&source).flatten())
+        .and_then(|tokens| expander.tokens(tokens));
+    (tokens, expander.expansions)
+}
+
+struct Expander<'a> {
+    preproc: &'a PreProc,
+    expansions: Vec<Expansion>,
+    next: usize,
+    active: Vec<SmolStr>,
+}
+
+impl Expander<'_> {
+    fn tokens(&mut self, tokens: Vec<Spanned>) -> Result<Vec<Spanned>, Diagnostic> {
+        let mut output = vec![];
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some((start, token, end)) = tokens.next() {
+            match &token {
+                Token::Name(name) if !self.active.contains(name) => {
+                    if let Some(value) = self.preproc.defines.get(name.as_str()) {
+                        let expansion = self.expand(name, value, start..end);
+                        output.extend(self.within(name, expansion)?);
+                        continue;
                     }
-                    for (start, token, end) in Lexer::new(mac.substitution.as_str()).flatten() {
-                        match &token {
-                            Token::Name(name) => {
-                                if let Some(value) = preproc.defines.get(name.as_str()) {
-                                    tokens.extend(Lexer::new(value).flatten());
-                                } else if let Some(index) =
-                                    mac.args.iter().position(|arg| arg == name)
-                                {
-                                    tokens.extend(args[index].iter().cloned());
-                                } else {
-                                    tokens.push((start, Token::Name(name.clone()), end));
-                                }
-                            }
-                            _ => {
-                                tokens.push((start, token, end));
-                            }
-                        }
+                }
+                Token::Mac(name) => {
+                    // `a!=b` is lexed as `a!` followed by `=`.
+                    if let Some((_, _, assign_end)) = tokens.next_if(|(assign_start, token, _)| {
+                        *assign_start == end && matches!(token, Token::Assign)
+                    }) {
+                        output.extend(
+                            Lexer::new(name)
+                                .flatten()
+                                .map(|(s, token, e)| (start + s, token, start + e)),
+                        );
+                        output.push((end - 1, Token::Ne, assign_end));
+                        continue;
                     }
-                } else {
-                    tokens.push((start, token, end));
+                    let expansion = self.call(name, start..end, &mut tokens)?;
+                    output.extend(self.within(name, expansion)?);
+                    continue;
                 }
+                _ => {}
             }
-            _ => {
-                tokens.push((start, token, end));
+            output.push((start, token, end));
+        }
+        Ok(output)
+    }
+
+    fn within(
+        &mut self,
+        name: &SmolStr,
+        expansion: Vec<Spanned>,
+    ) -> Result<Vec<Spanned>, Diagnostic> {
+        self.active.push(name.clone());
+        let tokens = self.tokens(expansion);
+        self.active.pop();
+        tokens
+    }
+
+    fn call(
+        &mut self,
+        name: &SmolStr,
+        span: Range<usize>,
+        tokens: &mut Peekable<impl Iterator<Item = Spanned>>,
+    ) -> Result<Vec<Spanned>, Diagnostic> {
+        let invalid = |reason: String, span: Range<usize>| Diagnostic {
+            kind: DiagnosticKind::InvalidMacroCall(reason),
+            span,
+        };
+        let Some(mac) = self.preproc.macros.get(name.as_str()) else {
+            let reason = if self.preproc.defines.contains_key(name.as_str()) {
+                format!("`{name}` does not take arguments, so it is used as `{name}`")
+            } else {
+                format!("there is no macro named `{name}`")
+            };
+            return Err(invalid(reason, span));
+        };
+        if self.active.contains(name) {
+            return Err(invalid(format!("`{name}!` is called inside itself"), span));
+        }
+        if !tokens
+            .peek()
+            .is_some_and(|(_, token, _)| matches!(token, Token::LParen))
+        {
+            return Err(invalid(
+                format!("`{name}!` must be followed by its arguments in brackets"),
+                span,
+            ));
+        }
+        let Some((mut values, call_end)) = args(tokens) else {
+            return Err(invalid(
+                format!("the arguments of `{name}!` are not closed by `)`"),
+                span,
+            ));
+        };
+        let span = span.start..call_end;
+        if mac.args.is_empty() && values.len() == 1 && values[0].is_empty() {
+            values.clear();
+        }
+        if values.len() != mac.args.len() {
+            let arguments = |count: usize| match count {
+                1 => "1 argument".to_owned(),
+                _ => format!("{count} arguments"),
+            };
+            return Err(invalid(
+                format!(
+                    "`{name}!` takes {}, but was given {}",
+                    arguments(mac.args.len()),
+                    arguments(values.len())
+                ),
+                span,
+            ));
+        }
+        let values = values
+            .into_iter()
+            .map(|value| self.tokens(value))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut output = vec![];
+        for (start, token, end) in self.expand(name, &mac.substitution, span) {
+            let index = match &token {
+                Token::Name(name) => mac.args.iter().position(|arg| arg == name),
+                _ => None,
+            };
+            match index {
+                Some(index) => output.extend(values[index].iter().cloned()),
+                None => output.push((start, token, end)),
             }
         }
+        Ok(output)
+    }
+
+    /// Names which are declared with `local` in `source` are renamed, so that they are
+    /// different in each expansion, and from names outside of it.
+    fn expand(&mut self, name: &str, source: &str, call_site: Range<usize>) -> Vec<Spanned> {
+        let start = self.next;
+        self.next += source.len() + 1;
+        self.expansions.push(Expansion {
+            name: name.to_owned(),
+            range: start..self.next,
+            call_site,
+            definition: self.preproc.definitions.get(name).cloned(),
+        });
+        let id = self.expansions.len();
+        let tokens: Vec<Spanned> = Lexer::new(source)
+            .flatten()
+            .map(|(token_start, token, token_end)| (start + token_start, token, start + token_end))
+            .collect();
+        let locals = locals(&tokens);
+        tokens
+            .into_iter()
+            .map(|(start, token, end)| match token {
+                Token::Name(name) if locals.contains(&name) => {
+                    (start, Token::Name(format_smolstr!("{name}#{id}")), end)
+                }
+                token => (start, token, end),
+            })
+            .collect()
     }
+}
+
+fn locals(tokens: &[Spanned]) -> FxHashSet<SmolStr> {
     tokens
+        .windows(3)
+        .filter_map(|window| match window {
+            [(_, Token::Local, _), (_, Token::Name(_), _), (_, Token::Name(name), _)]
+            | [(_, Token::Local, _), (_, Token::Name(name), _), _] => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn args(lex: &mut Peekable<impl Iterator<Item = Spanned>>) -> Option<(Vec<Vec<Spanned>>, usize)> {
+    lex.next();
+    let mut depth = 0;
+    let mut args = vec![];
+    let mut arg = vec![];
+    for (start, token, end) in lex.by_ref() {
+        match &token {
+            Token::LParen | Token::LBrace | Token::LBracket => depth += 1,
+            Token::RParen if depth == 0 => {
+                args.push(arg);
+                return Some((args, end));
+            }
+            Token::RParen | Token::RBrace | Token::RBracket => depth -= 1,
+            Token::Comma if depth == 0 => {
+                args.push(std::mem::take(&mut arg));
+                continue;
+            }
+            _ => {}
+        }
+        arg.push((start, token, end));
+    }
+    None
+}
+
+fn embed(
+    preproc: &PreProc,
+    embeds: &mut Vec<PathBuf>,
+    tokens: impl Iterator<Item = Spanned>,
+) -> Result<Vec<Spanned>, Diagnostic> {
+    let mut output = vec![];
+    let mut tokens = tokens.peekable();
+    while let Some((start, token, end)) = tokens.next() {
+        let Token::Embed = token else {
+            output.push((start, token, end));
+            continue;
+        };
+        let (encoding, path, end) = directive(&mut tokens, start, end)?;
+        let span = start..end;
+        let (embed_path, contents) = preproc.embed(start, &path);
+        embeds.push(embed_path);
+        let contents = contents.map_err(|err| Diagnostic {
+            kind: match err.kind() {
+                io::ErrorKind::NotFound => DiagnosticKind::FileNotFound(path.clone()),
+                _ => DiagnosticKind::InvalidEmbed(format!("could not read {path:?}: {err}")),
+            },
+            span: span.clone(),
+        })?;
+        let string = |value: &str| (start, Token::Str(value.into()), end);
+        match &encoding {
+            Encoding::Text => output.push(string(&text(contents, &path, &span)?)),
+            Encoding::Base64 => output.push(string(&STANDARD.encode(&contents))),
+            Encoding::Hex => {
+                let hex: String = contents.iter().map(|byte| format!("{byte:02x}")).collect();
+                output.push(string(&hex));
+            }
+            Encoding::Lines | Encoding::Split(_) => {
+                let text = text(contents, &path, &span)?;
+                let items: Vec<&str> = match &encoding {
+                    Encoding::Split(separator) => text.split(separator.as_str()).collect(),
+                    _ => text.lines().collect(),
+                };
+                output.push((start, Token::LBracket, end));
+                for item in items {
+                    output.push(string(item));
+                    output.push((start, Token::Comma, end));
+                }
+                output.push((start, Token::RBracket, end));
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn directive(
+    tokens: &mut Peekable<impl Iterator<Item = Spanned>>,
+    start: usize,
+    mut end: usize,
+) -> Result<(Encoding, SmolStr, usize), Diagnostic> {
+    let invalid = |reason: String, end: usize| Diagnostic {
+        kind: DiagnosticKind::InvalidEmbed(reason),
+        span: start..end,
+    };
+    let mut encoding = Encoding::Text;
+    if let Some((_, Token::Name(name), name_end)) =
+        tokens.next_if(|(_, token, _)| matches!(token, Token::Name(_)))
+    {
+        end = name_end;
+        encoding = match name.as_str() {
+            "base64" => Encoding::Base64,
+            "hex" => Encoding::Hex,
+            "lines" => Encoding::Lines,
+            "split" => match tokens.next() {
+                Some((_, Token::Str(separator), separator_end)) if !separator.is_empty() => {
+                    end = separator_end;
+                    Encoding::Split(separator)
+                }
+                _ => {
+                    return Err(invalid(
+                        "`split` must be followed by the string to split at".to_owned(),
+                        end,
+                    ))
+                }
+            },
+            _ => {
+                return Err(invalid(
+                    format!("unknown encoding `{name}`, expected base64, hex, lines or split"),
+                    end,
+                ))
+            }
+        };
+    }
+    match tokens.next() {
+        Some((_, Token::Str(path), path_end)) => Ok((encoding, path, path_end)),
+        _ => Err(invalid(
+            "expected the path of the file to embed, as a string".to_owned(),
+            end,
+        )),
+    }
+}
+
+fn text(contents: Vec<u8>, path: &str, span: &Span) -> Result<String, Diagnostic> {
+    String::from_utf8(contents).map_err(|_| Diagnostic {
+        kind: DiagnosticKind::InvalidEmbed(format!(
+            "{path:?} is not UTF-8 text, so it can only be embedded as base64 or hex"
+        )),
+        span: span.clone(),
+    })
 }
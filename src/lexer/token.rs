@@ -4,10 +4,12 @@ use smol_str::SmolStr;
 use super::literal::{arg, bin, cmd, float, hex, int, mac, name, oct, string};
 
 #[derive(Debug, Logos, Clone)]
-#[logos(skip r"[ \t\n\f]+")]
-#[logos(skip r"#[^\n]*\n")]
 pub enum Token {
-    #[regex(r"[_a-zA-Z][_a-zA-Z0-9]*", name)]
+    #[regex(r"[ \t\n\f]+")]
+    Whitespace,
+    #[regex(r"#[^\n]*")]
+    Comment,
+    #[regex(r"[_a-zA-Z][_a-zA-Z0-9]*(::[_a-zA-Z][_a-zA-Z0-9]*)*", name)]
     Name(SmolStr),
     #[regex(r"\$[_a-zA-Z0-9]+", arg)]
     Arg(SmolStr),
@@ -41,6 +43,10 @@ pub enum Token {
     Return,
     #[token("nowarp")]
     NoWarp,
+    #[token("pub")]
+    Pub,
+    #[token("import")]
+    Import,
     #[token("on")]
     On,
     #[token("onflag")]
@@ -57,6 +63,10 @@ pub enum Token {
     OnTimer,
     #[token("onclone")]
     OnClone,
+    #[token("test")]
+    Test,
+    #[token("assert")]
+    Assert,
     #[token("if")]
     If,
     #[token("else")]
@@ -139,6 +149,8 @@ pub enum Token {
     FloorDiv,
     #[token("%")]
     Percent,
+    #[token("%embed")]
+    Embed,
     #[token(";")]
     Semicolon,
     #[token(":")]
@@ -206,3 +218,9 @@ pub enum Token {
     #[token("cloud")]
     Cloud,
 }
+
+impl Token {
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Whitespace | Token::Comment)
+    }
+}
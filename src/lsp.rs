@@ -0,0 +1,219 @@
+mod completion;
+mod hover;
+mod index;
+mod navigation;
+mod workspace;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, References, Request as _},
+    CompletionOptions, CompletionParams, CompletionResponse, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+    GotoDefinitionParams, GotoDefinitionResponse, HoverParams, HoverProviderCapability, OneOf,
+    PublishDiagnosticsParams, ReferenceParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use workspace::Workspace;
+
+use crate::misc::normalize_path;
+
+pub fn serve() -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string(), "$".to_string()]),
+            ..Default::default()
+        }),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    let mut server = Server {
+        connection: &connection,
+        workspace: Workspace::default(),
+    };
+    server.run()?;
+    // The writer thread exits once every sender is dropped.
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+fn to_path(uri: &Url) -> Option<PathBuf> {
+    uri.to_file_path().ok().map(|path| normalize_path(&path))
+}
+
+fn params<P: DeserializeOwned>(params: Value) -> anyhow::Result<P> {
+    Ok(serde_json::from_value(params)?)
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    workspace: Workspace,
+}
+
+impl Server<'_> {
+    fn run(&mut self) -> anyhow::Result<()> {
+        for message in &self.connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        break;
+                    }
+                    self.request(request)?;
+                }
+                Message::Notification(notification) => self.notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn source(&self, path: &Path) -> Option<String> {
+        match self.workspace.documents.get(path) {
+            Some(src) => Some(src.clone()),
+            None => fs::read_to_string(path).ok(),
+        }
+    }
+
+    fn respond(&self, id: RequestId, result: impl serde::Serialize) -> anyhow::Result<()> {
+        self.connection
+            .sender
+            .send(Response::new_ok(id, result).into())?;
+        Ok(())
+    }
+
+    fn request(&mut self, request: Request) -> anyhow::Result<()> {
+        let Request { id, method, params } = request;
+        match method.as_str() {
+            HoverRequest::METHOD => {
+                let params: HoverParams = self::params(params)?;
+                let position = params.text_document_position_params;
+                let hover = to_path(&position.text_document.uri).and_then(|path| {
+                    let src = self.source(&path)?;
+                    let analysis = self.workspace.analysis(&path)?;
+                    hover::hover(analysis, &path, position.position, &src)
+                });
+                self.respond(id, hover)
+            }
+            Completion::METHOD => {
+                let params: CompletionParams = self::params(params)?;
+                let position = params.text_document_position;
+                let items = to_path(&position.text_document.uri)
+                    .and_then(|path| {
+                        let src = self.source(&path)?;
+                        let analysis = self.workspace.analysis(&path)?;
+                        Some(completion::completion(
+                            analysis,
+                            &path,
+                            position.position,
+                            &src,
+                        ))
+                    })
+                    .unwrap_or_default();
+                self.respond(id, CompletionResponse::Array(items))
+            }
+            GotoDefinition::METHOD => {
+                let params: GotoDefinitionParams = self::params(params)?;
+                let position = params.text_document_position_params;
+                let location = to_path(&position.text_document.uri).and_then(|path| {
+                    let analysis = self.workspace.analysis(&path)?;
+                    navigation::definition(analysis, &path, position.position)
+                });
+                self.respond(id, location.map(GotoDefinitionResponse::Scalar))
+            }
+            References::METHOD => {
+                let params: ReferenceParams = self::params(params)?;
+                let position = params.text_document_position;
+                let locations = to_path(&position.text_document.uri)
+                    .and_then(|path| {
+                        let analysis = self.workspace.analysis(&path)?;
+                        Some(navigation::references(
+                            analysis,
+                            &path,
+                            position.position,
+                            params.context.include_declaration,
+                        ))
+                    })
+                    .unwrap_or_default();
+                self.respond(id, locations)
+            }
+            _ => {
+                self.connection.sender.send(
+                    Response::new_err(
+                        id,
+                        lsp_server::ErrorCode::MethodNotFound as i32,
+                        format!("unsupported request {method}"),
+                    )
+                    .into(),
+                )?;
+                Ok(())
+            }
+        }
+    }
+
+    fn notification(&mut self, notification: Notification) -> anyhow::Result<()> {
+        let Notification { method, params } = notification;
+        let uri = match method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = self::params(params)?;
+                if let Some(path) = to_path(&params.text_document.uri) {
+                    Rc::make_mut(&mut self.workspace.documents)
+                        .insert(path, params.text_document.text);
+                }
+                params.text_document.uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams = self::params(params)?;
+                if let (Some(path), Some(change)) = (
+                    to_path(&params.text_document.uri),
+                    params.content_changes.into_iter().last(),
+                ) {
+                    Rc::make_mut(&mut self.workspace.documents).insert(path, change.text);
+                }
+                params.text_document.uri
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: DidSaveTextDocumentParams = self::params(params)?;
+                params.text_document.uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = self::params(params)?;
+                if let Some(path) = to_path(&params.text_document.uri) {
+                    Rc::make_mut(&mut self.workspace.documents).remove(&path);
+                }
+                params.text_document.uri
+            }
+            _ => return Ok(()),
+        };
+        let Some(root) = to_path(&uri).and_then(|path| self.workspace.project_root(&path)) else {
+            return Ok(());
+        };
+        for (path, diagnostics) in self.workspace.analyze(&root) {
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+            self.connection.sender.send(
+                lsp_server::Notification::new(PublishDiagnostics::METHOD.to_string(), params)
+                    .into(),
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use std::path::Path;
+
+use fxhash::FxHashSet;
+use lsp_types::{CompletionItem, CompletionItemKind, Position};
+use smol_str::SmolStr;
+
+use super::{
+    hover::{block_signature, blocks_named, repr_signature, reprs_named},
+    index::{func_detail, proc_detail},
+    workspace::{position_to_offset, Analysis, Unit},
+};
+use crate::{
+    ast::{Arg, Sprite, Struct, Type, Var},
+    blocks::{Block, Repr},
+};
+
+struct Completions {
+    items: Vec<CompletionItem>,
+    seen: FxHashSet<String>,
+}
+
+impl Completions {
+    fn add(&mut self, label: &str, kind: CompletionItemKind, detail: String) {
+        if !self.seen.insert(label.to_string()) {
+            return;
+        }
+        self.items.push(CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            detail: Some(detail),
+            ..Default::default()
+        });
+    }
+
+    fn fields(&mut self, struct_: &Struct) {
+        for field in &struct_.fields {
+            self.add(
+                &field.name,
+                CompletionItemKind::FIELD,
+                format!("{}.{}", struct_.name, field.name),
+            );
+        }
+    }
+}
+
+fn get_struct<'a>(unit: &Unit<'a>, stage: &'a Sprite, type_: &Type) -> Option<&'a Struct> {
+    let (name, _) = type_.struct_()?;
+    unit.sprite
+        .structs
+        .get(name)
+        .or_else(|| stage.structs.get(name))
+}
+
+fn enclosing(sprite: &Sprite, offset: usize) -> Option<(&[Arg], Vec<&Var>)> {
+    let procs = sprite
+        .procs
+        .values()
+        .map(|proc| (proc.span.start, Some((&proc.args[..], &proc.locals))));
+    let funcs = sprite
+        .funcs
+        .values()
+        .map(|func| (func.span.start, Some((&func.args[..], &func.locals))));
+    let events = sprite.events.iter().map(|event| (event.span.start, None));
+    let tests = sprite
+        .tests
+        .iter()
+        .map(|test| (test.span.start, Some((&[][..], &test.locals))));
+    let (_, enclosing) = procs
+        .chain(funcs)
+        .chain(events)
+        .chain(tests)
+        .filter(|(start, _)| *start <= offset)
+        .max_by_key(|(start, _)| *start)?;
+    enclosing.map(|(args, locals)| (args, locals.values().collect()))
+}
+
+pub fn completion(
+    analysis: &Analysis,
+    path: &Path,
+    position: Position,
+    src: &str,
+) -> Vec<CompletionItem> {
+    let mut completions = Completions {
+        items: vec![],
+        seen: FxHashSet::default(),
+    };
+    let units = analysis.units_at(path, position);
+    let Some((unit, unit_offset)) = units.into_iter().next() else {
+        return completions.items;
+    };
+    let stage = &analysis.parsed.project.stage;
+    let offset = position_to_offset(src, position);
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let before = src[..offset].trim_end_matches(is_word);
+    let enclosing = enclosing(unit.sprite, unit_offset);
+
+    if let Some(before_dot) = before.strip_suffix('.') {
+        let base = &before_dot[before_dot.trim_end_matches(is_word).len()..];
+        let is_arg = before_dot[..before_dot.len() - base.len()].ends_with('$');
+        if is_arg {
+            let args = unit
+                .sprite
+                .procs
+                .values()
+                .flat_map(|proc| &proc.args)
+                .chain(unit.sprite.funcs.values().flat_map(|func| &func.args));
+            for arg in args.filter(|arg| arg.name == base) {
+                if let Some(struct_) = get_struct(&unit, stage, &arg.type_) {
+                    completions.fields(struct_);
+                }
+            }
+            return completions.items;
+        }
+        if let Some(enum_) = unit.sprite.enums.get(base).or(stage.enums.get(base)) {
+            for variant in &enum_.variants {
+                completions.add(
+                    &variant.name,
+                    CompletionItemKind::ENUM_MEMBER,
+                    format!("{}.{}", enum_.name, variant.name),
+                );
+            }
+        }
+        let locals = enclosing
+            .iter()
+            .flat_map(|(_, locals)| locals.iter().copied());
+        let vars = locals
+            .chain(unit.sprite.vars.values())
+            .chain(stage.vars.values());
+        for var in vars.filter(|var| var.name == base) {
+            if let Some(struct_) = get_struct(&unit, stage, &var.type_) {
+                completions.fields(struct_);
+            }
+        }
+        let lists = unit.sprite.lists.values().chain(stage.lists.values());
+        for list in lists.filter(|list| list.name == base) {
+            if let Some(struct_) = get_struct(&unit, stage, &list.type_) {
+                completions.fields(struct_);
+            }
+        }
+        return completions.items;
+    }
+
+    if before.ends_with('$') {
+        for arg in enclosing.iter().flat_map(|(args, _)| args.iter()) {
+            completions.add(
+                &arg.name,
+                CompletionItemKind::VARIABLE,
+                "argument".to_string(),
+            );
+        }
+        return completions.items;
+    }
+
+    if let Some((_, locals)) = &enclosing {
+        for local in locals {
+            completions.add(
+                &local.name,
+                CompletionItemKind::VARIABLE,
+                "local variable".into(),
+            );
+        }
+    }
+    for sprite in [unit.sprite, stage] {
+        let public = |name: &SmolStr| !sprite.private.contains(name);
+        for var in sprite.vars.values().filter(|var| public(&var.name)) {
+            completions.add(&var.name, CompletionItemKind::VARIABLE, "variable".into());
+        }
+        for list in sprite.lists.values().filter(|list| public(&list.name)) {
+            completions.add(&list.name, CompletionItemKind::VARIABLE, "list".into());
+        }
+        for enum_ in sprite.enums.values().filter(|enum_| public(&enum_.name)) {
+            completions.add(&enum_.name, CompletionItemKind::ENUM, "enum".into());
+        }
+        for struct_ in sprite
+            .structs
+            .values()
+            .filter(|struct_| public(&struct_.name))
+        {
+            completions.add(&struct_.name, CompletionItemKind::STRUCT, "struct".into());
+        }
+    }
+    let public = |name: &SmolStr| !unit.sprite.private.contains(name);
+    for proc in unit.sprite.procs.values().filter(|proc| public(&proc.name)) {
+        completions.add(&proc.name, CompletionItemKind::FUNCTION, proc_detail(proc));
+    }
+    for func in unit.sprite.funcs.values().filter(|func| public(&func.name)) {
+        completions.add(&func.name, CompletionItemKind::FUNCTION, func_detail(func));
+    }
+    for name in Block::all_names() {
+        let signatures: Vec<String> = blocks_named(name).iter().map(block_signature).collect();
+        completions.add(name, CompletionItemKind::FUNCTION, signatures.join("\n"));
+    }
+    for name in Repr::all_names() {
+        let signatures: Vec<String> = reprs_named(name).iter().map(repr_signature).collect();
+        completions.add(name, CompletionItemKind::FUNCTION, signatures.join("\n"));
+    }
+    completions.items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lsp::workspace::{offset_to_position, Workspace},
+        misc::normalize_path,
+    };
+
+    #[test]
+    fn private_module_items_are_not_completed() {
+        let root = normalize_path(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/modules"));
+        let mut workspace = Workspace::default();
+        workspace.analyze(&root);
+        let path = root.join("main.gs");
+        let src = std::fs::read_to_string(&path).unwrap();
+        let position = offset_to_position(&src, src.find("say geo::distance").unwrap());
+        let analysis = workspace.analysis(&path).unwrap();
+        let labels: Vec<String> = completion(analysis, &path, position, &src)
+            .into_iter()
+            .map(|item| item.label)
+            .collect();
+        for public in ["geo::distance", "geo::move_by", "geo::Point", "geo::Side"] {
+            assert!(
+                labels.iter().any(|label| label == public),
+                "{public} missing"
+            );
+        }
+        for private in ["geo::square", "geo::measured", "geo::math::sign"] {
+            assert!(
+                !labels.iter().any(|label| label == private),
+                "{private} offered"
+            );
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind, Position};
+
+use super::workspace::{locate, position_to_offset, Analysis};
+use crate::blocks::{Block, Repr};
+
+pub fn block_signature(block: &Block) -> String {
+    format!("{} {}", block.name(), block.args().join(", "))
+        .trim_end()
+        .to_string()
+        + ";"
+}
+
+pub fn repr_signature(repr: &Repr) -> String {
+    format!("{}({})", repr.name(), repr.args().join(", "))
+}
+
+/// Every overload of the block `name`, as `overloads` only lists blocks which have more
+/// than one.
+pub fn blocks_named(name: &str) -> Vec<Block> {
+    match Block::overloads(name) {
+        [] => Block::from_shape(name, 0).into_iter().collect(),
+        overloads => overloads.to_vec(),
+    }
+}
+
+pub fn reprs_named(name: &str) -> Vec<Repr> {
+    match Repr::overloads(name) {
+        [] => Repr::from_shape(name, 0).into_iter().collect(),
+        overloads => overloads.to_vec(),
+    }
+}
+
+pub fn word_at(src: &str, offset: usize) -> &str {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let start = src[..offset]
+        .rfind(|c| !is_word(c))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let end = src[offset..]
+        .find(|c| !is_word(c))
+        .map(|i| offset + i)
+        .unwrap_or(src.len());
+    &src[start..end]
+}
+
+fn markdown(code: &str, description: &str) -> HoverContents {
+    HoverContents::Markup(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: format!("```goboscript\n{code}\n```\n{description}"),
+    })
+}
+
+pub fn hover(analysis: &Analysis, path: &Path, position: Position, src: &str) -> Option<Hover> {
+    for (unit, offset) in analysis.units_at(path, position) {
+        let Some(occurrence) = unit.index.occurrence_at(offset) else {
+            continue;
+        };
+        let definition = &unit.index.definitions[occurrence.definition];
+        let mut description = definition.kind.description().to_string();
+        if definition.in_stage && unit.name.is_some() {
+            description.push_str(", declared in `stage.gs`");
+        }
+        return Some(Hover {
+            contents: markdown(&definition.detail, &description),
+            range: locate(&unit.diagnostics.preproc, &occurrence.span).map(|span| span.lsp_range()),
+        });
+    }
+    let word = word_at(src, position_to_offset(src, position));
+    let blocks = blocks_named(word);
+    let reprs = reprs_named(word);
+    if blocks.is_empty() && reprs.is_empty() {
+        return None;
+    }
+    let signatures: Vec<String> = blocks
+        .iter()
+        .map(block_signature)
+        .chain(reprs.iter().map(repr_signature))
+        .collect();
+    let opcodes: Vec<String> = blocks
+        .iter()
+        .map(|block| format!("`{}`", block.opcode()))
+        .chain(reprs.iter().map(|repr| format!("`{}`", repr.opcode())))
+        .collect();
+    let description = if blocks.is_empty() {
+        "built-in reporter"
+    } else if reprs.is_empty() {
+        "built-in block"
+    } else {
+        "built-in block and reporter"
+    };
+    Some(Hover {
+        contents: markdown(
+            &signatures.join("\n"),
+            &format!("{description}, {}", opcodes.join(", ")),
+        ),
+        range: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lsp::workspace::{offset_to_position, Workspace},
+        misc::normalize_path,
+    };
+
+    fn hover_text(project: &str, file: &str, at: &str) -> Option<String> {
+        let root = normalize_path(&Path::new(env!("CARGO_MANIFEST_DIR")).join(project));
+        let mut workspace = Workspace::default();
+        workspace.analyze(&root);
+        let path = root.join(file);
+        let src = std::fs::read_to_string(&path).unwrap();
+        let position = offset_to_position(&src, src.find(at).unwrap());
+        let hover = hover(workspace.analysis(&path).unwrap(), &path, position, &src)?;
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("hover is not markdown");
+        };
+        Some(markup.value)
+    }
+
+    #[test]
+    fn hover_block() {
+        let text = hover_text("tests/motion", "main.gs", "move 10").unwrap();
+        assert!(text.contains("move STEPS;"), "{text}");
+        assert!(
+            text.contains("built-in block, `motion_movesteps`"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn hover_reporter() {
+        let text = hover_text("tests/sensing", "main.gs", "timer()").unwrap();
+        assert!(text.contains("timer()"), "{text}");
+        assert!(
+            text.contains("built-in reporter, `sensing_timer`"),
+            "{text}"
+        );
+    }
+}
@@ -0,0 +1,589 @@
+use fxhash::FxHashMap;
+use logos::Span;
+use smol_str::SmolStr;
+
+use crate::{ast::*, blocks::BinOp, misc::Rrc};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SymbolKind {
+    Proc,
+    Func,
+    Var,
+    Local,
+    List,
+    Struct,
+    StructField,
+    Enum,
+    EnumVariant,
+    Arg,
+}
+
+impl SymbolKind {
+    pub fn description(&self) -> &'static str {
+        match self {
+            SymbolKind::Proc => "procedure",
+            SymbolKind::Func => "function",
+            SymbolKind::Var => "variable",
+            SymbolKind::Local => "local variable",
+            SymbolKind::List => "list",
+            SymbolKind::Struct => "struct",
+            SymbolKind::StructField => "struct field",
+            SymbolKind::Enum => "enum",
+            SymbolKind::EnumVariant => "enum variant",
+            SymbolKind::Arg => "argument",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Definition {
+    pub kind: SymbolKind,
+    pub span: Span,
+    pub in_stage: bool,
+    pub detail: String,
+}
+
+#[derive(Debug)]
+pub struct Occurrence {
+    pub span: Span,
+    pub definition: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Index {
+    pub definitions: Vec<Definition>,
+    pub occurrences: Vec<Occurrence>,
+}
+
+impl Index {
+    pub fn new(sprite: &Sprite, stage: Option<&Sprite>) -> Self {
+        let mut index = Self::default();
+        let mut walker = Walker {
+            sprite,
+            stage,
+            locals: None,
+            args: &[],
+            index: &mut index,
+        };
+        walker.sprite();
+        index
+    }
+
+    pub fn occurrence_at(&self, offset: usize) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .filter(|occurrence| occurrence.span.start <= offset && offset <= occurrence.span.end)
+            .min_by_key(|occurrence| occurrence.span.len())
+    }
+
+    fn define(
+        &mut self,
+        kind: SymbolKind,
+        span: &Span,
+        in_stage: bool,
+        detail: impl FnOnce() -> String,
+    ) -> usize {
+        if let Some(index) = self.definitions.iter().position(|definition| {
+            definition.kind == kind && definition.in_stage == in_stage && definition.span == *span
+        }) {
+            return index;
+        }
+        self.definitions.push(Definition {
+            kind,
+            span: span.clone(),
+            in_stage,
+            detail: detail(),
+        });
+        self.definitions.len() - 1
+    }
+
+    fn occur(&mut self, span: &Span, definition: usize) {
+        if self
+            .occurrences
+            .iter()
+            .any(|occurrence| occurrence.span == *span && occurrence.definition == definition)
+        {
+            return;
+        }
+        self.occurrences.push(Occurrence {
+            span: span.clone(),
+            definition,
+        });
+    }
+}
+
+fn type_prefix(type_: &Type) -> String {
+    match type_ {
+        Type::Value => String::new(),
+        Type::Struct { name, .. } => format!("{name} "),
+    }
+}
+
+fn args_detail(args: &[Arg]) -> String {
+    args.iter()
+        .map(|arg| format!("{}{}", type_prefix(&arg.type_), arg.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn proc_detail(proc: &Proc) -> String {
+    let nowarp = if proc.warp { "" } else { "nowarp " };
+    format!("{nowarp}proc {} {}", proc.name, args_detail(&proc.args))
+        .trim_end()
+        .to_string()
+}
+
+pub fn func_detail(func: &Func) -> String {
+    format!(
+        "func {}{}({})",
+        type_prefix(&func.type_),
+        func.name,
+        args_detail(&func.args)
+    )
+}
+
+fn struct_detail(struct_: &Struct) -> String {
+    let fields: Vec<String> = struct_
+        .fields
+        .iter()
+        .map(|field| format!("    {}", field.name))
+        .collect();
+    format!("struct {} {{\n{}\n}}", struct_.name, fields.join(",\n"))
+}
+
+fn variant_value(variant: &EnumVariant) -> String {
+    match &variant.value {
+        Some((value, _)) => format!(" = {value}"),
+        None => String::new(),
+    }
+}
+
+fn enum_detail(enum_: &Enum) -> String {
+    let variants: Vec<String> = enum_
+        .variants
+        .iter()
+        .map(|variant| format!("    {}{}", variant.name, variant_value(variant)))
+        .collect();
+    format!("enum {} {{\n{}\n}}", enum_.name, variants.join(",\n"))
+}
+
+struct Walker<'a, 'b> {
+    sprite: &'a Sprite,
+    stage: Option<&'a Sprite>,
+    locals: Option<&'a FxHashMap<SmolStr, Var>>,
+    args: &'a [Arg],
+    index: &'b mut Index,
+}
+
+struct Resolved<'a> {
+    definition: usize,
+    struct_: Option<(&'a Struct, bool)>,
+}
+
+impl<'a> Walker<'a, '_> {
+    fn sprite(&mut self) {
+        let sprite = self.sprite;
+        for struct_ in sprite.structs.values() {
+            self.struct_definition(struct_, false);
+        }
+        for enum_ in sprite.enums.values() {
+            self.enum_definition(enum_, false);
+        }
+        for var in sprite.vars.values() {
+            self.var(var, SymbolKind::Var, false);
+        }
+        for list in sprite.lists.values() {
+            self.list(list, false);
+        }
+        for proc in sprite.procs.values() {
+            let definition = self
+                .index
+                .define(SymbolKind::Proc, &proc.span, false, || proc_detail(proc));
+            self.index.occur(&proc.span, definition);
+            self.locals = Some(&proc.locals);
+            self.args = &proc.args;
+            self.callable(&proc.args, &proc.locals, &proc.body);
+        }
+        for func in sprite.funcs.values() {
+            let definition = self
+                .index
+                .define(SymbolKind::Func, &func.span, false, || func_detail(func));
+            self.index.occur(&func.span, definition);
+            self.type_(&func.type_);
+            self.locals = Some(&func.locals);
+            self.args = &func.args;
+            self.callable(&func.args, &func.locals, &func.body);
+        }
+        self.locals = None;
+        self.args = &[];
+        for event in &sprite.events {
+            if let EventKind::OnLoudnessGt { value } | EventKind::OnTimerGt { value } = &event.kind
+            {
+                self.expr(value);
+            }
+            self.stmts(&event.body);
+        }
+        for test in &sprite.tests {
+            self.locals = Some(&test.locals);
+            self.callable(&[], &test.locals, &test.body);
+        }
+        self.locals = None;
+    }
+
+    fn callable(&mut self, args: &'a [Arg], locals: &'a FxHashMap<SmolStr, Var>, body: &[Stmt]) {
+        for arg in args {
+            self.type_(&arg.type_);
+            let definition = self.index.define(SymbolKind::Arg, &arg.span, false, || {
+                format!("{}{}", type_prefix(&arg.type_), arg.name)
+            });
+            self.index.occur(&arg.span, definition);
+        }
+        for local in locals.values() {
+            self.var(local, SymbolKind::Local, false);
+        }
+        self.stmts(body);
+    }
+
+    fn struct_definition(&mut self, struct_: &Struct, in_stage: bool) -> usize {
+        let definition = self
+            .index
+            .define(SymbolKind::Struct, &struct_.span, in_stage, || {
+                struct_detail(struct_)
+            });
+        if !in_stage {
+            self.index.occur(&struct_.span, definition);
+            for field in &struct_.fields {
+                let field_definition = self.field_definition(struct_, field, in_stage);
+                self.index.occur(&field.span, field_definition);
+            }
+        }
+        definition
+    }
+
+    fn field_definition(&mut self, struct_: &Struct, field: &StructField, in_stage: bool) -> usize {
+        self.index
+            .define(SymbolKind::StructField, &field.span, in_stage, || {
+                format!("{}.{}", struct_.name, field.name)
+            })
+    }
+
+    fn enum_definition(&mut self, enum_: &Enum, in_stage: bool) -> usize {
+        let definition = self
+            .index
+            .define(SymbolKind::Enum, &enum_.span, in_stage, || {
+                enum_detail(enum_)
+            });
+        if !in_stage {
+            self.index.occur(&enum_.span, definition);
+            for variant in &enum_.variants {
+                let variant_definition = self.variant_definition(enum_, variant, in_stage);
+                self.index.occur(&variant.span, variant_definition);
+            }
+        }
+        definition
+    }
+
+    fn variant_definition(&mut self, enum_: &Enum, variant: &EnumVariant, in_stage: bool) -> usize {
+        self.index
+            .define(SymbolKind::EnumVariant, &variant.span, in_stage, || {
+                format!("{}.{}{}", enum_.name, variant.name, variant_value(variant))
+            })
+    }
+
+    fn var(&mut self, var: &Var, kind: SymbolKind, in_stage: bool) -> usize {
+        let definition = self.index.define(kind, &var.span, in_stage, || {
+            let local = if kind == SymbolKind::Local {
+                "local "
+            } else {
+                ""
+            };
+            let cloud = if var.is_cloud { "cloud " } else { "" };
+            format!("{local}{cloud}{}{}", type_prefix(&var.type_), var.name)
+        });
+        if !in_stage {
+            self.index.occur(&var.span, definition);
+            self.type_(&var.type_);
+        }
+        definition
+    }
+
+    fn list(&mut self, list: &List, in_stage: bool) -> usize {
+        let definition = self
+            .index
+            .define(SymbolKind::List, &list.span, in_stage, || {
+                format!("list {}{}", type_prefix(&list.type_), list.name)
+            });
+        if !in_stage {
+            self.index.occur(&list.span, definition);
+            self.type_(&list.type_);
+        }
+        definition
+    }
+
+    fn get_struct(&mut self, name: &str) -> Option<(&'a Struct, bool)> {
+        if let Some(struct_) = self.sprite.structs.get(name) {
+            return Some((struct_, false));
+        }
+        self.stage
+            .and_then(|stage| stage.structs.get(name))
+            .map(|struct_| (struct_, true))
+    }
+
+    fn struct_of(&mut self, type_: &Type) -> Option<(&'a Struct, bool)> {
+        type_.struct_().and_then(|(name, _)| self.get_struct(name))
+    }
+
+    fn type_(&mut self, type_: &Type) {
+        let Type::Struct { name, span } = type_ else {
+            return;
+        };
+        if let Some((struct_, in_stage)) = self.get_struct(name) {
+            let definition = self.struct_definition(struct_, in_stage);
+            self.index.occur(span, definition);
+        }
+    }
+
+    fn resolve_name(&mut self, name: &str) -> Option<Resolved<'a>> {
+        if let Some(var) = self.locals.and_then(|locals| locals.get(name)) {
+            let struct_ = self.struct_of(&var.type_);
+            let definition = self.var(var, SymbolKind::Local, false);
+            return Some(Resolved {
+                definition,
+                struct_,
+            });
+        }
+        let scopes = [
+            Some((self.sprite, false)),
+            self.stage.map(|stage| (stage, true)),
+        ];
+        for (sprite, in_stage) in scopes.into_iter().flatten() {
+            if let Some(var) = sprite.vars.get(name) {
+                let struct_ = self.struct_of(&var.type_);
+                let definition = self.var(var, SymbolKind::Var, in_stage);
+                return Some(Resolved {
+                    definition,
+                    struct_,
+                });
+            }
+            if let Some(list) = sprite.lists.get(name) {
+                let struct_ = self.struct_of(&list.type_);
+                let definition = self.list(list, in_stage);
+                return Some(Resolved {
+                    definition,
+                    struct_,
+                });
+            }
+        }
+        None
+    }
+
+    fn field(&mut self, struct_: Option<(&Struct, bool)>, field: &str, span: &Span) {
+        let Some((struct_, in_stage)) = struct_ else {
+            return;
+        };
+        if let Some(struct_field) = struct_.fields.iter().find(|f| f.name == field) {
+            let definition = self.field_definition(struct_, struct_field, in_stage);
+            self.index.occur(span, definition);
+        }
+    }
+
+    fn name(&mut self, name: &Name) {
+        let Some(resolved) = self.resolve_name(name.basename()) else {
+            return;
+        };
+        self.index.occur(&name.basespan(), resolved.definition);
+        if let Some(field) = name.fieldname() {
+            self.field(resolved.struct_, field, &name.fieldspan());
+        }
+    }
+
+    fn arg(&mut self, name: &Name) -> Option<Option<(&'a Struct, bool)>> {
+        let arg = self.args.iter().find(|arg| arg.name == *name.basename())?;
+        let definition = self.index.define(SymbolKind::Arg, &arg.span, false, || {
+            format!("{}{}", type_prefix(&arg.type_), arg.name)
+        });
+        self.index.occur(&name.basespan(), definition);
+        Some(self.struct_of(&arg.type_))
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Repeat { times, body } => {
+                self.expr(times);
+                self.stmts(body);
+            }
+            Stmt::Forever { body, .. } => self.stmts(body),
+            Stmt::Branch {
+                cond,
+                if_body,
+                else_body,
+            } => {
+                self.expr(cond);
+                self.stmts(if_body);
+                self.stmts(else_body);
+            }
+            Stmt::Until { cond, body } => {
+                self.expr(cond);
+                self.stmts(body);
+            }
+            Stmt::SetVar {
+                name, value, type_, ..
+            } => {
+                self.type_(type_);
+                self.name(name);
+                self.expr(value);
+            }
+            Stmt::ChangeVar { name, value } => {
+                self.name(name);
+                self.expr(value);
+            }
+            Stmt::Show(name) | Stmt::Hide(name) | Stmt::DeleteList(name) => self.name(name),
+            Stmt::AddToList { name, value } => {
+                self.name(name);
+                self.expr(value);
+            }
+            Stmt::DeleteListIndex { name, index } => {
+                self.name(name);
+                self.expr(index);
+            }
+            Stmt::InsertAtList { name, index, value }
+            | Stmt::SetListIndex { name, index, value } => {
+                self.name(name);
+                self.expr(index);
+                self.expr(value);
+            }
+            Stmt::Block { args, .. } => {
+                for arg in args {
+                    self.expr(&arg.value);
+                }
+            }
+            Stmt::ProcCall { name, span, args } => {
+                if let Some(proc) = self.sprite.procs.get(name) {
+                    let definition = self
+                        .index
+                        .define(SymbolKind::Proc, &proc.span, false, || proc_detail(proc));
+                    self.index.occur(span, definition);
+                }
+                for arg in args {
+                    self.expr(&arg.value);
+                }
+            }
+            Stmt::FuncCall { name, span, args } => {
+                self.func_call(name, span);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Stmt::Return { value } => self.expr(value),
+            Stmt::Assert { cond, message, .. } => {
+                self.expr(cond);
+                if let Some(message) = message {
+                    self.expr(message);
+                }
+            }
+            Stmt::SetCallSite { .. } => {}
+        }
+    }
+
+    fn func_call(&mut self, name: &str, span: &Span) {
+        if let Some(func) = self.sprite.funcs.get(name) {
+            let definition = self
+                .index
+                .define(SymbolKind::Func, &func.span, false, || func_detail(func));
+            self.index.occur(span, definition);
+        }
+    }
+
+    fn get_enum(&self, name: &str) -> Option<(&'a Enum, bool)> {
+        if let Some(enum_) = self.sprite.enums.get(name) {
+            return Some((enum_, false));
+        }
+        self.stage
+            .and_then(|stage| stage.enums.get(name))
+            .map(|enum_| (enum_, true))
+    }
+
+    fn expr(&mut self, expr: &Rrc<Expr>) {
+        match &*expr.borrow() {
+            Expr::Value { .. } | Expr::CallSite { .. } => {}
+            Expr::Name(name) => {
+                if self.resolve_name(name.basename()).is_some() {
+                    self.name(name);
+                } else if let Some((enum_, in_stage)) = self.get_enum(name.basename()) {
+                    let definition = self.enum_definition(enum_, in_stage);
+                    self.index.occur(&name.basespan(), definition);
+                }
+            }
+            Expr::Arg(name) => {
+                self.arg(name);
+            }
+            Expr::Dot { lhs, rhs, rhs_span } => {
+                self.expr(lhs);
+                self.dot(&lhs.borrow(), rhs, rhs_span);
+            }
+            Expr::Repr { args, .. } => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::FuncCall { name, span, args } => {
+                self.func_call(name, span);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::UnOp { opr, .. } => self.expr(opr),
+            Expr::BinOp { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expr::StructLiteral { name, span, fields } => {
+                let struct_ = self.get_struct(name);
+                if let Some((struct_, in_stage)) = struct_ {
+                    let definition = self.struct_definition(struct_, in_stage);
+                    self.index.occur(span, definition);
+                }
+                for field in fields {
+                    self.field(struct_, &field.name, &field.span);
+                    self.expr(&field.value);
+                }
+            }
+        }
+    }
+
+    fn dot(&mut self, lhs: &Expr, rhs: &str, rhs_span: &Span) {
+        match lhs {
+            Expr::Name(Name::Name { name, .. }) => {
+                if let Some(resolved) = self.resolve_name(name) {
+                    self.field(resolved.struct_, rhs, rhs_span);
+                } else if let Some((enum_, in_stage)) = self.get_enum(name) {
+                    if let Some(variant) = enum_.variants.iter().find(|v| v.name == rhs) {
+                        let definition = self.variant_definition(enum_, variant, in_stage);
+                        self.index.occur(rhs_span, definition);
+                    }
+                }
+            }
+            Expr::Arg(name) => {
+                if let Some(struct_) = self.arg(name) {
+                    self.field(struct_, rhs, rhs_span);
+                }
+            }
+            Expr::BinOp {
+                op: BinOp::Of, lhs, ..
+            } => {
+                if let Expr::Name(Name::Name { name, .. }) = &*lhs.borrow() {
+                    if let Some(resolved) = self.resolve_name(name) {
+                        self.field(resolved.struct_, rhs, rhs_span);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::{ops::Range, path::Path};
+
+use fxhash::FxHashSet;
+use lsp_types::{Location, Position, Url};
+
+use super::workspace::{locate, Analysis, FileSpan};
+use crate::misc::normalize_path;
+
+type Key = (std::path::PathBuf, Range<usize>);
+
+fn key(span: &FileSpan) -> Key {
+    (normalize_path(span.path), span.range.clone())
+}
+
+fn location(span: &FileSpan) -> Option<Location> {
+    Some(Location::new(
+        Url::from_file_path(normalize_path(span.path)).ok()?,
+        span.lsp_range(),
+    ))
+}
+
+fn definition_at<'a>(
+    analysis: &'a Analysis,
+    path: &Path,
+    position: Position,
+) -> Option<FileSpan<'a>> {
+    for (unit, offset) in analysis.units_at(path, position) {
+        let Some(occurrence) = unit.index.occurrence_at(offset) else {
+            continue;
+        };
+        let definition = &unit.index.definitions[occurrence.definition];
+        if let Some(span) = analysis.locate_definition(&unit, definition) {
+            return Some(span);
+        }
+    }
+    None
+}
+
+pub fn definition(analysis: &Analysis, path: &Path, position: Position) -> Option<Location> {
+    location(&definition_at(analysis, path, position)?)
+}
+
+pub fn references(
+    analysis: &Analysis,
+    path: &Path,
+    position: Position,
+    include_declaration: bool,
+) -> Vec<Location> {
+    let Some(target) = definition_at(analysis, path, position).map(|span| key(&span)) else {
+        return vec![];
+    };
+    let mut seen: FxHashSet<Key> = FxHashSet::default();
+    let mut locations = vec![];
+    for unit in analysis.units() {
+        for occurrence in &unit.index.occurrences {
+            let definition = &unit.index.definitions[occurrence.definition];
+            let Some(definition_span) = analysis.locate_definition(&unit, definition) else {
+                continue;
+            };
+            if key(&definition_span) != target {
+                continue;
+            }
+            let Some(span) = locate(&unit.diagnostics.preproc, &occurrence.span) else {
+                continue;
+            };
+            let span_key = key(&span);
+            if !include_declaration && span_key == target {
+                continue;
+            }
+            if seen.insert(span_key) {
+                locations.extend(location(&span));
+            }
+        }
+    }
+    locations
+}
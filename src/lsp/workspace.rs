@@ -0,0 +1,326 @@
+use std::{
+    io::Cursor,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use annotate_snippets::Level;
+use fxhash::{FxHashMap, FxHashSet};
+use log::warn;
+use lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Position, Url,
+};
+use smol_str::SmolStr;
+
+use super::index::{Definition, Index};
+use crate::{
+    ast::Sprite,
+    config::CacheMode,
+    diagnostic::{DiagnosticKind, ProjectDiagnostics, SpriteDiagnostics},
+    frontend::build::{compile, load_config, parse_project, BuildError},
+    misc::normalize_path,
+    preproc::{Overlay, PreProc},
+    visitor,
+};
+
+/// Converts a byte offset into an LSP position, whose column is counted in UTF-16 code
+/// units.
+pub fn offset_to_position(src: &str, offset: usize) -> Position {
+    let before = &src[..offset.min(src.len())];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+pub fn position_to_offset(src: &str, position: Position) -> usize {
+    let mut offset = 0;
+    for (line_number, line) in src.split_inclusive('\n').enumerate() {
+        if line_number as u32 == position.line {
+            let mut column = 0;
+            for (i, ch) in line.char_indices() {
+                if column >= position.character || ch == '\n' {
+                    return offset + i;
+                }
+                column += ch.len_utf16() as u32;
+            }
+            return offset + line.len();
+        }
+        offset += line.len();
+    }
+    src.len()
+}
+
+pub struct FileSpan<'a> {
+    pub path: &'a Path,
+    pub src: &'a str,
+    pub range: Range<usize>,
+}
+
+impl FileSpan<'_> {
+    pub fn lsp_range(&self) -> lsp_types::Range {
+        lsp_types::Range::new(
+            offset_to_position(self.src, self.range.start),
+            offset_to_position(self.src, self.range.end),
+        )
+    }
+}
+
+/// Like [`PreProc::translate_position`], but does not panic on positions that do not
+/// belong to any file.
+pub fn locate<'a>(preproc: &'a PreProc, span: &Range<usize>) -> Option<FileSpan<'a>> {
+    let span = &preproc.call_site(span);
+    let include = preproc
+        .includes
+        .iter()
+        .find(|include| include.range.contains(&span.start))?;
+    let end = span.end.clamp(span.start, include.range.end);
+    Some(FileSpan {
+        path: &include.path,
+        src: &preproc.get_translation_unit()[include.range.clone()],
+        range: span.start - include.range.start..end - include.range.start,
+    })
+}
+
+pub struct Unit<'a> {
+    pub name: Option<&'a SmolStr>,
+    pub diagnostics: &'a SpriteDiagnostics,
+    pub sprite: &'a Sprite,
+    pub index: &'a Index,
+}
+
+pub struct Analysis {
+    pub parsed: ProjectDiagnostics,
+    pub indexes: FxHashMap<Option<SmolStr>, Index>,
+}
+
+impl Analysis {
+    fn new(parsed: ProjectDiagnostics) -> Self {
+        let project = &parsed.project;
+        let mut indexes = FxHashMap::default();
+        indexes.insert(None, Index::new(&project.stage, None));
+        for (name, sprite) in &project.sprites {
+            indexes.insert(Some(name.clone()), Index::new(sprite, Some(&project.stage)));
+        }
+        Self { parsed, indexes }
+    }
+
+    pub fn stage(&self) -> Unit<'_> {
+        Unit {
+            name: None,
+            diagnostics: &self.parsed.stage_diagnostics,
+            sprite: &self.parsed.project.stage,
+            index: &self.indexes[&None],
+        }
+    }
+
+    pub fn units(&self) -> impl Iterator<Item = Unit<'_>> {
+        let sprites = self
+            .parsed
+            .project
+            .sprites
+            .iter()
+            .map(|(name, sprite)| Unit {
+                name: Some(name),
+                diagnostics: &self.parsed.sprites_diagnostics[name],
+                sprite,
+                index: &self.indexes[&Some(name.clone())],
+            });
+        std::iter::once(self.stage()).chain(sprites)
+    }
+
+    pub fn units_at(&self, path: &Path, position: Position) -> Vec<(Unit<'_>, usize)> {
+        let mut units = vec![];
+        for unit in self.units() {
+            let preproc = &unit.diagnostics.preproc;
+            let Some(include) = preproc
+                .includes
+                .iter()
+                .find(|include| normalize_path(&include.path) == path)
+            else {
+                continue;
+            };
+            let src = &preproc.get_translation_unit()[include.range.clone()];
+            let offset = include.range.start + position_to_offset(src, position);
+            units.push((unit, offset));
+        }
+        units
+    }
+
+    pub fn locate_definition<'a>(
+        &'a self,
+        unit: &Unit<'a>,
+        definition: &Definition,
+    ) -> Option<FileSpan<'a>> {
+        let preproc = if definition.in_stage {
+            &self.parsed.stage_diagnostics.preproc
+        } else {
+            &unit.diagnostics.preproc
+        };
+        locate(preproc, &definition.span)
+    }
+
+    fn paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.units().flat_map(|unit| {
+            unit.diagnostics
+                .preproc
+                .includes
+                .iter()
+                .map(|include| normalize_path(&include.path))
+        })
+    }
+
+    fn watched(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        let embeds = self.units().flat_map(|unit| {
+            unit.diagnostics
+                .preproc
+                .embeds
+                .iter()
+                .map(|path| normalize_path(path))
+        });
+        self.paths().chain(embeds)
+    }
+}
+
+#[derive(Default)]
+pub struct Workspace {
+    pub documents: Rc<Overlay>,
+    pub projects: FxHashMap<PathBuf, Analysis>,
+    published: FxHashMap<PathBuf, FxHashSet<PathBuf>>,
+}
+
+impl Workspace {
+    pub fn project_root(&self, path: &Path) -> Option<PathBuf> {
+        for (root, analysis) in &self.projects {
+            if analysis.watched().any(|watched| watched == path) {
+                return Some(root.clone());
+            }
+        }
+        path.ancestors()
+            .skip(1)
+            .find(|dir| dir.join("stage.gs").is_file())
+            .map(Path::to_path_buf)
+    }
+
+    pub fn analysis(&self, path: &Path) -> Option<&Analysis> {
+        self.projects.get(&self.project_root(path)?)
+    }
+
+    pub fn analyze(&mut self, root: &Path) -> Vec<(PathBuf, Vec<lsp_types::Diagnostic>)> {
+        let overlay = self.documents.clone();
+        let parsed = panic::catch_unwind(AssertUnwindSafe(|| {
+            // An invalid goboscript.toml is reported by compiling the project below.
+            let config = load_config(root).unwrap_or_default();
+            let mut parsed = parse_project(root, overlay.clone(), &config)?;
+            visitor::pass0::visit_project(&mut parsed.project);
+            anyhow::Ok(parsed)
+        }));
+        match parsed {
+            Ok(Ok(parsed)) => {
+                // Keep the last analysis which parsed without errors, so that completions
+                // keep working while the user is typing.
+                if parsed.is_empty() || !self.projects.contains_key(root) {
+                    self.projects
+                        .insert(root.to_path_buf(), Analysis::new(parsed));
+                }
+            }
+            Ok(Err(err)) => warn!("failed to parse {}: {err:?}", root.display()),
+            Err(_) => warn!("panicked while parsing {}", root.display()),
+        }
+        let mut files: FxHashMap<PathBuf, Vec<lsp_types::Diagnostic>> = FxHashMap::default();
+        for path in self.published.remove(root).into_iter().flatten() {
+            files.insert(path, vec![]);
+        }
+        if let Some(analysis) = self.projects.get(root) {
+            for path in analysis.paths() {
+                files.insert(path, vec![]);
+            }
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            // Editing must never run a project's commands, or write to its directory.
+            compile(
+                root,
+                Cursor::new(Vec::new()),
+                overlay,
+                false,
+                true,
+                CacheMode::ReadOnly,
+                &[],
+            )
+        }));
+        match result {
+            Ok(Ok(_)) => {}
+            Ok(Err(BuildError::ProjectDiagnostics(diagnostics))) => {
+                let units = std::iter::once(&diagnostics.stage_diagnostics)
+                    .chain(diagnostics.sprites_diagnostics.values());
+                for sprite_diagnostics in units {
+                    for diagnostic in &sprite_diagnostics.diagnostics {
+                        // Commands are never run here, which is not the user's mistake.
+                        if matches!(diagnostic.kind, DiagnosticKind::CommandNotAllowed) {
+                            continue;
+                        }
+                        let Some(span) = locate(&sprite_diagnostics.preproc, &diagnostic.span)
+                        else {
+                            continue;
+                        };
+                        let mut message = diagnostic
+                            .kind
+                            .to_string(&diagnostics.project, sprite_diagnostics);
+                        if let Some(help) = diagnostic.kind.help(sprite_diagnostics) {
+                            message.push_str("\nhelp: ");
+                            message.push_str(&help);
+                        }
+                        let related_information = sprite_diagnostics
+                            .preproc
+                            .expansions_of(&diagnostic.span)
+                            .into_iter()
+                            .filter_map(|(expansion, definition)| {
+                                let definition = locate(&sprite_diagnostics.preproc, &definition?)?;
+                                Some(DiagnosticRelatedInformation {
+                                    location: Location::new(
+                                        Url::from_file_path(normalize_path(definition.path))
+                                            .ok()?,
+                                        definition.lsp_range(),
+                                    ),
+                                    message: format!(
+                                        "in the definition of macro `{}`",
+                                        expansion.name
+                                    ),
+                                })
+                            })
+                            .collect::<Vec<_>>();
+                        let severity = match Level::from(&diagnostic.kind) {
+                            Level::Error => DiagnosticSeverity::ERROR,
+                            Level::Warning => DiagnosticSeverity::WARNING,
+                            _ => DiagnosticSeverity::INFORMATION,
+                        };
+                        files.entry(normalize_path(span.path)).or_default().push(
+                            lsp_types::Diagnostic {
+                                range: span.lsp_range(),
+                                severity: Some(severity),
+                                code: Some(NumberOrString::String(
+                                    diagnostic.kind.code().to_string(),
+                                )),
+                                source: Some("goboscript".to_string()),
+                                message,
+                                related_information: (!related_information.is_empty())
+                                    .then_some(related_information),
+                                ..Default::default()
+                            },
+                        );
+                    }
+                }
+            }
+            Ok(Err(BuildError::AnyhowError(err))) => {
+                warn!("failed to build {}: {err:?}", root.display())
+            }
+            Err(_) => warn!("panicked while building {}", root.display()),
+        }
+        self.published
+            .insert(root.to_path_buf(), files.keys().cloned().collect());
+        files.into_iter().collect()
+    }
+}
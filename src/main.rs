@@ -2,12 +2,20 @@ mod ast;
 mod blocks;
 mod codegen;
 mod config;
+mod decompiler;
 mod diagnostic;
+mod formatter;
 mod frontend;
+mod imports;
+mod interpreter;
 mod lexer;
+mod lsp;
 mod misc;
 mod parser;
 mod preproc;
+mod sb3;
+mod stdlib;
+mod validator;
 mod visitor;
 use std::{process::ExitCode, time::Instant};
 
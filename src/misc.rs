@@ -1,22 +1,35 @@
-use core::fmt;
-use std::{cell::RefCell, io, rc::Rc};
+use std::{
+    cell::RefCell,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
 
-pub fn write_comma_io<T>(mut file: T, comma: &mut bool) -> io::Result<()>
-where T: io::Write {
-    if *comma {
-        file.write_all(b",")?;
-    }
-    *comma = true;
-    Ok(())
-}
+pub type Rrc<T> = Rc<RefCell<T>>;
 
-pub fn write_comma_fmt<T>(mut file: T, comma: &mut bool) -> fmt::Result
-where T: fmt::Write {
-    if *comma {
-        file.write_str(",")?;
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            _ => normalized.push(component),
+        }
     }
-    *comma = true;
-    Ok(())
+    normalized
 }
 
-pub type Rrc<T> = Rc<RefCell<T>>;
+/// Collects `items` sorted by `key`, which should be their position in the source, so
+/// that output does not depend on the iteration order of a hash map.
+pub fn in_source_order<I, K>(items: I, key: impl Fn(&I::Item) -> K) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    K: Ord,
+{
+    let mut items: Vec<I::Item> = items.into_iter().collect();
+    items.sort_by_key(key);
+    items
+}
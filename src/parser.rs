@@ -5,8 +5,19 @@ use crate::{ast::Sprite, diagnostic::Diagnostic, lexer::preproc, preproc::PrePro
 
 lalrpop_mod!(grammar, "/parser/grammar.rs");
 
-pub fn parse(preproc_: &PreProc) -> Result<Sprite, Diagnostic> {
-    let tokens = preproc::preproc(preproc_);
+pub fn parse(preproc_: &mut PreProc) -> Result<Sprite, Diagnostic> {
+    let mut embeds = vec![];
+    let (tokens, expansions) = preproc::preproc(preproc_, &mut embeds);
+    preproc_.expansions = expansions;
+    preproc_.embeds = embeds;
+    let offset = preproc_.offset;
+    let tokens = tokens.map_err(|err| Diagnostic {
+        span: err.span.start + offset..err.span.end + offset,
+        kind: err.kind,
+    })?;
+    let tokens = tokens
+        .into_iter()
+        .map(|(start, token, end)| (start + offset, token, end + offset));
     let parser = SpriteParser::new();
     let mut sprite = Sprite::default();
     parser.parse(&mut sprite, tokens)?;
@@ -1,11 +1,70 @@
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{self, BufReader, Read},
     ops::Range,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    rc::Rc,
 };
 
-use fxhash::FxHashMap;
+mod condition;
+
+use fxhash::{FxHashMap, FxHashSet};
+use glob::{glob, Pattern};
+
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticKind},
+    misc::normalize_path,
+    stdlib,
+};
+
+pub type Overlay = FxHashMap<PathBuf, String>;
+
+const DIRECTIVES: &[&str] = &[
+    "include", "define", "undef", "if", "ifdef", "ifndef", "elif", "else", "endif", "macro",
+    "endmacro",
+];
+
+fn line_end(src: &str, start: usize) -> usize {
+    src[start..]
+        .find('\n')
+        .map_or(src.len(), |length| start + length)
+}
+
+pub fn directive_end(src: &str, start: usize) -> Option<usize> {
+    let mut end = line_end(src, start);
+    let (name, _) = directive(&src[start..end])?;
+    if name == "macro" {
+        while end < src.len() {
+            let line_start = end + 1;
+            end = line_end(src, line_start);
+            if directive(&src[line_start..end]).is_some_and(|(name, _)| name == "endmacro") {
+                return Some(end);
+            }
+        }
+        return Some(end);
+    }
+    while src[start..end].trim_end().ends_with('\\') && end < src.len() {
+        end = line_end(src, end + 1);
+    }
+    Some(end)
+}
+
+fn offset(outer: &str, inner: &str) -> usize {
+    inner.as_ptr() as usize - outer.as_ptr() as usize
+}
+
+/// `text` with the `\` at the end of each line which is continued on the next line
+/// replaced by a space, so that positions in it are the same as in `text`.
+fn join_lines(text: &str) -> String {
+    text.replace("\\\n", " \n").replace("\\\r\n", " \r\n")
+}
+
+fn directive(line: &str) -> Option<(&'static str, &str)> {
+    let line = line.strip_prefix('%')?.trim_end();
+    let (name, rest) = line.split_once([' ', '\t']).unwrap_or((line, ""));
+    let name = DIRECTIVES.iter().find(|directive| **directive == name)?;
+    Some((name, rest))
+}
 
 #[derive(Debug)]
 pub struct Include {
@@ -19,19 +78,60 @@ pub struct Macro {
     pub substitution: String,
 }
 
+/// Code which a macro was substituted with. Its tokens are given positions after the end
+/// of the buffer, in `range`, so that each of them can be traced back to both where the
+/// macro is used and where it is defined.
+#[derive(Debug)]
+pub struct Expansion {
+    pub name: String,
+    pub range: Range<usize>,
+    pub call_site: Range<usize>,
+    pub definition: Option<Range<usize>>,
+}
+
+struct Condition {
+    directive: &'static str,
+    span: Range<usize>,
+    active: bool,
+    taken: bool,
+    enclosing: bool,
+    has_else: bool,
+}
+
 #[derive(Default)]
 pub struct PreProc {
     pub basepath: PathBuf,
+    /// Where the buffer starts in the positions of the sprite, which is after the code of
+    /// the files which import it for a module imported with `import`.
+    pub offset: usize,
+    pub include_paths: Vec<PathBuf>,
     pub buffer: Vec<u8>,
     pub includes: Vec<Include>,
     pub defines: FxHashMap<String, String>,
     pub macros: FxHashMap<String, Macro>,
+    pub hidden: Vec<Range<usize>>,
+    pub definitions: FxHashMap<String, Range<usize>>,
+    /// Names removed with `%undef`, which cannot be defined again, as macros are
+    /// substituted only once the whole sprite is preprocessed.
+    undefined: FxHashSet<String>,
+    pub expansions: Vec<Expansion>,
+    pub embeds: Vec<PathBuf>,
+    pub overlay: Rc<Overlay>,
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl PreProc {
-    pub fn new(basepath: PathBuf) -> Self {
+    pub fn new(
+        basepath: PathBuf,
+        overlay: Rc<Overlay>,
+        defines: FxHashMap<String, String>,
+        include_paths: Vec<PathBuf>,
+    ) -> Self {
         Self {
             basepath,
+            overlay,
+            defines,
+            include_paths,
             ..Default::default()
         }
     }
@@ -40,18 +140,34 @@ impl PreProc {
         std::str::from_utf8(&self.buffer).unwrap()
     }
 
-    pub fn include_relative(&mut self, path: PathBuf) -> io::Result<()> {
-        self.include(self.basepath.join(path))
+    /// The translation unit with every hidden line blanked, so that positions in it are
+    /// the same as in the buffer.
+    pub fn lexer_source(&self) -> String {
+        let mut source = self.buffer.clone();
+        for range in &self.hidden {
+            source[range.clone()].fill(b' ');
+        }
+        String::from_utf8(source).unwrap()
     }
 
     pub fn include(&mut self, path: PathBuf) -> io::Result<()> {
-        if self.includes.iter().any(|i| i.path == path) {
+        if self
+            .includes
+            .iter()
+            .any(|include| normalize_path(&include.path) == normalize_path(&path))
+        {
             return Ok(());
         }
         let length = self.buffer.len();
-        let file = File::open(&path)?;
-        let mut reader = BufReader::new(file);
-        reader.read_to_end(&mut self.buffer)?;
+        if let Some(src) = self.overlay.get(&normalize_path(&path)) {
+            self.buffer.extend_from_slice(src.as_bytes());
+        } else if let Some(src) = stdlib::file(&path) {
+            self.buffer.extend_from_slice(src.as_bytes());
+        } else {
+            let file = File::open(&path)?;
+            let mut reader = BufReader::new(file);
+            reader.read_to_end(&mut self.buffer)?;
+        }
         self.includes.push(Include {
             range: length..self.buffer.len(),
             path,
@@ -60,74 +176,372 @@ impl PreProc {
     }
 
     pub fn process(&mut self) -> io::Result<()> {
-        let mut includes: Vec<PathBuf> = vec![];
-        let mut line_start = true;
-        let mut i = 0;
-        while i < self.buffer.len() {
-            if line_start {
-                if self.buffer[i..].starts_with(b"%include ") {
-                    self.buffer[i] = b'#';
-                    i += b"%include ".len();
-                    let path = self.buffer[i..].split(|&c| c == b'\n').next().unwrap();
-                    i += path.len();
-                    let path = std::str::from_utf8(path).unwrap();
-                    let path = PathBuf::from(path);
-                    includes.push(path);
-                } else if self.buffer[i..].starts_with(b"%define ") {
-                    self.buffer[i] = b'#';
-                    i += b"%define ".len();
-                    let name = self.buffer[i..]
-                        .split(|&c| c == b' ' || c == b'(')
-                        .next()
-                        .unwrap();
-                    i += name.len();
-                    if self.buffer[i] == b'(' {
-                        i += 1;
-                        let mut args = vec![];
-                        while self.buffer[i] != b')' {
-                            if self.buffer[i] == b',' {
-                                i += 1;
+        let mut index = 0;
+        while index < self.includes.len() {
+            let range = self.includes[index].range.clone();
+            let directory = self.includes[index].path.parent().unwrap().to_path_buf();
+            for (path, span) in self.process_file(range) {
+                let paths = self.resolve(&directory, &path);
+                if paths.is_empty() {
+                    self.diagnostics.push(Diagnostic {
+                        kind: DiagnosticKind::FileNotFound(path.into()),
+                        span,
+                    });
+                }
+                for path in paths {
+                    self.include(path)?;
+                }
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+
+    pub fn resolve(&self, directory: &Path, path: &str) -> Vec<PathBuf> {
+        let is_glob = path.contains(['*', '?', '[']);
+        let directories = [directory, &self.basepath]
+            .into_iter()
+            .chain(self.include_paths.iter().map(PathBuf::as_path));
+        for directory in directories {
+            if !is_glob {
+                let path = normalize_path(&directory.join(path));
+                if self.overlay.contains_key(&path)
+                    || stdlib::file(&path).is_some()
+                    || path.is_file()
+                {
+                    return vec![path];
+                }
+                continue;
+            }
+            let Some(directory) = directory.to_str() else {
+                continue;
+            };
+            let pattern = Path::new(&Pattern::escape(directory)).join(path);
+            let Ok(paths) = glob(&pattern.to_string_lossy()) else {
+                return vec![];
+            };
+            let mut paths: Vec<PathBuf> = paths
+                .flatten()
+                .filter(|path| path.is_file())
+                .map(|path| normalize_path(&path))
+                .collect();
+            if !paths.is_empty() {
+                paths.sort();
+                return paths;
+            }
+        }
+        stdlib::resolve(path)
+    }
+
+    fn process_file(&mut self, range: Range<usize>) -> Vec<(String, Range<usize>)> {
+        let src = String::from_utf8_lossy(&self.buffer[range.clone()]).into_owned();
+        let mut includes = vec![];
+        let mut conditions: Vec<Condition> = vec![];
+        let mut start = 0;
+        while start < src.len() {
+            let end = directive_end(&src, start).unwrap_or_else(|| line_end(&src, start));
+            let text = &src[start..end];
+            let span = range.start + start..range.start + end;
+            let active = conditions.last().is_none_or(|condition| condition.active);
+            match directive(text) {
+                Some((name @ ("if" | "ifdef" | "ifndef" | "elif" | "else" | "endif"), rest)) => {
+                    self.condition(&mut conditions, name, &join_lines(rest), span.clone());
+                    self.hidden.push(span);
+                }
+                Some((name, rest)) if active => {
+                    match name {
+                        "include" => {
+                            let path = join_lines(rest);
+                            let start = span.start + offset(text, rest);
+                            let start = start + (path.len() - path.trim_start().len());
+                            let path = path.trim();
+                            includes.push((path.to_owned(), start..start + path.len()));
+                        }
+                        "define" | "macro" => {
+                            let defined = if name == "define" {
+                                Some(self.define_line(rest, span.start + offset(text, rest)))
+                            } else {
+                                self.define_block(text, span.start)
+                            };
+                            if let Some(defined) =
+                                defined.filter(|defined| self.undefined.contains(*defined))
+                            {
+                                self.report(
+                                    format!(
+                                        "{defined} is defined again after %undef, but a macro \
+                                         can only have one definition in a sprite"
+                                    ),
+                                    span.clone(),
+                                );
                             }
-                            let arg = self.buffer[i..]
-                                .split(|&c| c == b',' || c == b')')
-                                .next()
-                                .unwrap();
-                            i += arg.len();
-                            args.push(std::str::from_utf8(arg).unwrap().to_string());
                         }
-                        i += 1;
-                        let substitution = self.buffer[i..].split(|&c| c == b'\n').next().unwrap();
-                        i += substitution.len();
-                        let name = std::str::from_utf8(name).unwrap();
-                        let substitution = std::str::from_utf8(substitution).unwrap();
-                        self.macros.insert(
-                            name.to_string(),
-                            Macro {
-                                args,
-                                substitution: substitution.to_string(),
-                            },
-                        );
-                    } else {
-                        i += 1;
-                        let value = self.buffer[i..].split(|&c| c == b'\n').next().unwrap();
-                        i += value.len();
-                        let name = std::str::from_utf8(name).unwrap();
-                        let value = std::str::from_utf8(value).unwrap();
-                        self.defines.insert(name.to_string(), value.to_string());
+                        "endmacro" => {
+                            self.report("%endmacro without %macro".to_owned(), span.clone())
+                        }
+                        _ => {
+                            let name = join_lines(rest);
+                            self.defines.remove(name.trim());
+                            self.macros.remove(name.trim());
+                            self.definitions.remove(name.trim());
+                            self.undefined.insert(name.trim().to_owned());
+                        }
                     }
+                    self.hidden.push(span);
                 }
+                _ if !active => self.hidden.push(span),
+                _ => {}
             }
-            line_start = self.buffer[i] == b'\n';
-            i += 1;
+            start = end + 1;
         }
-        let is_includes_empty = includes.is_empty();
-        for include in includes {
-            self.include_relative(include)?;
+        for condition in conditions {
+            self.report(
+                format!("%{} is not closed by %endif", condition.directive),
+                condition.span,
+            );
         }
-        if !is_includes_empty {
-            self.process()?;
+        includes
+    }
+
+    fn condition(
+        &mut self,
+        conditions: &mut Vec<Condition>,
+        name: &'static str,
+        rest: &str,
+        span: Range<usize>,
+    ) {
+        let active = conditions.last().is_none_or(|condition| condition.active);
+        match name {
+            "if" | "ifdef" | "ifndef" => {
+                let value = active && self.evaluate(name, rest, &span);
+                conditions.push(Condition {
+                    directive: name,
+                    span,
+                    active: value,
+                    taken: value,
+                    enclosing: active,
+                    has_else: false,
+                });
+            }
+            _ => {
+                let Some(condition) = conditions.last_mut() else {
+                    self.report(format!("%{name} without %if"), span);
+                    return;
+                };
+                if name == "endif" {
+                    conditions.pop();
+                    return;
+                }
+                if condition.has_else {
+                    self.report(format!("%{name} after %else"), span);
+                    return;
+                }
+                let value = condition.enclosing
+                    && !condition.taken
+                    && (name == "else" || self.evaluate(name, rest, &span));
+                condition.active = value;
+                condition.taken |= value;
+                condition.has_else = name == "else";
+            }
         }
-        Ok(())
+    }
+
+    fn evaluate(&mut self, name: &str, rest: &str, span: &Range<usize>) -> bool {
+        let result = match name {
+            "ifdef" | "ifndef" => condition::name(rest)
+                .map(|macro_name| self.is_defined(macro_name) == (name == "ifdef")),
+            _ => condition::evaluate(rest, self),
+        };
+        result.unwrap_or_else(|reason| {
+            self.report(reason, span.clone());
+            false
+        })
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.defines.contains_key(name) || self.macros.contains_key(name)
+    }
+
+    fn define_line<'a>(&mut self, definition: &'a str, position: usize) -> &'a str {
+        let name_end = definition
+            .find([' ', '\t', '('])
+            .unwrap_or(definition.len());
+        let head_end = if definition[name_end..].starts_with('(') {
+            definition[name_end..]
+                .find(')')
+                .map_or(definition.len(), |length| name_end + length + 1)
+        } else {
+            name_end
+        };
+        let rest = &definition[head_end..];
+        let substitution = rest.strip_prefix([' ', '\t']).unwrap_or(rest);
+        self.define(
+            &definition[..head_end],
+            substitution,
+            position + offset(definition, substitution),
+        )
+    }
+
+    fn define_block<'a>(&mut self, text: &'a str, position: usize) -> Option<&'a str> {
+        let header_end = line_end(text, 0);
+        let (_, head) = directive(&text[..header_end]).unwrap();
+        let footer_start = text
+            .rfind('\n')
+            .filter(|&footer_start| footer_start >= header_end)
+            .filter(|&footer_start| {
+                directive(&text[footer_start + 1..]).is_some_and(|(name, _)| name == "endmacro")
+            });
+        let Some(footer_start) = footer_start else {
+            self.report(
+                "%macro is not closed by %endmacro".to_owned(),
+                position..position + header_end,
+            );
+            return None;
+        };
+        let body = &text[(header_end + 1).min(footer_start)..footer_start];
+        Some(self.define(head.trim(), body, position + offset(text, body)))
+    }
+
+    fn define<'a>(&mut self, head: &'a str, substitution: &str, position: usize) -> &'a str {
+        let joined = join_lines(substitution);
+        let name = match head.split_once('(') {
+            Some((name, args)) => {
+                let args = args.trim_end();
+                let args = args.strip_suffix(')').unwrap_or(args);
+                let args = if args.trim().is_empty() {
+                    vec![]
+                } else {
+                    args.split(',').map(|arg| arg.trim().to_owned()).collect()
+                };
+                self.macros.insert(
+                    name.to_owned(),
+                    Macro {
+                        args,
+                        substitution: joined,
+                    },
+                );
+                name
+            }
+            None => {
+                self.defines.insert(head.to_owned(), joined);
+                head
+            }
+        };
+        self.definitions
+            .insert(name.to_owned(), position..position + substitution.len());
+        name
+    }
+
+    pub fn end(&self) -> usize {
+        self.expansions
+            .iter()
+            .map(|expansion| expansion.range.end)
+            .fold(self.buffer.len(), usize::max)
+    }
+
+    /// Its macros are not added, as they are private to it.
+    pub fn append(&mut self, module: PreProc) {
+        let at = module.offset - self.offset;
+        let shift = |range: &Range<usize>| range.start + at..range.end + at;
+        self.buffer.resize(at, b' ');
+        self.buffer.extend(module.buffer);
+        self.includes
+            .extend(module.includes.into_iter().map(|include| Include {
+                range: shift(&include.range),
+                path: include.path,
+            }));
+        self.hidden.extend(module.hidden.iter().map(shift));
+        self.expansions
+            .extend(module.expansions.into_iter().map(|expansion| Expansion {
+                range: shift(&expansion.range),
+                call_site: shift(&expansion.call_site),
+                definition: expansion.definition.as_ref().map(shift),
+                name: expansion.name,
+            }));
+        self.embeds.extend(module.embeds);
+    }
+
+    pub fn expansion(&self, position: usize) -> Option<&Expansion> {
+        self.expansions
+            .iter()
+            .find(|expansion| expansion.range.contains(&position))
+    }
+
+    pub fn call_site(&self, span: &Range<usize>) -> Range<usize> {
+        let mut start = span.start;
+        while let Some(expansion) = self.expansion(start) {
+            start = expansion.call_site.start;
+        }
+        let mut end = span.end;
+        while let Some(expansion) = self
+            .expansions
+            .iter()
+            .find(|expansion| expansion.range.start < end && end <= expansion.range.end)
+        {
+            end = expansion.call_site.end;
+        }
+        start..end.max(start)
+    }
+
+    /// `text` with the names of `local`s declared in macros, which are renamed to
+    /// `name#id` for the expansion `id`, spelled as they are in the macro.
+    pub fn source_names(&self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(hash) = rest.find('#') {
+            let (before, after) = rest.split_at(hash);
+            output.push_str(before);
+            let digits = after[1..]
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(after.len() - 1);
+            let is_name = before
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_');
+            let is_expansion = after[1..1 + digits]
+                .parse::<usize>()
+                .is_ok_and(|id| (1..=self.expansions.len()).contains(&id));
+            if !(is_name && is_expansion) {
+                output.push('#');
+                rest = &after[1..];
+                continue;
+            }
+            rest = &after[1 + digits..];
+        }
+        output.push_str(rest);
+        output
+    }
+
+    pub fn expansions_of(&self, span: &Range<usize>) -> Vec<(&Expansion, Option<Range<usize>>)> {
+        let mut expansions = vec![];
+        let mut span = span.clone();
+        while let Some(expansion) = self.expansion(span.start) {
+            let end = span.end.min(expansion.range.end).max(span.start);
+            let definition = expansion.definition.as_ref().map(|definition| {
+                let start = definition.start + (span.start - expansion.range.start);
+                let end = definition.start + (end - expansion.range.start);
+                start.min(definition.end)..end.min(definition.end)
+            });
+            expansions.push((expansion, definition));
+            span = expansion.call_site.clone();
+        }
+        expansions
+    }
+
+    fn report(&mut self, reason: String, span: Range<usize>) {
+        self.diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::InvalidDirective(reason),
+            span,
+        });
+    }
+
+    pub fn embed(&self, position: usize, path: &str) -> (PathBuf, io::Result<Vec<u8>>) {
+        let (_, include) = self.translate_position(position);
+        let path = include.path.parent().unwrap().join(path);
+        let contents = match self.overlay.get(&normalize_path(&path)) {
+            Some(src) => Ok(src.as_bytes().to_vec()),
+            None => fs::read(&path),
+        };
+        (path, contents)
     }
 
     pub fn translate_position(&self, position: usize) -> (usize, &Include) {
@@ -0,0 +1,209 @@
+use super::PreProc;
+use crate::lexer::{adaptor::Lexer, token::Token};
+
+/// How many macros deep a condition may look into the values of macros which refer to
+/// other macros, before giving up on a macro which refers to itself.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    String(String),
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Number(number) => *number != 0.0,
+            Value::String(string) => !string.is_empty(),
+        }
+    }
+
+    fn from_bool(value: bool) -> Self {
+        Value::Number(if value { 1.0 } else { 0.0 })
+    }
+}
+
+pub fn name(rest: &str) -> Result<&str, String> {
+    let name = rest.trim();
+    let is_name = name.starts_with(|c: char| c == '_' || c.is_ascii_alphabetic())
+        && name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric());
+    if is_name {
+        Ok(name)
+    } else {
+        Err(format!("expected the name of a macro, not {name:?}"))
+    }
+}
+
+pub fn evaluate(condition: &str, preproc: &PreProc) -> Result<bool, String> {
+    Ok(expression(condition, preproc, 0)?.is_truthy())
+}
+
+fn expression(source: &str, preproc: &PreProc, depth: usize) -> Result<Value, String> {
+    let tokens = Lexer::new(source)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("{source:?} is not a valid condition"))?;
+    if tokens.is_empty() {
+        return Err("expected a condition".to_owned());
+    }
+    let mut parser = Parser {
+        source,
+        tokens,
+        position: 0,
+        preproc,
+        depth,
+    };
+    let value = parser.or()?;
+    match parser.tokens.get(parser.position) {
+        None => Ok(value),
+        Some(_) => Err(parser.unexpected(parser.position)),
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(usize, Token, usize)>,
+    position: usize,
+    preproc: &'a PreProc,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(_, token, _)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token, _)| token)
+    }
+
+    fn unexpected(&self, position: usize) -> String {
+        match self.tokens.get(position) {
+            Some((start, _, end)) => {
+                format!("unexpected `{}` in condition", &self.source[*start..*end])
+            }
+            None => "the condition ends too early".to_owned(),
+        }
+    }
+
+    fn eat(&mut self, matches: fn(&Token) -> bool) -> bool {
+        let eaten = self.peek().is_some_and(matches);
+        if eaten {
+            self.position += 1;
+        }
+        eaten
+    }
+
+    fn or(&mut self) -> Result<Value, String> {
+        let mut value = self.and()?;
+        while self.eat(|token| matches!(token, Token::Or)) {
+            let rhs = self.and()?;
+            value = Value::from_bool(value.is_truthy() || rhs.is_truthy());
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<Value, String> {
+        let mut value = self.not()?;
+        while self.eat(|token| matches!(token, Token::And)) {
+            let rhs = self.not()?;
+            value = Value::from_bool(value.is_truthy() && rhs.is_truthy());
+        }
+        Ok(value)
+    }
+
+    fn not(&mut self) -> Result<Value, String> {
+        if self.eat(|token| matches!(token, Token::Not)) {
+            return Ok(Value::from_bool(!self.not()?.is_truthy()));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Value, String> {
+        let lhs = self.unary()?;
+        let Some(operator) = self.peek().cloned() else {
+            return Ok(lhs);
+        };
+        if !matches!(
+            operator,
+            Token::Eq | Token::Ne | Token::Lt | Token::Gt | Token::Le | Token::Ge
+        ) {
+            return Ok(lhs);
+        }
+        self.position += 1;
+        let rhs = self.unary()?;
+        let ordering = match (&lhs, &rhs) {
+            (Value::Number(lhs), Value::Number(rhs)) => lhs.partial_cmp(rhs),
+            _ => Some(string(&lhs).cmp(&string(&rhs))),
+        };
+        let value = ordering.is_some_and(|ordering| match operator {
+            Token::Eq => ordering.is_eq(),
+            Token::Ne => ordering.is_ne(),
+            Token::Lt => ordering.is_lt(),
+            Token::Gt => ordering.is_gt(),
+            Token::Le => ordering.is_le(),
+            _ => ordering.is_ge(),
+        });
+        Ok(Value::from_bool(value))
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        if self.eat(|token| matches!(token, Token::Minus)) {
+            return match self.unary()? {
+                Value::Number(number) => Ok(Value::Number(-number)),
+                Value::String(string) => Err(format!("cannot negate {string:?}")),
+            };
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Value, String> {
+        match self.next() {
+            Some(Token::Int(int) | Token::Hex(int) | Token::Oct(int) | Token::Bin(int)) => {
+                Ok(Value::Number(int as f64))
+            }
+            Some(Token::Float(float)) => Ok(Value::Number(float)),
+            Some(Token::Str(string)) => Ok(Value::String(string.to_string())),
+            Some(Token::True) => Ok(Value::from_bool(true)),
+            Some(Token::False) => Ok(Value::from_bool(false)),
+            Some(Token::LParen) => {
+                let value = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(value),
+                    _ => Err("expected `)` in condition".to_owned()),
+                }
+            }
+            Some(Token::Name(name)) if name == "defined" => {
+                match (self.next(), self.next(), self.next()) {
+                    (Some(Token::LParen), Some(Token::Name(name)), Some(Token::RParen)) => {
+                        Ok(Value::from_bool(self.preproc.is_defined(&name)))
+                    }
+                    _ => Err("expected `defined(NAME)` in condition".to_owned()),
+                }
+            }
+            Some(Token::Name(name)) => match self.preproc.defines.get(name.as_str()) {
+                None => Ok(Value::from_bool(
+                    self.preproc.macros.contains_key(name.as_str()),
+                )),
+                Some(value) if value.trim().is_empty() => Ok(Value::from_bool(true)),
+                Some(_) if self.depth == MAX_DEPTH => {
+                    Err(format!("the value of {name} refers to itself"))
+                }
+                Some(value) => expression(value, self.preproc, self.depth + 1),
+            },
+            _ => Err(self.unexpected(self.position - 1)),
+        }
+    }
+}
+
+fn string(value: &Value) -> String {
+    match value {
+        Value::Number(number) => number.to_string(),
+        Value::String(string) => string.clone(),
+    }
+}
@@ -0,0 +1,326 @@
+mod block;
+
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use anyhow::Context;
+pub use block::{Block, Field, Input, InputValue, Mutation, Node, Primitive};
+use indexmap::IndexMap;
+use serde::{de, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use zip::ZipArchive;
+
+/// Every type in this module keeps the keys it does not know about in `extra`, so that a
+/// project which is read and written back is unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub targets: Vec<Target>,
+    #[serde(default)]
+    pub monitors: Vec<Monitor>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    pub meta: Meta,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Target {
+    pub is_stage: bool,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub comments: IndexMap<String, Comment>,
+    #[serde(default)]
+    pub variables: IndexMap<String, Variable>,
+    #[serde(default)]
+    pub lists: IndexMap<String, List>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub broadcasts: IndexMap<String, String>,
+    #[serde(default)]
+    pub blocks: IndexMap<String, Node>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_costume: Option<usize>,
+    #[serde(default)]
+    pub costumes: Vec<Costume>,
+    #[serde(default)]
+    pub sounds: Vec<Sound>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer_order: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f64>,
+    // Only the Stage has these.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tempo: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_transparency: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_to_speech_language: Option<String>,
+    // Only sprites have these.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub draggable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation_style: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Target {
+    pub fn block(&self, id: &str) -> Option<&Block> {
+        match self.blocks.get(id)? {
+            Node::Block(block) => Some(block),
+            Node::Primitive(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    pub block_id: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub minimized: bool,
+    pub text: String,
+}
+
+/// A variable, stored as `[name, value]`, or `[name, value, true]` for a cloud
+/// variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub value: Value,
+    pub is_cloud: bool,
+}
+
+impl Variable {
+    pub fn new(name: impl Into<String>, value: impl Into<Value>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            is_cloud: false,
+        }
+    }
+}
+
+impl Serialize for Variable {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        let mut seq = serializer.serialize_seq(Some(2 + self.is_cloud as usize))?;
+        seq.serialize_element(&self.name)?;
+        seq.serialize_element(&self.value)?;
+        if self.is_cloud {
+            seq.serialize_element(&true)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Variable {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let array = Vec::<Value>::deserialize(deserializer)?;
+        let Some(Value::String(name)) = array.first() else {
+            return Err(de::Error::custom("expected a variable"));
+        };
+        Ok(Self {
+            name: name.clone(),
+            value: array.get(1).cloned().unwrap_or(Value::Null),
+            is_cloud: array.get(2) == Some(&Value::Bool(true)),
+        })
+    }
+}
+
+/// A list, stored as `[name, items]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct List {
+    pub name: String,
+    pub items: Vec<Value>,
+}
+
+impl Serialize for List {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        (&self.name, &self.items).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for List {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let (name, items) = <(String, Vec<Value>)>::deserialize(deserializer)?;
+        Ok(Self { name, items })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Costume {
+    pub name: String,
+    pub asset_id: String,
+    pub data_format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub md5ext: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitmap_resolution: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation_center_x: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation_center_y: Option<f64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Costume {
+    pub fn md5ext(&self) -> String {
+        self.md5ext
+            .clone()
+            .unwrap_or_else(|| format!("{}.{}", self.asset_id, self.data_format))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sound {
+    pub name: String,
+    pub asset_id: String,
+    pub data_format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub md5ext: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_count: Option<f64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Sound {
+    pub fn md5ext(&self) -> String {
+        self.md5ext
+            .clone()
+            .unwrap_or_else(|| format!("{}.{}", self.asset_id, self.data_format))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorMode {
+    Default,
+    Large,
+    Slider,
+    List,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Monitor {
+    pub id: String,
+    pub mode: MonitorMode,
+    pub opcode: String,
+    #[serde(default)]
+    pub params: IndexMap<String, Value>,
+    pub sprite_name: Option<String>,
+    #[serde(default)]
+    pub value: Value,
+    #[serde(default)]
+    pub width: f64,
+    #[serde(default)]
+    pub height: f64,
+    #[serde(default)]
+    pub x: f64,
+    #[serde(default)]
+    pub y: f64,
+    #[serde(default)]
+    pub visible: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slider_min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slider_max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_discrete: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Meta {
+    pub semver: String,
+    #[serde(default)]
+    pub vm: String,
+    #[serde(default)]
+    pub agent: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+pub struct Archive {
+    pub project: Project,
+    pub assets: Vec<(String, Vec<u8>)>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let sb3 = fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+        Self::read(sb3).with_context(|| format!("could not read {}", path.display()))
+    }
+
+    pub fn read(sb3: Vec<u8>) -> anyhow::Result<Self> {
+        let mut archive = ZipArchive::new(Cursor::new(sb3))?;
+        let mut project = None;
+        let mut assets = Vec::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            if entry.name() == "project.json" {
+                project = Some(
+                    serde_json::from_slice(&contents)
+                        .context("project.json is not a Scratch 3 project")?,
+                );
+            } else {
+                assets.push((entry.name().to_owned(), contents));
+            }
+        }
+        assets.sort();
+        Ok(Self {
+            project: project.context("project.json is missing from the archive")?,
+            assets,
+        })
+    }
+
+    pub fn project_json(sb3: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        let mut archive = ZipArchive::new(Cursor::new(sb3))?;
+        let mut entry = archive
+            .by_name("project.json")
+            .ok()
+            .context("project.json is missing from the archive")?;
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+
+    pub fn asset(&self, md5ext: &str) -> Option<&[u8]> {
+        self.assets
+            .binary_search_by(|(name, _)| name.as_str().cmp(md5ext))
+            .ok()
+            .map(|i| self.assets[i].1.as_slice())
+    }
+}
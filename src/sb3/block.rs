@@ -0,0 +1,384 @@
+use indexmap::IndexMap;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Map, Value};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Node {
+    Block(Box<Block>),
+    Primitive(Primitive),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Block {
+    pub opcode: String,
+    pub next: Option<String>,
+    pub parent: Option<String>,
+    pub top_level: bool,
+    pub shadow: bool,
+    #[serde(default)]
+    pub inputs: IndexMap<String, Input>,
+    #[serde(default)]
+    pub fields: IndexMap<String, Field>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mutation: Option<Mutation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<f64>,
+}
+
+impl Block {
+    pub fn new(opcode: impl Into<String>) -> Self {
+        Self {
+            opcode: opcode.into(),
+            next: None,
+            parent: None,
+            top_level: false,
+            shadow: false,
+            inputs: IndexMap::new(),
+            fields: IndexMap::new(),
+            mutation: None,
+            comment: None,
+            x: None,
+            y: None,
+        }
+    }
+
+    pub fn input_block(&self, name: &str) -> Option<&str> {
+        match self.inputs.get(name)?.value()? {
+            InputValue::Block(id) => Some(id),
+            InputValue::Primitive(_) => None,
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name)?.value.as_str()
+    }
+
+    pub fn field_id(&self, name: &str) -> Option<&str> {
+        self.fields.get(name)?.id.as_deref()
+    }
+}
+
+/// An input of a block, stored as `[1, shadow]` if it holds its shadow, `[2, block]` if
+/// it holds a block without a shadow, such as a condition or a substack, and
+/// `[3, block, shadow]` if it holds a block which covers its shadow.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Shadow(Option<InputValue>),
+    NoShadow(Option<InputValue>),
+    Obscured(Option<InputValue>, Option<InputValue>),
+}
+
+impl Input {
+    pub fn value(&self) -> Option<&InputValue> {
+        match self {
+            Self::Shadow(value) | Self::NoShadow(value) | Self::Obscured(value, _) => {
+                value.as_ref()
+            }
+        }
+    }
+
+    pub fn shadow(&self) -> Option<&InputValue> {
+        match self {
+            Self::Shadow(shadow) | Self::Obscured(_, shadow) => shadow.as_ref(),
+            Self::NoShadow(_) => None,
+        }
+    }
+}
+
+impl Serialize for Input {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        match self {
+            Self::Shadow(shadow) => (1, shadow).serialize(serializer),
+            Self::NoShadow(value) => (2, value).serialize(serializer),
+            Self::Obscured(value, shadow) => (3, value, shadow).serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Input {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let array = Vec::<Value>::deserialize(deserializer)?;
+        let value = |i: usize| -> Result<Option<InputValue>, D::Error> {
+            match array.get(i) {
+                None | Some(Value::Null) => Ok(None),
+                Some(value) => InputValue::deserialize(value)
+                    .map(Some)
+                    .map_err(de::Error::custom),
+            }
+        };
+        match array.first().and_then(Value::as_u64) {
+            Some(1) => Ok(Self::Shadow(value(1)?)),
+            Some(2) => Ok(Self::NoShadow(value(1)?)),
+            Some(3) => Ok(Self::Obscured(value(1)?, value(2)?)),
+            _ => Err(de::Error::custom("expected an input")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InputValue {
+    Block(String),
+    Primitive(Primitive),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Primitive {
+    Number(Value),
+    PositiveNumber(Value),
+    PositiveInteger(Value),
+    Integer(Value),
+    Angle(Value),
+    Color(Value),
+    Text(Value),
+    Broadcast {
+        name: String,
+        id: String,
+    },
+    Variable {
+        name: String,
+        id: String,
+        position: Option<(f64, f64)>,
+    },
+    List {
+        name: String,
+        id: String,
+        position: Option<(f64, f64)>,
+    },
+}
+
+impl Primitive {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(Value::String(text.into()))
+    }
+
+    pub fn variable(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self::Variable {
+            id: name.clone(),
+            name,
+            position: None,
+        }
+    }
+
+    pub fn list(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self::List {
+            id: name.clone(),
+            name,
+            position: None,
+        }
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            Self::Number(_) => 4,
+            Self::PositiveNumber(_) => 5,
+            Self::PositiveInteger(_) => 6,
+            Self::Integer(_) => 7,
+            Self::Angle(_) => 8,
+            Self::Color(_) => 9,
+            Self::Text(_) => 10,
+            Self::Broadcast { .. } => 11,
+            Self::Variable { .. } => 12,
+            Self::List { .. } => 13,
+        }
+    }
+}
+
+impl Serialize for Primitive {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        let kind = self.kind();
+        match self {
+            Self::Number(value)
+            | Self::PositiveNumber(value)
+            | Self::PositiveInteger(value)
+            | Self::Integer(value)
+            | Self::Angle(value)
+            | Self::Color(value)
+            | Self::Text(value) => (kind, value).serialize(serializer),
+            Self::Broadcast { name, id } => (kind, name, id).serialize(serializer),
+            Self::Variable { name, id, position } | Self::List { name, id, position } => {
+                match position {
+                    Some((x, y)) => (kind, name, id, x, y).serialize(serializer),
+                    None => (kind, name, id).serialize(serializer),
+                }
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Primitive {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let array = Vec::<Value>::deserialize(deserializer)?;
+        let value = || array.get(1).cloned().unwrap_or(Value::Null);
+        let string = |i: usize| match array.get(i) {
+            Some(Value::String(string)) => string.clone(),
+            Some(value) if !value.is_null() => value.to_string(),
+            _ => String::new(),
+        };
+        let position = || Some((array.get(3)?.as_f64()?, array.get(4)?.as_f64()?));
+        Ok(match array.first().and_then(Value::as_u64) {
+            Some(4) => Self::Number(value()),
+            Some(5) => Self::PositiveNumber(value()),
+            Some(6) => Self::PositiveInteger(value()),
+            Some(7) => Self::Integer(value()),
+            Some(8) => Self::Angle(value()),
+            Some(9) => Self::Color(value()),
+            Some(10) => Self::Text(value()),
+            Some(11) => Self::Broadcast {
+                name: string(1),
+                id: string(2),
+            },
+            Some(12) => Self::Variable {
+                name: string(1),
+                id: string(2),
+                position: position(),
+            },
+            Some(13) => Self::List {
+                name: string(1),
+                id: string(2),
+                position: position(),
+            },
+            _ => return Err(de::Error::custom("expected a primitive")),
+        })
+    }
+}
+
+/// A field of a block, stored as `[value, id]`, where `id` is the ID of the variable,
+/// list or broadcast it refers to, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub value: Value,
+    pub id: Option<String>,
+}
+
+impl Field {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self {
+            value: Value::String(value.into()),
+            id: None,
+        }
+    }
+
+    pub fn with_id(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            value: Value::String(name.clone()),
+            id: Some(name),
+        }
+    }
+}
+
+impl Serialize for Field {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        (&self.value, &self.id).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let array = Vec::<Value>::deserialize(deserializer)?;
+        Ok(Self {
+            value: array.first().cloned().unwrap_or(Value::Null),
+            id: array.get(1).and_then(Value::as_str).map(str::to_owned),
+        })
+    }
+}
+
+/// The mutation of a procedure's prototype, or of a call to it. Scratch stores the
+/// argument lists as JSON inside strings, and `warp` as a string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mutation {
+    pub tag_name: String,
+    #[serde(default)]
+    pub children: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "bool_string")]
+    pub warp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proccode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "json_string")]
+    pub argumentids: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "json_string")]
+    pub argumentnames: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "json_string")]
+    pub argumentdefaults: Option<Vec<Value>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for Mutation {
+    fn default() -> Self {
+        Self {
+            tag_name: "mutation".to_owned(),
+            children: Vec::new(),
+            warp: None,
+            proccode: None,
+            argumentids: None,
+            argumentnames: None,
+            argumentdefaults: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+mod bool_string {
+    use super::*;
+
+    pub fn serialize<S>(value: &Option<bool>, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        value.map(|value| value.to_string()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+    where D: Deserializer<'de> {
+        Ok(match Value::deserialize(deserializer)? {
+            Value::Bool(value) => Some(value),
+            Value::String(value) => Some(value == "true"),
+            _ => None,
+        })
+    }
+}
+
+mod json_string {
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        value
+            .as_ref()
+            .map(|value| json!(value).to_string())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: DeserializeOwned,
+        D: Deserializer<'de>,
+    {
+        match Value::deserialize(deserializer)? {
+            Value::String(json) => serde_json::from_str(&json)
+                .map(Some)
+                .map_err(de::Error::custom),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::path::{Path, PathBuf};
+
+use fxhash::FxHashSet;
+use glob::Pattern;
+use smol_str::SmolStr;
+
+use crate::{
+    ast::{Expr, Sprite, Stmt},
+    misc::Rrc,
+    preproc::PreProc,
+};
+
+pub const ROOT: &str = "<std>";
+
+const FILES: &[(&str, &str)] = &[
+    ("cloud.gs", include_str!("../std/cloud.gs")),
+    ("list.gs", include_str!("../std/list.gs")),
+    ("math.gs", include_str!("../std/math.gs")),
+    ("string.gs", include_str!("../std/string.gs")),
+];
+
+pub fn file(path: &Path) -> Option<&'static str> {
+    let name = path.strip_prefix(ROOT).ok()?.to_str()?;
+    FILES
+        .iter()
+        .find(|(file, _)| *file == name)
+        .map(|(_, src)| *src)
+}
+
+pub fn resolve(path: &str) -> Vec<PathBuf> {
+    let Some(name) = path.strip_prefix("std/") else {
+        return vec![];
+    };
+    if let Ok(pattern) = Pattern::new(name) {
+        if name.contains(['*', '?', '[']) {
+            return FILES
+                .iter()
+                .filter(|(file, _)| pattern.matches(file))
+                .map(|(file, _)| Path::new(ROOT).join(file))
+                .collect();
+        }
+    }
+    let path = Path::new(ROOT).join(name);
+    let path = if path.extension().is_none() {
+        path.with_extension("gs")
+    } else {
+        path
+    };
+    if file(&path).is_some() {
+        vec![path]
+    } else {
+        vec![]
+    }
+}
+
+/// Modules must have been imported first, as they may use it.
+pub fn prune(sprite: &mut Sprite, preproc: &PreProc) {
+    let is_std = |start: usize| {
+        preproc
+            .includes
+            .iter()
+            .any(|include| include.range.contains(&start) && include.path.starts_with(ROOT))
+    };
+    let mut used = FxHashSet::default();
+    let mut pending: Vec<&[Stmt]> = vec![];
+    for proc in sprite.procs.values() {
+        if !is_std(proc.span.start) {
+            pending.push(&proc.body);
+        }
+    }
+    for func in sprite.funcs.values() {
+        if !is_std(func.span.start) {
+            pending.push(&func.body);
+        }
+    }
+    for event in &sprite.events {
+        pending.push(&event.body);
+    }
+    for test in &sprite.tests {
+        pending.push(&test.body);
+    }
+    while let Some(stmts) = pending.pop() {
+        let mut calls = vec![];
+        for stmt in stmts {
+            stmt_calls(stmt, &mut calls);
+        }
+        for name in calls {
+            if !used.insert(name.clone()) {
+                continue;
+            }
+            if let Some(proc) = sprite.procs.get(&name) {
+                pending.push(&proc.body);
+            }
+            if let Some(func) = sprite.funcs.get(&name) {
+                pending.push(&func.body);
+            }
+        }
+    }
+    sprite
+        .procs
+        .retain(|name, proc| used.contains(name) || !is_std(proc.span.start));
+    sprite
+        .funcs
+        .retain(|name, func| used.contains(name) || !is_std(func.span.start));
+}
+
+fn stmt_calls(stmt: &Stmt, calls: &mut Vec<SmolStr>) {
+    match stmt {
+        Stmt::Repeat { times, body } => {
+            expr_calls(times, calls);
+            body.iter().for_each(|stmt| stmt_calls(stmt, calls));
+        }
+        Stmt::Forever { body, .. } => {
+            body.iter().for_each(|stmt| stmt_calls(stmt, calls));
+        }
+        Stmt::Branch {
+            cond,
+            if_body,
+            else_body,
+        } => {
+            expr_calls(cond, calls);
+            if_body.iter().for_each(|stmt| stmt_calls(stmt, calls));
+            else_body.iter().for_each(|stmt| stmt_calls(stmt, calls));
+        }
+        Stmt::Until { cond, body } => {
+            expr_calls(cond, calls);
+            body.iter().for_each(|stmt| stmt_calls(stmt, calls));
+        }
+        Stmt::SetVar { value, .. }
+        | Stmt::ChangeVar { value, .. }
+        | Stmt::AddToList { value, .. }
+        | Stmt::Return { value } => expr_calls(value, calls),
+        Stmt::DeleteListIndex { index, .. } => expr_calls(index, calls),
+        Stmt::InsertAtList { index, value, .. } | Stmt::SetListIndex { index, value, .. } => {
+            expr_calls(index, calls);
+            expr_calls(value, calls);
+        }
+        Stmt::Block { args, .. } => {
+            args.iter().for_each(|arg| expr_calls(&arg.value, calls));
+        }
+        Stmt::ProcCall { name, args, .. } => {
+            calls.push(name.clone());
+            args.iter().for_each(|arg| expr_calls(&arg.value, calls));
+        }
+        Stmt::FuncCall { name, args, .. } => {
+            calls.push(name.clone());
+            args.iter().for_each(|arg| expr_calls(arg, calls));
+        }
+        Stmt::Assert { cond, message, .. } => {
+            expr_calls(cond, calls);
+            if let Some(message) = message {
+                expr_calls(message, calls);
+            }
+        }
+        Stmt::SetCallSite { .. } | Stmt::Show(_) | Stmt::Hide(_) | Stmt::DeleteList(_) => {}
+    }
+}
+
+fn expr_calls(expr: &Rrc<Expr>, calls: &mut Vec<SmolStr>) {
+    match &*expr.borrow() {
+        Expr::Dot { lhs, .. } => expr_calls(lhs, calls),
+        Expr::Repr { args, .. } => args.iter().for_each(|arg| expr_calls(arg, calls)),
+        Expr::FuncCall { name, args, .. } => {
+            calls.push(name.clone());
+            args.iter().for_each(|arg| expr_calls(arg, calls));
+        }
+        Expr::UnOp { opr, .. } => expr_calls(opr, calls),
+        Expr::BinOp { lhs, rhs, .. } => {
+            expr_calls(lhs, calls);
+            expr_calls(rhs, calls);
+        }
+        Expr::StructLiteral { fields, .. } => {
+            fields
+                .iter()
+                .for_each(|field| expr_calls(&field.value, calls));
+        }
+        Expr::Value { .. } | Expr::Name(_) | Expr::CallSite { .. } | Expr::Arg(_) => {}
+    }
+}
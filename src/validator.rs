@@ -0,0 +1,278 @@
+mod schema;
+
+use std::fmt::{self, Display};
+
+use fxhash::FxHashSet;
+use schema::Schema;
+use serde_json::Value;
+
+use crate::sb3::{Block, InputValue, Node, Primitive, Project, Target};
+
+pub struct Problem {
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "project.json {}", self.message)
+        } else {
+            write!(f, "{} {}", self.path, self.message)
+        }
+    }
+}
+
+pub fn validate(json: &[u8]) -> Vec<Problem> {
+    let value: Value = match serde_json::from_slice(json) {
+        Ok(value) => value,
+        Err(err) => {
+            return vec![Problem {
+                path: String::new(),
+                message: format!("is not valid JSON: {err}"),
+            }]
+        }
+    };
+    let mut problems = Vec::new();
+    Schema::new().check(&value, &mut problems);
+    match serde_json::from_value::<Project>(value) {
+        Ok(project) => check_references(&project, &mut problems),
+        // The schema has already said what is wrong with it.
+        Err(_) if !problems.is_empty() => {}
+        Err(err) => problems.push(Problem {
+            path: String::new(),
+            message: format!("is not a Scratch 3 project: {err}"),
+        }),
+    }
+    problems
+}
+
+fn pointer(path: &[String]) -> String {
+    let mut pointer = String::new();
+    for part in path {
+        pointer.push('/');
+        pointer.push_str(&part.replace('~', "~0").replace('/', "~1"));
+    }
+    pointer
+}
+
+fn check_references(project: &Project, problems: &mut Vec<Problem>) {
+    let stage = project.targets.iter().find(|target| target.is_stage);
+    for (index, target) in project.targets.iter().enumerate() {
+        let check = References {
+            target,
+            stage,
+            path: vec!["targets".to_owned(), index.to_string(), "blocks".to_owned()],
+        };
+        for (id, node) in &target.blocks {
+            match node {
+                Node::Block(block) => check.block(id, block, problems),
+                Node::Primitive(primitive) => check.primitive(primitive, &[id.as_str()], problems),
+            }
+        }
+    }
+}
+
+struct References<'a> {
+    target: &'a Target,
+    stage: Option<&'a Target>,
+    path: Vec<String>,
+}
+
+impl References<'_> {
+    fn problem(&self, at: &[&str], message: String) -> Problem {
+        let mut path = self.path.clone();
+        path.extend(at.iter().map(|part| part.to_string()));
+        Problem {
+            path: pointer(&path),
+            message,
+        }
+    }
+
+    fn block(&self, id: &str, block: &Block, problems: &mut Vec<Problem>) {
+        let blocks = &self.target.blocks;
+        if let Some(parent) = &block.parent {
+            if !blocks.contains_key(parent) {
+                problems.push(self.problem(
+                    &[id, "parent"],
+                    format!("refers to block {parent:?}, which does not exist"),
+                ));
+            }
+        }
+        if let Some(next) = &block.next {
+            match self.target.block(next) {
+                None => problems.push(self.problem(
+                    &[id, "next"],
+                    format!("refers to block {next:?}, which does not exist"),
+                )),
+                Some(after) if after.parent.as_deref() != Some(id) => problems.push(self.problem(
+                    &[id, "next"],
+                    format!("refers to block {next:?}, whose parent is not this block"),
+                )),
+                Some(_) => {}
+            }
+        }
+        for (name, input) in &block.inputs {
+            let shadow = input
+                .shadow()
+                .filter(|&shadow| Some(shadow) != input.value());
+            for value in [input.value(), shadow].into_iter().flatten() {
+                match value {
+                    InputValue::Block(input_id) if !blocks.contains_key(input_id) => {
+                        problems.push(self.problem(
+                            &[id, "inputs", name],
+                            format!("refers to block {input_id:?}, which does not exist"),
+                        ))
+                    }
+                    InputValue::Block(_) => {}
+                    InputValue::Primitive(primitive) => {
+                        self.primitive(primitive, &[id, "inputs", name], problems)
+                    }
+                }
+            }
+        }
+        if let Some(var) = block.field_id("VARIABLE") {
+            if !self.is_var(var) {
+                problems.push(self.problem(
+                    &[id, "fields", "VARIABLE"],
+                    format!("refers to variable {var:?}, which is not declared"),
+                ));
+            }
+        }
+        if let Some(list) = block.field_id("LIST") {
+            if !self.is_list(list) {
+                problems.push(self.problem(
+                    &[id, "fields", "LIST"],
+                    format!("refers to list {list:?}, which is not declared"),
+                ));
+            }
+        }
+        match block.opcode.as_str() {
+            "procedures_definition" => {
+                let prototype = block
+                    .input_block("custom_block")
+                    .and_then(|prototype| self.target.block(prototype));
+                if prototype.is_none_or(|prototype| prototype.opcode != "procedures_prototype") {
+                    problems.push(self.problem(
+                        &[id, "inputs", "custom_block"],
+                        "does not refer to a procedures_prototype block".to_owned(),
+                    ));
+                }
+            }
+            "procedures_prototype" => self.prototype(id, block, problems),
+            "procedures_call" => self.call(id, block, problems),
+            _ => {}
+        }
+    }
+
+    fn primitive(&self, primitive: &Primitive, at: &[&str], problems: &mut Vec<Problem>) {
+        match primitive {
+            Primitive::Variable { id, .. } if !self.is_var(id) => problems.push(self.problem(
+                at,
+                format!("refers to variable {id:?}, which is not declared"),
+            )),
+            Primitive::List { id, .. } if !self.is_list(id) => problems
+                .push(self.problem(at, format!("refers to list {id:?}, which is not declared"))),
+            _ => {}
+        }
+    }
+
+    fn is_var(&self, id: &str) -> bool {
+        [Some(self.target), self.stage]
+            .into_iter()
+            .flatten()
+            .any(|target| target.variables.contains_key(id))
+    }
+
+    fn is_list(&self, id: &str) -> bool {
+        [Some(self.target), self.stage]
+            .into_iter()
+            .flatten()
+            .any(|target| target.lists.contains_key(id))
+    }
+
+    fn prototype(&self, id: &str, block: &Block, problems: &mut Vec<Problem>) {
+        let Some(mutation) = &block.mutation else {
+            problems.push(self.problem(&[id], "is a procedure without a mutation".to_owned()));
+            return;
+        };
+        let proccode = mutation.proccode.as_deref().unwrap_or_default();
+        let args = arg_count(proccode);
+        let lists = [
+            ("argumentids", mutation.argumentids.as_ref().map(Vec::len)),
+            (
+                "argumentnames",
+                mutation.argumentnames.as_ref().map(Vec::len),
+            ),
+            (
+                "argumentdefaults",
+                mutation.argumentdefaults.as_ref().map(Vec::len),
+            ),
+        ];
+        for (name, len) in lists {
+            let len = len.unwrap_or_default();
+            if len != args {
+                problems.push(self.problem(
+                    &[id, "mutation", name],
+                    format!("has {len} items, but the proccode {proccode:?} has {args} arguments"),
+                ));
+            }
+        }
+        for arg in mutation.argumentids.iter().flatten() {
+            if !block.inputs.contains_key(arg) {
+                problems.push(
+                    self.problem(&[id, "inputs"], format!("is missing the argument {arg:?}")),
+                );
+            }
+        }
+    }
+
+    fn call(&self, id: &str, block: &Block, problems: &mut Vec<Problem>) {
+        let Some(mutation) = &block.mutation else {
+            problems.push(self.problem(&[id], "is a procedure without a mutation".to_owned()));
+            return;
+        };
+        let proccode = mutation.proccode.as_deref().unwrap_or_default();
+        let prototype = self.target.blocks.values().find_map(|node| match node {
+            Node::Block(prototype) if prototype.opcode == "procedures_prototype" => prototype
+                .mutation
+                .as_ref()
+                .filter(|prototype| prototype.proccode.as_deref() == Some(proccode)),
+            _ => None,
+        });
+        let Some(prototype) = prototype else {
+            problems.push(self.problem(
+                &[id, "mutation", "proccode"],
+                format!("is {proccode:?}, which is not defined in this sprite"),
+            ));
+            return;
+        };
+        let arg_ids = mutation.argumentids.as_deref().unwrap_or_default();
+        if arg_ids != prototype.argumentids.as_deref().unwrap_or_default() {
+            problems.push(self.problem(
+                &[id, "mutation", "argumentids"],
+                format!(
+                    "is {arg_ids:?}, which does not match {:?} of the definition of \
+                     {proccode:?}",
+                    prototype.argumentids.as_deref().unwrap_or_default()
+                ),
+            ));
+        }
+        let arg_ids: FxHashSet<&str> = arg_ids.iter().map(String::as_str).collect();
+        for name in block.inputs.keys() {
+            if !arg_ids.contains(name.as_str()) {
+                problems.push(self.problem(
+                    &[id, "inputs", name],
+                    "is not an argument of the procedure".to_owned(),
+                ));
+            }
+        }
+    }
+}
+
+fn arg_count(proccode: &str) -> usize {
+    proccode
+        .split(' ')
+        .filter(|word| matches!(*word, "%s" | "%n" | "%b"))
+        .count()
+}
@@ -0,0 +1,305 @@
+use std::cell::RefCell;
+
+use fxhash::FxHashMap;
+use regex::Regex;
+use serde_json::Value;
+
+use super::{pointer, Problem};
+
+const SCHEMA: &str = include_str!("../../tools/sb3_schema.json");
+const DEFINITIONS: &str = include_str!("../../tools/sb3_definitions.json");
+const DEFINITIONS_FILE: &str = "sb3_definitions.json";
+
+/// The Scratch 3 project schema from `tools/`, which is the schema Scratch itself loads
+/// projects with. Only the keywords these files use are supported.
+pub struct Schema {
+    schema: Value,
+    definitions: Value,
+    patterns: RefCell<FxHashMap<String, Regex>>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self {
+            schema: serde_json::from_str(SCHEMA).unwrap(),
+            definitions: serde_json::from_str(DEFINITIONS).unwrap(),
+            patterns: Default::default(),
+        }
+    }
+
+    pub fn check(&self, project: &Value, problems: &mut Vec<Problem>) {
+        self.check_value(
+            &self.schema,
+            &self.schema,
+            project,
+            &mut Vec::new(),
+            problems,
+        );
+    }
+
+    fn check_value<'a>(
+        &'a self,
+        doc: &'a Value,
+        schema: &'a Value,
+        value: &Value,
+        path: &mut Vec<String>,
+        problems: &mut Vec<Problem>,
+    ) {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let (file, fragment) = reference.split_once('#').unwrap_or((reference, ""));
+            let doc = if file == DEFINITIONS_FILE {
+                &self.definitions
+            } else {
+                doc
+            };
+            let schema = doc
+                .pointer(fragment)
+                .unwrap_or_else(|| panic!("{reference} is not in the schema"));
+            self.check_value(doc, schema, value, path, problems);
+            return;
+        }
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                _ => types.as_str().into_iter().collect(),
+            };
+            if !types.iter().any(|type_| has_type(value, type_)) {
+                problems.push(problem(path, format!("is not {}", article(&types))));
+                return;
+            }
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                let message = match values.as_slice() {
+                    [only] => format!("is {value}, but must be {only}"),
+                    _ => {
+                        let values: Vec<String> = values.iter().map(Value::to_string).collect();
+                        format!("is {value}, which is not one of {}", values.join(", "))
+                    }
+                };
+                problems.push(problem(path, message));
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.matches(doc, not, value, path) {
+                problems.push(problem(path, format!("must not be {value}")));
+            }
+        }
+        for all in schema["allOf"].as_array().into_iter().flatten() {
+            self.check_value(doc, all, value, path, problems);
+        }
+        if let Some(one_of) = schema["oneOf"].as_array() {
+            self.check_one_of(doc, one_of, value, path, problems);
+        }
+        if let Some(min) = schema["minimum"].as_f64() {
+            if value.as_f64().is_some_and(|number| number < min) {
+                problems.push(problem(path, format!("is less than {min}")));
+            }
+        }
+        if let Value::String(string) = value {
+            self.check_string(schema, string, path, problems);
+        }
+        if let Value::Array(items) = value {
+            self.check_array(doc, schema, items, path, problems);
+        }
+        if let Value::Object(object) = value {
+            for name in schema["required"].as_array().into_iter().flatten() {
+                let name = name.as_str().unwrap();
+                if !object.contains_key(name) {
+                    problems.push(problem(path, format!("is missing {name:?}")));
+                }
+            }
+            let properties = schema["properties"].as_object();
+            for (name, property) in object {
+                let property_schema = properties.and_then(|properties| properties.get(name));
+                let Some(property_schema) =
+                    property_schema.or_else(|| schema.get("additionalProperties"))
+                else {
+                    continue;
+                };
+                path.push(name.clone());
+                if *property_schema == Value::Bool(false) {
+                    problems.push(problem(path, "is not allowed".to_owned()));
+                } else {
+                    self.check_value(doc, property_schema, property, path, problems);
+                }
+                path.pop();
+            }
+        }
+    }
+
+    fn check_string(
+        &self,
+        schema: &Value,
+        string: &str,
+        path: &[String],
+        problems: &mut Vec<Problem>,
+    ) {
+        if let Some(max) = schema["maxLength"].as_u64() {
+            if string.chars().count() as u64 > max {
+                problems.push(problem(path, format!("is longer than {max} characters")));
+            }
+        }
+        if let Some(pattern) = schema["pattern"].as_str() {
+            let mut patterns = self.patterns.borrow_mut();
+            let regex = patterns
+                .entry(pattern.to_owned())
+                .or_insert_with(|| Regex::new(pattern).unwrap());
+            if !regex.is_match(string) {
+                problems.push(problem(
+                    path,
+                    format!("is {string:?}, which does not match {pattern}"),
+                ));
+            }
+        }
+    }
+
+    fn check_array<'a>(
+        &'a self,
+        doc: &'a Value,
+        schema: &'a Value,
+        items: &[Value],
+        path: &mut Vec<String>,
+        problems: &mut Vec<Problem>,
+    ) {
+        if let Some(min) = schema["minItems"].as_u64() {
+            if (items.len() as u64) < min {
+                problems.push(problem(path, format!("has fewer than {min} items")));
+            }
+        }
+        if let Some(max) = schema["maxItems"].as_u64() {
+            if items.len() as u64 > max {
+                problems.push(problem(path, format!("has more than {max} items")));
+            }
+        }
+        if schema["uniqueItems"] == true {
+            for (i, item) in items.iter().enumerate() {
+                if items[..i].contains(item) {
+                    path.push(i.to_string());
+                    problems.push(problem(path, "is the same as an item before it".to_owned()));
+                    path.pop();
+                }
+            }
+        }
+        for (i, item) in items.iter().enumerate() {
+            let item_schema = match &schema["items"] {
+                Value::Array(tuple) => match tuple.get(i) {
+                    Some(item_schema) => item_schema,
+                    None => match schema.get("additionalItems") {
+                        Some(Value::Bool(false)) => {
+                            problems.push(problem(
+                                path,
+                                format!("has more than {} items", tuple.len()),
+                            ));
+                            break;
+                        }
+                        Some(additional) => additional,
+                        None => continue,
+                    },
+                },
+                Value::Null => continue,
+                item_schema => item_schema,
+            };
+            path.push(i.to_string());
+            self.check_value(doc, item_schema, item, path, problems);
+            path.pop();
+        }
+    }
+
+    /// The value must match exactly one of the schemas. If it matches none, the problems
+    /// of the schema it got furthest into are reported, as it was most likely meant to
+    /// be that one.
+    fn check_one_of<'a>(
+        &'a self,
+        doc: &'a Value,
+        one_of: &'a [Value],
+        value: &Value,
+        path: &mut Vec<String>,
+        problems: &mut Vec<Problem>,
+    ) {
+        let results: Vec<Vec<Problem>> = one_of
+            .iter()
+            .map(|schema| {
+                let mut problems = Vec::new();
+                self.check_value(doc, schema, value, path, &mut problems);
+                problems
+            })
+            .collect();
+        let matches = results
+            .iter()
+            .filter(|problems| problems.is_empty())
+            .count();
+        if matches > 1 {
+            problems.push(problem(path, "matches more than one schema".to_owned()));
+        }
+        if matches > 0 {
+            return;
+        }
+        let depth = |problems: &Vec<Problem>| {
+            problems
+                .iter()
+                .map(|problem| problem.path.matches('/').count())
+                .max()
+                .unwrap_or_default()
+        };
+        let deepest = results.iter().map(depth).max().unwrap_or_default();
+        let mut furthest = results
+            .into_iter()
+            .filter(|problems| depth(problems) == deepest);
+        match (furthest.next(), furthest.next()) {
+            (Some(only), None) if deepest > path.len() => problems.extend(only),
+            _ => {
+                let names: Vec<String> = one_of.iter().map(describe).collect();
+                problems.push(problem(path, format!("is not {}", names.join(" or "))));
+            }
+        }
+    }
+
+    fn matches(&self, doc: &Value, schema: &Value, value: &Value, path: &mut Vec<String>) -> bool {
+        let mut problems = Vec::new();
+        self.check_value(doc, schema, value, path, &mut problems);
+        problems.is_empty()
+    }
+}
+
+fn describe(schema: &Value) -> String {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.rsplit('/').next().unwrap();
+        return format!("a {}", name.replace('_', " "));
+    }
+    match &schema["type"] {
+        Value::String(type_) => article(&[type_]),
+        _ => "an allowed value".to_owned(),
+    }
+}
+
+fn has_type(value: &Value, type_: &str) -> bool {
+    match type_ {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+fn article(types: &[&str]) -> String {
+    let types: Vec<String> = types
+        .iter()
+        .map(|type_| match *type_ {
+            "array" | "integer" | "object" => format!("an {type_}"),
+            _ => format!("a {type_}"),
+        })
+        .collect();
+    types.join(" or ")
+}
+
+fn problem(path: &[String], message: String) -> Problem {
+    Problem {
+        path: pointer(path),
+        message,
+    }
+}
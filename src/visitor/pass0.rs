@@ -1,8 +1,8 @@
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use glob::glob;
 use smol_str::SmolStr;
 
-use crate::ast::*;
+use crate::{ast::*, misc::in_source_order};
 
 struct V<'a> {
     locals: Option<&'a mut FxHashMap<SmolStr, Var>>,
@@ -12,7 +12,7 @@ struct V<'a> {
 
 pub fn visit_project(project: &mut Project) {
     visit_sprite(&mut project.stage, None);
-    for sprite in project.sprites.values_mut() {
+    for (_, sprite) in in_source_order(project.sprites.iter_mut(), |(name, _)| *name) {
         visit_sprite(sprite, Some(&mut project.stage));
     }
 }
@@ -22,7 +22,7 @@ fn visit_sprite(sprite: &mut Sprite, mut stage: Option<&mut Sprite>) {
     for enum_ in sprite.enums.values_mut() {
         visit_enum(enum_);
     }
-    for proc in sprite.procs.values_mut() {
+    for proc in in_source_order(sprite.procs.values_mut(), |proc| proc.span.start) {
         visit_stmts(
             &mut proc.body,
             &mut V {
@@ -32,7 +32,7 @@ fn visit_sprite(sprite: &mut Sprite, mut stage: Option<&mut Sprite>) {
             },
         );
     }
-    for func in sprite.funcs.values_mut() {
+    for func in in_source_order(sprite.funcs.values_mut(), |func| func.span.start) {
         visit_stmts(
             &mut func.body,
             &mut V {
@@ -52,6 +52,29 @@ fn visit_sprite(sprite: &mut Sprite, mut stage: Option<&mut Sprite>) {
             },
         );
     }
+    // Tests are visited last, so that variables which are also set outside of tests are
+    // already declared. Any other variable set in a test is made local to it.
+    for test in &mut sprite.tests {
+        let declared: FxHashSet<SmolStr> = sprite.vars.keys().cloned().collect();
+        visit_stmts(
+            &mut test.body,
+            &mut V {
+                locals: Some(&mut test.locals),
+                vars: &mut sprite.vars,
+                global_vars: stage.as_mut().map(|stage| &mut stage.vars),
+            },
+        );
+        let undeclared: Vec<SmolStr> = sprite
+            .vars
+            .keys()
+            .filter(|name| !declared.contains(*name))
+            .cloned()
+            .collect();
+        for name in undeclared {
+            let var = sprite.vars.remove(&name).unwrap();
+            test.locals.insert(name, var);
+        }
+    }
 }
 
 fn visit_enum(enum_: &mut Enum) {
@@ -125,6 +148,7 @@ fn visit_stmt(stmt: &mut Stmt, v: &mut V) {
                 span: name.span(),
                 type_: type_.clone(),
                 is_cloud: *is_cloud,
+                initial_value: None,
             };
             if *is_local {
                 if let Some(locals) = &mut v.locals {
@@ -163,8 +187,12 @@ fn visit_stmt(stmt: &mut Stmt, v: &mut V) {
                 // reassign it without a type (defaulting to `Value`). Only variables that are of type `Value` can be
                 // overwritten by the new assignment.
 
+                // Variables declared with `var` keep their declaration, and the value they
+                // start with.
                 // TODO: Make redeclaration of variables with different struct types an error.
-                if existing_declaration.type_.is_value() {
+                if existing_declaration.type_.is_value()
+                    && existing_declaration.initial_value.is_none()
+                {
                     v.vars.insert(basename.clone(), var);
                 }
             } else {
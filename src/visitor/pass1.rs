@@ -7,8 +7,8 @@ use crate::{
     ast::*,
     blocks::{BinOp, Block, UnOp},
     codegen::sb3::D,
-    diagnostic::{DiagnosticKind, SpriteDiagnostics},
-    misc::Rrc,
+    diagnostic::{suggest, DiagnosticKind, SpriteDiagnostics},
+    misc::{in_source_order, Rrc},
 };
 
 #[derive(Copy, Clone)]
@@ -57,7 +57,7 @@ pub fn visit_project(
     sprites_diagnostics: &mut FxHashMap<SmolStr, SpriteDiagnostics>,
 ) {
     visit_sprite(&mut project.stage, None, stage_diagnostics);
-    for (sprite_name, sprite) in &mut project.sprites {
+    for (sprite_name, sprite) in in_source_order(&mut project.sprites, |(name, _)| *name) {
         visit_sprite(
             sprite,
             Some(&project.stage),
@@ -67,7 +67,7 @@ pub fn visit_project(
 }
 
 fn visit_sprite(sprite: &mut Sprite, stage: Option<&Sprite>, d: D) {
-    for proc in sprite.procs.values_mut() {
+    for proc in in_source_order(sprite.procs.values_mut(), |proc| proc.span.start) {
         visit_stmts(
             &mut proc.body,
             S {
@@ -86,7 +86,7 @@ fn visit_sprite(sprite: &mut Sprite, stage: Option<&Sprite>, d: D) {
             true,
         );
     }
-    for func in sprite.funcs.values_mut() {
+    for func in in_source_order(sprite.funcs.values_mut(), |func| func.span.start) {
         visit_stmts(
             &mut func.body,
             S {
@@ -124,6 +124,25 @@ fn visit_sprite(sprite: &mut Sprite, stage: Option<&Sprite>, d: D) {
             true,
         );
     }
+    for test in &mut sprite.tests {
+        visit_stmts(
+            &mut test.body,
+            S {
+                args: None,
+                local_vars: Some(&test.locals),
+                vars: &sprite.vars,
+                lists: &sprite.lists,
+                enums: &sprite.enums,
+                structs: &sprite.structs,
+                global_vars: stage.map(|stage| &stage.vars),
+                global_lists: stage.map(|stage| &stage.lists),
+                global_enums: stage.map(|stage| &stage.enums),
+                global_structs: stage.map(|stage| &stage.structs),
+            },
+            d,
+            true,
+        );
+    }
 }
 
 fn visit_stmts(stmts: &mut Vec<Stmt>, s: S, d: D, top_level: bool) {
@@ -167,7 +186,8 @@ fn visit_stmts(stmts: &mut Vec<Stmt>, s: S, d: D, top_level: bool) {
             for replace in replace.into_iter().rev() {
                 stmts.insert(i, replace);
             }
-            i += len - 1;
+            i += len;
+            continue;
         }
         i += 1;
     }
@@ -262,6 +282,16 @@ fn visit_stmt(stmt: &mut Stmt, s: S, d: D) {
             }
         }
         Stmt::Return { value } => visit_expr(value, s, d),
+        Stmt::Assert {
+            cond,
+            message,
+            span: _,
+        } => {
+            visit_expr(cond, s, d);
+            if let Some(message) = message {
+                visit_expr(message, s, d);
+            }
+        }
     }
 }
 
@@ -295,13 +325,12 @@ fn visit_expr(expr: &mut Rrc<Expr>, s: S, d: D) {
             }
             None
         }
-        Expr::UnOp {
-            op: _,
-            span: _,
-            opr,
-        } => {
+        Expr::UnOp { op, span, opr } => {
             visit_expr(opr, s, d);
-            None
+            match op {
+                UnOp::Minus => Some(visit_expr_minus(span, opr)),
+                _ => None,
+            }
         }
         Expr::BinOp { op, span, lhs, rhs } => {
             visit_expr(lhs, s, d);
@@ -448,6 +477,31 @@ fn visit_expr_arg(s: S, name: &Name) -> Option<Rrc<Expr>> {
     )
 }
 
+/// There is no block for negation, so `-x` is `0 - x`, and a negated number is a number.
+fn visit_expr_minus(span: &Span, opr: &Rrc<Expr>) -> Rrc<Expr> {
+    let negated = match &*opr.borrow() {
+        Expr::Value {
+            value: Value::Int(int),
+            ..
+        } => Some(Value::Int(-int)),
+        Expr::Value {
+            value: Value::Float(float),
+            ..
+        } => Some(Value::Float(-float)),
+        _ => None,
+    };
+    match negated {
+        Some(value) => value.to_expr(span.start..opr.borrow().span().end).into(),
+        None => BinOp::Sub
+            .to_expr(
+                span.clone(),
+                Value::Int(0).to_expr(span.clone()).into(),
+                opr.clone(),
+            )
+            .into(),
+    }
+}
+
 fn visit_expr_bin_op_of(s: S, span: &Span, lhs: &Rrc<Expr>, rhs: &Rrc<Expr>) -> Option<Rrc<Expr>> {
     let Expr::Name(Name::Name { name, span }) = &*lhs.borrow() else {
         return None;
@@ -499,6 +553,7 @@ fn visit_expr_dot(d: D, lhs: &Rrc<Expr>, rhs: &SmolStr, rhs_span: &Span) -> Opti
             DiagnosticKind::StructDoesNotHaveField {
                 type_name: lhs_name.clone(),
                 field_name: rhs.clone(),
+                suggestion: suggest(rhs, fields.iter().map(|field| field.name.as_str())),
             },
             rhs_span,
         );
@@ -1,19 +1,28 @@
-use crate::{ast::*, misc::Rrc};
+use crate::{
+    ast::*,
+    misc::{in_source_order, Rrc},
+};
 
 pub fn visit_project(project: &mut Project) {
     let mut callsites = 0;
     visit_sprite(&mut project.stage, &mut callsites);
-    for sprite in project.sprites.values_mut() {
+    for (_, sprite) in in_source_order(project.sprites.iter_mut(), |(name, _)| *name) {
         visit_sprite(sprite, &mut callsites);
     }
+    // Tests are visited last, so that the call sites of the build are numbered the same
+    // with or without them.
+    visit_tests(&mut project.stage, &mut callsites);
+    for (_, sprite) in in_source_order(project.sprites.iter_mut(), |(name, _)| *name) {
+        visit_tests(sprite, &mut callsites);
+    }
 }
 
 fn visit_sprite(sprite: &mut Sprite, callsites: &mut usize) {
     let old_callsites = *callsites;
-    for proc in sprite.procs.values_mut() {
+    for proc in in_source_order(sprite.procs.values_mut(), |proc| proc.span.start) {
         visit_proc(proc, callsites);
     }
-    for func in sprite.funcs.values_mut() {
+    for func in in_source_order(sprite.funcs.values_mut(), |func| func.span.start) {
         visit_func(func, callsites);
     }
     for event in &mut sprite.events {
@@ -24,6 +33,16 @@ fn visit_sprite(sprite: &mut Sprite, callsites: &mut usize) {
     }
 }
 
+fn visit_tests(sprite: &mut Sprite, callsites: &mut usize) {
+    let old_callsites = *callsites;
+    for test in &mut sprite.tests {
+        visit_stmts(&mut test.body, callsites);
+    }
+    if *callsites != old_callsites {
+        visit_tests(sprite, callsites);
+    }
+}
+
 fn visit_proc(proc: &mut Proc, callsites: &mut usize) {
     visit_stmts(&mut proc.body, callsites);
 }
@@ -137,6 +156,16 @@ fn visit_stmt(stmt: &mut Stmt, callsites: &mut usize) -> Vec<Stmt> {
         Stmt::Return { value } => {
             visit_expr(value, &mut before, callsites);
         }
+        Stmt::Assert {
+            cond,
+            message,
+            span: _,
+        } => {
+            visit_expr(cond, &mut before, callsites);
+            if let Some(message) = message {
+                visit_expr(message, &mut before, callsites);
+            }
+        }
     }
     before
 }
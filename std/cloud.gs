@@ -0,0 +1,32 @@
+# Encode text as a number, so that it can be stored in a cloud variable, which can only
+# hold numbers. Each character is encoded as two digits. Scratch compares letters
+# ignoring case, so letters are decoded as lowercase, and characters which are not in
+# CLOUD_CHARSET are left out.
+
+%define CLOUD_CHARSET " abcdefghijklmnopqrstuvwxyz0123456789.,:;!?'\"()[]+-*/=<>_@#$%&"
+
+pub func cloud_encode(text) {
+    local result = "";
+    local i = 1;
+    repeat length $text {
+        local code = 1;
+        until code > length CLOUD_CHARSET or CLOUD_CHARSET[code] == $text[i] {
+            code += 1;
+        }
+        if code <= length CLOUD_CHARSET {
+            result = result & (code + 9);
+        }
+        i += 1;
+    }
+    return result;
+}
+
+pub func cloud_decode(number) {
+    local result = "";
+    local i = 1;
+    until i >= length $number {
+        result = result & CLOUD_CHARSET[($number[i] & $number[i + 1]) - 9];
+        i += 2;
+    }
+    return result;
+}
@@ -0,0 +1,67 @@
+# List algorithms. Lists cannot be passed to procedures, so these are macros, which are
+# used as statements inside a procedure, function or test, such as `sort!(scores);`.
+# Procedures which are not `nowarp` run them without screen refresh.
+
+# Reverse the order of the items of `items`.
+%macro reverse(items)
+local i = 1;
+local j = length items;
+until i >= j {
+    local swapped = items[i];
+    items[i] = items[j];
+    items[j] = swapped;
+    i += 1;
+    j -= 1;
+}
+%endmacro
+
+# Sort the items of `items` in ascending order, as compared by `<`. This is a Shell sort,
+# so it needs no other list.
+%macro sort(items)
+local gap = length items // 2;
+until gap < 1 {
+    local i = gap + 1;
+    until i > length items {
+        local value = items[i];
+        local j = i;
+        until j <= gap or items[j - gap] <= value {
+            items[j] = items[j - gap];
+            j -= gap;
+        }
+        items[j] = value;
+        i += 1;
+    }
+    gap = gap // 2;
+}
+%endmacro
+
+# Put the items of `items` in a random order.
+%macro shuffle(items)
+local i = length items;
+until i < 2 {
+    local j = random(1, i);
+    local swapped = items[i];
+    items[i] = items[j];
+    items[j] = swapped;
+    i -= 1;
+}
+%endmacro
+
+# Set the variable `index` to the position of `value` in `items`, which must be sorted,
+# or to 0 if `items` does not contain it.
+%macro binary_search(items, value, index)
+local low = 1;
+local high = length items;
+local target = value;
+index = 0;
+until low > high or index > 0 {
+    local middle = (low + high) // 2;
+    if items[middle] == target {
+        index = middle;
+    } elif items[middle] < target {
+        low = middle + 1;
+    } else {
+        high = middle - 1;
+    }
+}
+%endmacro
@@ -0,0 +1,113 @@
+# Math functions. Angles are in degrees, as in Scratch.
+
+pub func min(a, b) {
+    if $a < $b {
+        return $a;
+    }
+    return $b;
+}
+
+pub func max(a, b) {
+    if $a > $b {
+        return $a;
+    }
+    return $b;
+}
+
+# `x`, but no less than `low` and no more than `high`.
+pub func clamp(x, low, high) {
+    if $x < $low {
+        return $low;
+    }
+    if $x > $high {
+        return $high;
+    }
+    return $x;
+}
+
+# -1, 0 or 1.
+pub func sign(x) {
+    if $x > 0 {
+        return 1;
+    }
+    if $x < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+# The value `t` of the way from `a` to `b`, where `t` is from 0 to 1.
+pub func lerp(a, b, t) {
+    return $a + ($b - $a) * $t;
+}
+
+# `base` to the power of `exponent`. Whole exponents are exact, and work for negative
+# bases.
+pub func pow(base, exponent) {
+    if $exponent == round $exponent {
+        local result = 1;
+        local factor = $base;
+        local remaining = abs $exponent;
+        until remaining == 0 {
+            if remaining % 2 == 1 {
+                result = result * factor;
+            }
+            factor = factor * factor;
+            remaining = remaining // 2;
+        }
+        if $exponent < 0 {
+            return 1 / result;
+        }
+        return result;
+    }
+    return antiln($exponent * ln $base);
+}
+
+# The angle from the positive x axis to the point (`x`, `y`), from -180 to 180.
+pub func atan2(y, x) {
+    if $x > 0 {
+        return atan($y / $x);
+    }
+    if $x < 0 {
+        if $y < 0 {
+            return atan($y / $x) - 180;
+        }
+        return atan($y / $x) + 180;
+    }
+    if $y > 0 {
+        return 90;
+    }
+    if $y < 0 {
+        return -90;
+    }
+    return 0;
+}
+
+# A whole number which is 0 or more in lowercase hexadecimal, such as "ff" for 255.
+pub func to_hex(n) {
+    local value = floor $n;
+    if value == 0 {
+        return "0";
+    }
+    local hex = "";
+    until value == 0 {
+        hex = "0123456789abcdef"[value % 16 + 1] & hex;
+        value = value // 16;
+    }
+    return hex;
+}
+
+# The number written in hexadecimal in `text`, such as 255 for "ff" or "FF".
+pub func from_hex(text) {
+    local value = 0;
+    local i = 1;
+    repeat length $text {
+        local digit = 0;
+        until digit == 15 or "0123456789abcdef"[digit + 1] == $text[i] {
+            digit += 1;
+        }
+        value = value * 16 + digit;
+        i += 1;
+    }
+    return value;
+}
@@ -0,0 +1,132 @@
+# String functions. Characters are counted from 1, as in Scratch, and comparisons
+# ignore case, as Scratch's do.
+
+# The `count` characters of `text` from the character at `start`.
+pub func substring(text, start, count) {
+    local result = "";
+    local i = $start;
+    local stop = $start + $count;
+    if i < 1 {
+        i = 1;
+    }
+    if stop > length $text + 1 {
+        stop = length $text + 1;
+    }
+    until i >= stop {
+        result = result & $text[i];
+        i += 1;
+    }
+    return result;
+}
+
+# The position of the first `part` in `text`, or 0 if `text` does not contain it.
+pub func index_of(text, part) {
+    local i = 1;
+    repeat length $text - length $part + 1 {
+        if substring($text, i, length $part) == $part {
+            return i;
+        }
+        i += 1;
+    }
+    return 0;
+}
+
+pub func starts_with(text, prefix) {
+    return substring($text, 1, length $prefix) == $prefix;
+}
+
+pub func ends_with(text, suffix) {
+    return substring($text, length $text - length $suffix + 1, length $suffix)
+        == $suffix;
+}
+
+# `text` with every `pattern` in it replaced by `replacement`.
+pub func replace(text, pattern, replacement) {
+    local result = "";
+    local i = 1;
+    until i > length $text {
+        if length $pattern > 0
+            and substring($text, i, length $pattern) == $pattern {
+            result = result & $replacement;
+            i += length $pattern;
+        } else {
+            result = result & $text[i];
+            i += 1;
+        }
+    }
+    return result;
+}
+
+# Scratch compares letters ignoring case, so a letter's position in the alphabet is
+# found by comparison, and the letter of the other case is taken from that position.
+# This needs no costumes.
+pub func upper(text) {
+    local result = "";
+    local i = 1;
+    repeat length $text {
+        local char = $text[i];
+        if char in "abcdefghijklmnopqrstuvwxyz" {
+            local letter = 1;
+            until "abcdefghijklmnopqrstuvwxyz"[letter] == char {
+                letter += 1;
+            }
+            char = "ABCDEFGHIJKLMNOPQRSTUVWXYZ"[letter];
+        }
+        result = result & char;
+        i += 1;
+    }
+    return result;
+}
+
+pub func lower(text) {
+    local result = "";
+    local i = 1;
+    repeat length $text {
+        local char = $text[i];
+        if char in "abcdefghijklmnopqrstuvwxyz" {
+            local letter = 1;
+            until "abcdefghijklmnopqrstuvwxyz"[letter] == char {
+                letter += 1;
+            }
+            char = "abcdefghijklmnopqrstuvwxyz"[letter];
+        }
+        result = result & char;
+        i += 1;
+    }
+    return result;
+}
+
+# Split `text` at each `separator`, replacing the items of the list `items` with the
+# parts.
+%macro split(text, separator, items)
+delete items;
+local source = text;
+local sep = separator;
+local part = "";
+local i = 1;
+until i > length source {
+    if length sep > 0 and substring(source, i, length sep) == sep {
+        add part to items;
+        part = "";
+        i += length sep;
+    } else {
+        part = part & source[i];
+        i += 1;
+    }
+}
+add part to items;
+%endmacro
+
+# Join the items of the list `items` with `separator` between them, into the variable
+# `result`.
+%macro join(items, separator, result)
+result = "";
+local i = 1;
+repeat length items {
+    if i > 1 {
+        result = result & separator;
+    }
+    result = result & items[i];
+    i += 1;
+}
+%endmacro
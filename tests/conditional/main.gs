@@ -0,0 +1,57 @@
+costumes "blank.svg";
+
+%define FEATURE 2
+
+test "defines" {
+    assert TARGET == "turbowarp";
+    assert DEBUG == 1;
+    # -D overrides [defines].
+    assert LEVEL == 1;
+    assert FROM_CLI == 1;
+}
+
+test "if and elif" {
+    local branch = "";
+%if TARGET == "turbowarp" and LEVEL >= 2
+    branch = "high";
+%elif TARGET == "turbowarp"
+    branch = "low";
+%else
+    branch = "other";
+%endif
+    assert branch == "low";
+%if FEATURE > 1 or defined(MISSING)
+    branch = "feature";
+%endif
+    assert branch == "feature";
+%if 0
+    this is not ✓ valid code
+    %include missing.gs
+%endif
+}
+
+test "ifdef and ifndef" {
+    local found = 0;
+%ifdef FROM_CLI
+    found += 1;
+%endif
+%ifndef MISSING
+    found += 10;
+%endif
+%ifdef MISSING
+    found += 100;
+%endif
+    assert found == 11;
+}
+
+test "undef" {
+    local found = 0;
+%undef FEATURE
+%ifdef FEATURE
+    found = 1;
+%endif
+%if defined(FEATURE) or FEATURE != 0
+    found = 2;
+%endif
+    assert found == 0;
+}
@@ -0,0 +1,5 @@
+costumes "blank.svg" as "idle", "blank.svg" as "angry", "blank.svg" as "hurt";
+
+onflag {
+    show;
+}
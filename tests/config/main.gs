@@ -0,0 +1,16 @@
+costumes "blank.svg";
+
+var speed = 5;
+list inventory = ["sword", "shield"];
+
+proc count {
+    local i = 0;
+    repeat 3 {
+        i += 1;
+    }
+}
+
+onflag {
+    count;
+    speed += 1;
+}
@@ -0,0 +1,7 @@
+costumes "blank.svg" as "day", "blank.svg" as "night";
+
+var score = 0;
+
+onflag {
+    score = 10;
+}
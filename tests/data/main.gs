@@ -0,0 +1,45 @@
+costumes "blank.svg";
+
+struct Point { x, y, z }
+
+list names = csv ```names.csv```;
+list Point csv_points = csv ```points.csv```;
+list Point tsv_points = tsv ```points.tsv```;
+list scores = json ```scores.json```;
+list Point json_points = json ```points.json```;
+
+test "csv" {
+    assert length names == 3;
+    assert names[1] == "ada";
+    assert names[2] == "Smith, J";
+    assert names[3] == "say \"hi\"";
+}
+
+test "csv struct list" {
+    assert length csv_points == 2;
+    assert csv_points[1].x == 1;
+    assert csv_points[1].y == 2;
+    assert csv_points[1].z == 3;
+    assert csv_points[2].x == 4;
+    assert csv_points[2].z == 6;
+}
+
+test "tsv struct list" {
+    assert length tsv_points == 1;
+    assert tsv_points[1].x == 7;
+    assert tsv_points[1].z == 9;
+}
+
+test "json" {
+    assert length scores == 4;
+    assert scores[2] == 25.5;
+    assert scores[3] == "ten";
+    assert scores[4] == "true";
+}
+
+test "json struct list" {
+    assert length json_points == 2;
+    assert json_points[1].y == 2;
+    assert json_points[2].x == "a";
+    assert json_points[2].z == "c";
+}
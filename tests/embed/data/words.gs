@@ -0,0 +1,3 @@
+func words() {
+    return %embed "words.txt";
+}
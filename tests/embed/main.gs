@@ -0,0 +1,28 @@
+costumes "blank.svg";
+%include data/words.gs
+
+var level = %embed "data/level.txt";
+list rows = %embed lines "data/level.txt";
+list digits = %embed split "," "data/digits.txt";
+
+test "text" {
+    assert level == "ab\ncd\n";
+    assert words() == "hello world";
+}
+
+test "base64 and hex" {
+    assert %embed base64 "data/bytes.bin" == "/wAB";
+    assert %embed hex "data/bytes.bin" == "ff0001";
+}
+
+test "lines" {
+    assert length rows == 2;
+    assert rows[1] == "ab";
+    assert rows[2] == "cd";
+}
+
+test "split" {
+    assert length digits == 3;
+    assert digits[1] == 3;
+    assert digits[3] == 4;
+}
@@ -0,0 +1,5 @@
+costumes "blank.svg";
+
+onflag {
+    say middle_missing;
+}
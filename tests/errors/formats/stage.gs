@@ -0,0 +1,5 @@
+costumes "blank.svg";
+
+onflag {
+    say stage_missing;
+}
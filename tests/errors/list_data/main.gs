@@ -0,0 +1,13 @@
+costumes "blank.svg";
+
+struct Point { x, y }
+
+list two_columns = csv ```two_columns.csv```;
+list Point no_y = csv ```no_y.csv```;
+list Point short_row = csv ```short_row.csv```;
+list unclosed = csv ```unclosed.csv```;
+list object = json ```object.json```;
+list nested = json ```nested.json```;
+list Point no_key = json ```no_key.json```;
+list broken = json ```broken.json```;
+list missing = tsv ```missing.tsv```;
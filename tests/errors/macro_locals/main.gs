@@ -0,0 +1,12 @@
+costumes "blank.svg";
+
+%macro swap(a, b)
+local tmp = a;
+a = b;
+b = tmp;
+%endmacro
+
+# Locals are only declared in procedures, functions and tests.
+onflag {
+    swap!(x, y);
+}
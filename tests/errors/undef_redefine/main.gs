@@ -0,0 +1,26 @@
+costumes "blank.svg";
+
+%define SPEED 10
+%macro double(x)
+x * 2
+%endmacro
+
+onflag {
+    move SPEED;
+    move double!(SPEED);
+}
+
+%undef SPEED
+%undef double
+
+# Macros are substituted once the whole sprite is preprocessed, so these would change the
+# uses above too.
+%define SPEED 20
+%macro double(x)
+x + x
+%endmacro
+
+onflag {
+    move SPEED;
+    move double!(SPEED);
+}
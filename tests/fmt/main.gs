@@ -0,0 +1,32 @@
+costumes "blank.svg";
+
+# `tools/run test` checks that `goboscript fmt` leaves this file as it is.
+
+func classify(n) {
+    if $n > 0
+        and $n < 10 {
+        return "digit";
+    } elif $n >= 10
+        and $n < 100 {
+        return "tens";
+    } else {
+        return "other";
+    }
+}
+
+func count_until(limit) {
+    local i = 0;
+    until i >= $limit
+        or i >= 100 {
+        i += 1;
+    }
+    return i;
+}
+
+test "two-line conditions" {
+    assert classify(5) == "digit";
+    assert classify(42) == "tens";
+    assert classify(-1) == "other";
+    assert count_until(3) == 3;
+    assert count_until(1000) == 100;
+}
@@ -0,0 +1,6 @@
+# Found next to this file.
+%include b.gs
+
+func from_a() {
+    return "a" & from_b();
+}
@@ -0,0 +1,6 @@
+# Not next to this file, so found in the project directory.
+%include util/common.gs
+
+func from_b() {
+    return "b" & from_common();
+}
@@ -0,0 +1,20 @@
+costumes "blank.svg";
+%include lib/a.gs
+# Included once, however many times and by whichever path.
+%include lib/b.gs
+%include ./lib/../lib/a.gs
+%include shapes/*.gs
+%include vendored.gs
+
+test "relative to the including file" {
+    assert from_a() == "abc";
+}
+
+test "glob" {
+    assert circle_sides() == 0;
+    assert square_sides() == 4;
+}
+
+test "include paths" {
+    assert from_vendor() == "v";
+}
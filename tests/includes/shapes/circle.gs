@@ -0,0 +1,3 @@
+func circle_sides() {
+    return 0;
+}
@@ -0,0 +1,3 @@
+func square_sides() {
+    return 4;
+}
@@ -0,0 +1,3 @@
+func from_common() {
+    return "c";
+}
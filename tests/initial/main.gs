@@ -0,0 +1,46 @@
+costumes "blank.svg";
+
+struct Point { x, y }
+
+var score = 10;
+var name = "gobo";
+var temp = -2.5;
+var Point origin = Point { y: 3, x: -1 };
+var Point corner = Point { x: 5 };
+list primes = [2, 3, 5, 7];
+list names = ["a", "b c"];
+list Point path = [Point { x: 1, y: 2 }, Point { x: 3, y: -4 }];
+
+test "variables" {
+    assert score == 10;
+    assert name == "gobo";
+    assert temp == -2.5;
+    assert high_score == 100;
+    score += 1;
+    assert score == 11;
+}
+
+test "struct variables" {
+    assert origin.x == -1;
+    assert origin.y == 3;
+    assert corner.x == 5;
+    assert corner.y == 0;
+}
+
+test "lists" {
+    assert length primes == 4;
+    assert primes[1] == 2;
+    assert primes[4] == 7;
+    assert names[2] == "b c";
+}
+
+test "struct lists" {
+    assert length path == 2;
+    assert path[1].x == 1;
+    assert path[2].x == 3;
+    assert path[2].y == -4;
+}
+
+test "fresh values" {
+    assert score == 10;
+}
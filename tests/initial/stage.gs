@@ -0,0 +1,3 @@
+costumes "blank.svg";
+
+var high_score = 100;
@@ -0,0 +1,58 @@
+costumes "blank.svg";
+
+%define SQUARE(x) ((x) * (x))
+%define clamp(x, low, high) \
+    (low * (x < low) + high * (x > high) + \
+     x * (x >= low and x <= high))
+
+%macro swap(a, b)
+local tmp = a;
+a = b;
+b = tmp;
+%endmacro
+
+%macro add_twice(list_name, value)
+add value to list_name;
+add value to list_name;
+%endmacro
+
+list items;
+
+test "function-like macros" {
+    assert SQUARE!(1 + 2) == 9;
+    assert SQUARE!(SQUARE!(2)) == 16;
+}
+
+test "multi-line define" {
+    assert clamp!(5, 0, 10) == 5;
+    assert clamp!(-3, 0, 10) == 0;
+    assert clamp!(12, 0, 10) == 10;
+}
+
+test "multi-line macro" {
+    delete items;
+    add_twice!(items, "a");
+    assert length items == 2;
+    assert items[2] == "a";
+}
+
+test "hygiene" {
+    local x = 1;
+    local y = 2;
+    local tmp = "outer";
+    swap!(x, y);
+    assert x == 2;
+    assert y == 1;
+    swap!(x, y);
+    assert x == 1;
+    assert y == 2;
+    assert tmp == "outer";
+}
+
+test "hygiene with the macro's own names" {
+    local tmp = 3;
+    local other = 4;
+    swap!(tmp, other);
+    assert tmp == 4;
+    assert other == 3;
+}
@@ -0,0 +1,32 @@
+import "std/math" as math;
+
+pub struct Point { x, y }
+
+pub enum Side { Left, Right }
+
+var measured = 0;
+
+func square(n) {
+    return $n * $n;
+}
+
+pub func distance(Point a, Point b) {
+    measured += 1;
+    return sqrt(square($a.x - $b.x) + square($a.y - $b.y));
+}
+
+pub func side(Point p) {
+    if math::sign($p.x) < 0 {
+        return Side.Left;
+    }
+    return Side.Right;
+}
+
+pub func measurements() {
+    return measured;
+}
+
+pub proc move_by Point p {
+    local measured = $p.x + $p.y;
+    change_x measured;
+}
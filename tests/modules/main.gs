@@ -0,0 +1,19 @@
+costumes "blank.svg";
+import "lib/geometry.gs" as geo;
+
+func square(n) {
+    return "square";
+}
+
+onflag {
+    geo::Point origin = geo::Point { x: 0, y: 0 };
+    say geo::distance(origin, geo::Point { x: 3, y: 4 });
+    geo::move_by origin;
+}
+
+test "module" {
+    assert geo::distance(geo::Point { x: 0, y: 0 }, geo::Point { x: 3, y: 4 }) == 5;
+    assert geo::measurements() == 1;
+    assert geo::side(geo::Point { x: -1, y: 0 }) == geo::Side.Left;
+    assert square(2) == "square";
+}
@@ -0,0 +1,94 @@
+# Scratch compares text ignoring case, but switches costumes by exact name, so these
+# costumes tell the case of the text `upper` and `lower` return.
+costumes "blank.svg", "blank.svg" as "Hello, World!", "blank.svg" as "HELLO, WORLD!",
+    "blank.svg" as "hello, world!";
+
+%include std/math
+%include std/string
+%include std/list
+%include std/cloud
+
+list numbers;
+list parts;
+
+test "math" {
+    assert min(1, 2) == 1;
+    assert max(1, 2) == 2;
+    assert clamp(5, 0, 3) == 3;
+    assert clamp(-5, 0, 3) == 0;
+    assert sign(-3) == -1;
+    assert sign(0) == 0;
+    assert lerp(0, 10, 0.5) == 5;
+    assert pow(2, 10) == 1024;
+    assert pow(-2, 3) == -8;
+    assert pow(2, -1) == 0.5;
+    assert round (pow(9, 0.5) * 1000) == 3000;
+    assert atan2(1, 1) == 45;
+    assert atan2(1, -1) == 135;
+    assert atan2(-1, -1) == -135;
+    assert to_hex(255) == "ff";
+    assert to_hex(0) == "0";
+    assert from_hex("FF") == 255;
+    assert from_hex("1a") == 26;
+}
+
+test "string" {
+    assert substring("goboscript", 5, 6) == "script";
+    assert substring("gobo", 3, 10) == "bo";
+    assert substring("gobo", 0, 2) == "g";
+    assert index_of("goboscript", "script") == 5;
+    assert index_of("goboscript", "x") == 0;
+    assert starts_with("goboscript", "gobo");
+    assert not starts_with("goboscript", "script");
+    assert ends_with("goboscript", "script");
+    assert replace("a-b-c", "-", ", ") == "a, b, c";
+    assert replace("aaa", "aa", "b") == "ba";
+    switch_costume upper("Hello, World!");
+    assert costume_number() == 3;
+    switch_costume lower("Hello, World!");
+    assert costume_number() == 4;
+    switch_costume upper("hello, world!");
+    assert costume_number() == 3;
+}
+
+test "split and join" {
+    local text = "";
+    split!("a,bc,,d", ",", parts);
+    assert length parts == 4;
+    assert parts[2] == "bc";
+    assert parts[3] == "";
+    join!(parts, "+", text);
+    assert text == "a+bc++d", text;
+}
+
+test "list" {
+    delete numbers;
+    add 5 to numbers;
+    add 3 to numbers;
+    add 10 to numbers;
+    add 1 to numbers;
+    add 4 to numbers;
+    sort!(numbers);
+    local text = "";
+    join!(numbers, " ", text);
+    assert text == "1 3 4 5 10", text;
+    local index = -1;
+    binary_search!(numbers, 5, index);
+    assert index == 4;
+    binary_search!(numbers, 2, index);
+    assert index == 0;
+    reverse!(numbers);
+    join!(numbers, " ", text);
+    assert text == "10 5 4 3 1", text;
+    shuffle!(numbers);
+    assert length numbers == 5;
+    sort!(numbers);
+    join!(numbers, " ", text);
+    assert text == "1 3 4 5 10", text;
+}
+
+test "cloud" {
+    local encoded = cloud_encode("hi, gobo!");
+    assert encoded == encoded + 0, encoded;
+    assert cloud_decode(encoded) == "hi, gobo!", cloud_decode(encoded);
+}
@@ -0,0 +1,53 @@
+costumes "blank.svg";
+
+list stack;
+
+proc push value {
+    add $value to stack;
+}
+
+proc pop {
+    popped = stack[length(stack)];
+    delete stack[length(stack)];
+}
+
+proc check_pop expected {
+    pop;
+    assert popped == $expected, "expected " & $expected & ", got " & popped;
+}
+
+func sum(a, b) {
+    return $a + $b;
+}
+
+onflag {
+    push 1;
+    say sum(1, 2);
+    if length(stack) > 0 {
+        assert stack[1] == 1;
+    }
+}
+
+test "sum" {
+    assert sum(1, 2) == 3, "1 + 2 should be 3";
+    assert sum("1", "2") == 3;
+}
+
+test "stack" {
+    push "a";
+    push "b";
+    pop;
+    assert popped == "b", "expected b, got " & popped;
+    assert length(stack) == 1;
+}
+
+test "stack in a proc" {
+    push "c";
+    check_pop "c";
+}
+
+test "stack starts empty" {
+    local expected = 0;
+    assert length(stack) == expected;
+}
+
@@ -0,0 +1,34 @@
+"""Checks that a project.json has the fields given in an expected.json.
+
+expected.json has the fields of each target, by target name, and of each monitor, by
+monitor ID. Fields which are not given are not checked.
+"""
+
+import json
+import sys
+
+project_path, expected_path = sys.argv[1:]
+with open(project_path) as file:
+    project = json.load(file)
+with open(expected_path) as file:
+    expected = json.load(file)
+
+found = {
+    "targets": {target["name"]: target for target in project["targets"]},
+    "monitors": {monitor["id"]: monitor for monitor in project["monitors"]},
+}
+problems = []
+for kind, items in expected.items():
+    for name, fields in items.items():
+        item = found[kind].get(name)
+        if item is None:
+            problems.append(f"{kind}: {name} is missing")
+            continue
+        for field, value in fields.items():
+            if item.get(field) != value:
+                problems.append(
+                    f"{kind}: {name}: {field} is {item.get(field)!r}, not {value!r}"
+                )
+for problem in problems:
+    print(problem)
+sys.exit(1 if problems else 0)